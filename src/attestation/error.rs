@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt, time::Duration};
+
+/// Error that may occur when verifying an attestation document.
+#[derive(Debug)]
+pub enum AttestationError {
+    /// The document is not a well-formed COSE_Sign1 structure or payload.
+    Malformed(String),
+
+    /// The document is signed with an algorithm other than ECDSA with SHA-384.
+    UnsupportedAlgorithm,
+
+    /// A certificate of the document's chain is malformed, expired or incorrectly signed.
+    Certificate(String),
+
+    /// The document's certificate chain does not start at the trusted root.
+    UntrustedRoot,
+
+    /// The document's signature does not verify with its certificate.
+    Signature,
+
+    /// The document is older than the maximum age allowed.
+    Stale(Duration),
+
+    /// The document is dated further ahead of the current time than clock skew allows.
+    FutureTimestamp(Duration),
+
+    /// A PCR does not have the value required by the policy.
+    PcrMismatch(usize),
+
+    /// The document does not include the nonce required by the policy.
+    NonceMismatch,
+}
+
+impl fmt::Display for AttestationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Malformed(e) => format!("malformed attestation document: {e}"),
+            Self::UnsupportedAlgorithm => {
+                "attestation document signature algorithm is not ES384".to_string()
+            }
+            Self::Certificate(e) => format!("invalid attestation certificate chain: {e}"),
+            Self::UntrustedRoot => {
+                "attestation certificate chain does not start at the trusted root".to_string()
+            }
+            Self::Signature => "invalid attestation document signature".to_string(),
+            Self::Stale(age) => format!("attestation document is {}s old", age.as_secs()),
+            Self::FutureTimestamp(ahead) => {
+                format!(
+                    "attestation document is dated {}s in the future",
+                    ahead.as_secs()
+                )
+            }
+            Self::PcrMismatch(pcr) => format!("PCR{pcr} does not match policy"),
+            Self::NonceMismatch => "attestation document nonce does not match".to_string(),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
@@ -0,0 +1,306 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Parsing and verification of NSM attestation documents: COSE_Sign1 structures, signed with
+//! ECDSA P-384 by a certificate chaining up to a trusted root, whose CBOR payload holds the
+//! enclave's PCRs along with any requested public key, user data and nonce.
+
+mod error;
+mod types;
+
+pub use error::*;
+pub use types::*;
+
+use ciborium::Value;
+use ring::signature::{UnparsedPublicKey, ECDSA_P384_SHA384_FIXED};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use x509_parser::{certificate::X509Certificate, prelude::FromDer, time::ASN1Time};
+
+type Result<T> = std::result::Result<T, AttestationError>;
+
+// COSE algorithm identifier of ECDSA with SHA-384.
+const COSE_ALG_ES384: i64 = -35;
+
+// COSE header parameter holding the algorithm.
+const COSE_HEADER_ALG: i64 = 1;
+
+// CBOR tag of COSE_Sign1 structures.
+const COSE_SIGN1_TAG: u64 = 18;
+
+// Time by which a document may be dated ahead of the verifier's clock.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(30);
+
+impl AttestationDoc {
+    /// Decode the payload of an attestation document without verifying it.
+    pub fn parse(document: &[u8]) -> Result<Self> {
+        let (_, payload, _) = decode_sign1(document)?;
+
+        Self::from_payload(&payload)
+    }
+
+    /// Verify an attestation document against a policy at the given time, returning its payload
+    /// once trusted.
+    pub fn verify(document: &[u8], policy: &VerifyPolicy, now: SystemTime) -> Result<Self> {
+        let (protected, payload, signature) = decode_sign1(document)?;
+
+        let header: Value = ciborium::from_reader(&protected[..])
+            .map_err(|e| AttestationError::Malformed(e.to_string()))?;
+        let alg = header
+            .as_map()
+            .and_then(|m| m.iter().find(|(k, _)| integer(k) == Some(COSE_HEADER_ALG)))
+            .and_then(|(_, v)| integer(v));
+        if alg != Some(COSE_ALG_ES384) {
+            return Err(AttestationError::UnsupportedAlgorithm);
+        }
+
+        let doc = Self::from_payload(&payload)?;
+        if doc.digest != "SHA384" {
+            return Err(AttestationError::Malformed(format!(
+                "unsupported digest {}",
+                doc.digest
+            )));
+        }
+
+        // The chain runs from the root, through the intermediates, to the signing certificate.
+        if doc.cabundle.first() != Some(&policy.root) {
+            return Err(AttestationError::UntrustedRoot);
+        }
+        let chain = doc
+            .cabundle
+            .iter()
+            .chain([&doc.certificate])
+            .map(|der| {
+                X509Certificate::from_der(der)
+                    .map(|(_, cert)| cert)
+                    .map_err(|e| AttestationError::Certificate(e.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        verify_chain(&chain, now)?;
+
+        let key = &chain[chain.len() - 1].public_key().subject_public_key.data;
+        UnparsedPublicKey::new(&ECDSA_P384_SHA384_FIXED, key)
+            .verify(&sig_structure(&protected, &payload), &signature)
+            .map_err(|_| AttestationError::Signature)?;
+
+        let produced = UNIX_EPOCH + Duration::from_millis(doc.timestamp);
+        let age = match now.duration_since(produced) {
+            Ok(age) => age,
+            Err(e) if e.duration() <= MAX_CLOCK_SKEW => Duration::ZERO,
+            Err(e) => return Err(AttestationError::FutureTimestamp(e.duration())),
+        };
+        if age > policy.max_age {
+            return Err(AttestationError::Stale(age));
+        }
+
+        for (pcr, value) in &policy.pcrs {
+            if doc.pcrs.get(pcr) != Some(value) {
+                return Err(AttestationError::PcrMismatch(*pcr));
+            }
+        }
+
+        if policy.nonce.is_some() && doc.nonce != policy.nonce {
+            return Err(AttestationError::NonceMismatch);
+        }
+
+        Ok(doc)
+    }
+
+    /// Encode the document as a COSE_Sign1 payload.
+    pub(crate) fn to_payload(&self) -> Vec<u8> {
+        let text = |s: &str| Value::Text(s.to_string());
+        let bytes = |b: &[u8]| Value::Bytes(b.to_vec());
+        let optional = |b: &Option<Vec<u8>>| b.as_deref().map_or(Value::Null, bytes);
+
+        let map = Value::Map(vec![
+            (text("module_id"), text(&self.module_id)),
+            (text("digest"), text(&self.digest)),
+            (text("timestamp"), Value::Integer(self.timestamp.into())),
+            (
+                text("pcrs"),
+                Value::Map(
+                    self.pcrs
+                        .iter()
+                        .map(|(i, v)| (Value::Integer((*i as u64).into()), bytes(v)))
+                        .collect(),
+                ),
+            ),
+            (text("certificate"), bytes(&self.certificate)),
+            (
+                text("cabundle"),
+                Value::Array(self.cabundle.iter().map(|c| bytes(c)).collect()),
+            ),
+            (text("public_key"), optional(&self.public_key)),
+            (text("user_data"), optional(&self.user_data)),
+            (text("nonce"), optional(&self.nonce)),
+        ]);
+
+        let mut payload = Vec::new();
+        ciborium::into_writer(&map, &mut payload).unwrap();
+
+        payload
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self> {
+        let malformed = |e: &str| AttestationError::Malformed(e.to_string());
+
+        let value: Value = ciborium::from_reader(payload).map_err(|e| malformed(&e.to_string()))?;
+        let map = value
+            .as_map()
+            .ok_or_else(|| malformed("payload is not a map"))?;
+        let get = |name: &str| {
+            map.iter()
+                .find(|(k, _)| k.as_text() == Some(name))
+                .map(|(_, v)| v)
+                .filter(|v| !v.is_null())
+        };
+        let text = |name: &str| {
+            get(name)
+                .and_then(Value::as_text)
+                .map(String::from)
+                .ok_or_else(|| malformed(&format!("missing {name}")))
+        };
+        let bytes = |name: &str| {
+            get(name)
+                .and_then(Value::as_bytes)
+                .cloned()
+                .ok_or_else(|| malformed(&format!("missing {name}")))
+        };
+        let optional = |name: &str| get(name).and_then(Value::as_bytes).cloned();
+
+        let timestamp = get("timestamp")
+            .and_then(integer)
+            .and_then(|t| u64::try_from(t).ok())
+            .ok_or_else(|| malformed("missing timestamp"))?;
+
+        let pcrs = get("pcrs")
+            .and_then(Value::as_map)
+            .ok_or_else(|| malformed("missing pcrs"))?
+            .iter()
+            .map(|(k, v)| {
+                let index = integer(k).and_then(|i| usize::try_from(i).ok());
+                match (index, v.as_bytes()) {
+                    (Some(index), Some(value)) => Ok((index, value.clone())),
+                    _ => Err(malformed("invalid pcr")),
+                }
+            })
+            .collect::<Result<_>>()?;
+
+        let cabundle = get("cabundle")
+            .and_then(Value::as_array)
+            .ok_or_else(|| malformed("missing cabundle"))?
+            .iter()
+            .map(|c| {
+                c.as_bytes()
+                    .cloned()
+                    .ok_or_else(|| malformed("invalid cabundle"))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            module_id: text("module_id")?,
+            digest: text("digest")?,
+            timestamp,
+            pcrs,
+            certificate: bytes("certificate")?,
+            cabundle,
+            public_key: optional("public_key"),
+            user_data: optional("user_data"),
+            nonce: optional("nonce"),
+        })
+    }
+}
+
+/// Encode a COSE_Sign1 structure signed with ECDSA P-384, given a function signing its
+/// Sig_structure.
+pub(crate) fn encode_sign1<E>(
+    payload: Vec<u8>,
+    sign: impl FnOnce(&[u8]) -> std::result::Result<Vec<u8>, E>,
+) -> std::result::Result<Vec<u8>, E> {
+    let header = Value::Map(vec![(
+        Value::Integer(COSE_HEADER_ALG.into()),
+        Value::Integer(COSE_ALG_ES384.into()),
+    )]);
+    let mut protected = Vec::new();
+    ciborium::into_writer(&header, &mut protected).unwrap();
+
+    let signature = sign(&sig_structure(&protected, &payload))?;
+
+    let sign1 = Value::Array(vec![
+        Value::Bytes(protected),
+        Value::Map(Vec::new()),
+        Value::Bytes(payload),
+        Value::Bytes(signature),
+    ]);
+    let mut document = Vec::new();
+    ciborium::into_writer(&sign1, &mut document).unwrap();
+
+    Ok(document)
+}
+
+/// Decode a COSE_Sign1 structure into its protected header, payload and signature.
+fn decode_sign1(document: &[u8]) -> Result<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+    let value: Value =
+        ciborium::from_reader(document).map_err(|e| AttestationError::Malformed(e.to_string()))?;
+
+    // The structure may or may not be tagged.
+    let value = match value {
+        Value::Tag(COSE_SIGN1_TAG, inner) => *inner,
+        value => value,
+    };
+
+    match value.into_array().as_deref() {
+        Ok(
+            [Value::Bytes(protected), Value::Map(_), Value::Bytes(payload), Value::Bytes(signature)],
+        ) => Ok((protected.clone(), payload.clone(), signature.clone())),
+        _ => Err(AttestationError::Malformed(
+            "not a COSE_Sign1 structure".to_string(),
+        )),
+    }
+}
+
+/// Encode the structure signed by a COSE_Sign1 signature, without external data.
+fn sig_structure(protected: &[u8], payload: &[u8]) -> Vec<u8> {
+    let value = Value::Array(vec![
+        Value::Text("Signature1".to_string()),
+        Value::Bytes(protected.to_vec()),
+        Value::Bytes(Vec::new()),
+        Value::Bytes(payload.to_vec()),
+    ]);
+
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&value, &mut bytes).unwrap();
+
+    bytes
+}
+
+/// Verify that each certificate of a chain is signed by its predecessor, that all but the last
+/// are CAs, and that all are valid at the given time.
+fn verify_chain(chain: &[X509Certificate], now: SystemTime) -> Result<()> {
+    let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let now = ASN1Time::from_timestamp(secs as i64)
+        .map_err(|e| AttestationError::Certificate(e.to_string()))?;
+
+    for (i, cert) in chain.iter().enumerate() {
+        let subject = cert.subject();
+        let issuer = i.checked_sub(1).map_or(cert, |i| &chain[i]);
+
+        if !cert.validity().is_valid_at(now) {
+            return Err(AttestationError::Certificate(format!(
+                "{subject} is not valid at this time"
+            )));
+        }
+        if i + 1 < chain.len() && !cert.is_ca() {
+            return Err(AttestationError::Certificate(format!(
+                "{subject} is not a CA"
+            )));
+        }
+        cert.verify_signature(Some(issuer.public_key()))
+            .map_err(|_| AttestationError::Certificate(format!("{subject} signature invalid")))?;
+    }
+
+    Ok(())
+}
+
+/// Get the value of a CBOR integer.
+fn integer(value: &Value) -> Option<i64> {
+    value.as_integer().and_then(|i| i64::try_from(i).ok())
+}
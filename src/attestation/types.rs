@@ -0,0 +1,64 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::BTreeMap, time::Duration};
+
+/// Payload of an attestation document.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AttestationDoc {
+    /// ID of the NSM that produced the document.
+    pub module_id: String,
+
+    /// Digest function used to compute the PCRs (always SHA384).
+    pub digest: String,
+
+    /// Time at which the document was produced, in milliseconds since the Unix epoch.
+    pub timestamp: u64,
+
+    /// Platform configuration registers, by index.
+    pub pcrs: BTreeMap<usize, Vec<u8>>,
+
+    /// DER-encoded certificate whose key signed the document.
+    pub certificate: Vec<u8>,
+
+    /// DER-encoded certificates from the root to the signing certificate's issuer.
+    pub cabundle: Vec<Vec<u8>>,
+
+    /// Public key of the enclave, if requested.
+    pub public_key: Option<Vec<u8>>,
+
+    /// Application data, if requested.
+    pub user_data: Option<Vec<u8>>,
+
+    /// Verifier nonce, if requested.
+    pub nonce: Option<Vec<u8>>,
+}
+
+/// Requirements that an attestation document must meet to be trusted.
+#[derive(Clone, Debug)]
+pub struct VerifyPolicy {
+    /// DER-encoded root certificate that the document's certificate chain must start at. For
+    /// documents produced by the NSM, this is the AWS Nitro Enclaves root certificate.
+    pub root: Vec<u8>,
+
+    /// Values that PCRs must have, by index. PCRs not listed may have any value.
+    pub pcrs: BTreeMap<usize, Vec<u8>>,
+
+    /// Maximum age of the document.
+    pub max_age: Duration,
+
+    /// Nonce that the document must include, if any.
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl VerifyPolicy {
+    /// Trust documents chaining up to root, produced within the last 5 minutes, regardless of
+    /// their PCRs.
+    pub fn new(root: Vec<u8>) -> Self {
+        Self {
+            root,
+            pcrs: BTreeMap::new(),
+            max_age: Duration::from_secs(300),
+            nonce: None,
+        }
+    }
+}
@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Init process of enclave images: prepares the enclave, signals readiness to the parent instance
+//! and runs the application read from /cmd and /env, rooted at /rootfs, powering the enclave off
+//! once it exits.
+
+use nitro_enclaves::init;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    // init() only returns if initialization, running the application or powering off failed.
+    let Err(e) = init::init();
+    eprintln!("enclave-init: {e}");
+
+    ExitCode::FAILURE
+}
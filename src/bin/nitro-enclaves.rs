@@ -0,0 +1,293 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Command line utilities for AWS Nitro Enclaves.
+
+use nitro_enclaves::{
+    eif::{Eif, EifMetadata, KernelCmdline, SectionType},
+    forward::ShutdownHandle,
+    oci::Image,
+    proxy::{Allowlist, IpVersion, Proxy, ProxyConfig},
+};
+use nix::sys::signal::{SigSet, Signal};
+use std::{process::ExitCode, thread};
+use vsock::{VsockListener, VMADDR_CID_ANY};
+
+const DEFAULT_PROXY_CONFIG: &str = "/etc/nitro_enclaves/vsock-proxy.yaml";
+
+const USAGE: &str = "\
+Usage: nitro-enclaves <COMMAND>
+
+Commands:
+  proxy <LOCAL_PORT> <REMOTE_HOST> <REMOTE_PORT> [OPTIONS]
+      Forward connections on a vsock port to an allowed TCP host.
+
+      --config <FILE>          Allowlist configuration [default: /etc/nitro_enclaves/vsock-proxy.yaml]
+      --ipv4                   Only connect to the remote host over IPv4
+      --ipv6                   Only connect to the remote host over IPv6
+      --max-connections <N>    Maximum number of connections forwarded at once
+
+  build-eif --image <PATH> --kernel <FILE> --init <FILE> --output <FILE> [OPTIONS]
+      Convert an OCI image layout, or a tarball of one or of a docker save archive, into an EIF.
+
+      --cmdline <CMDLINE>      Kernel command line [default: that of the Nitro Enclaves kernels]
+      --arch <ARCH>            Architecture of the manifest to use (e.g. arm64) [default: host's]
+      --name <NAME>            Image name recorded in the metadata section
+      --version <VERSION>      Image version recorded in the metadata section [default: latest]
+      --metadata <KEY=VALUE>   Custom metadata field (may be repeated)
+
+  diff-eif <OLD> <NEW>
+      Report the differences between two EIFs, per section and file, and the PCRs they change.
+
+  edit-cmdline <EIF> --output <FILE> [OPTIONS]
+      Edit the kernel command line of an EIF. This invalidates any signature of the image.
+
+      --cmdline <CMDLINE>      Replace the whole command line
+      --set <KEY[=VALUE]>      Set a parameter, replacing its previous values (may be repeated)
+      --remove <KEY>           Remove a parameter (may be repeated)";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(String::as_str) {
+        Some("proxy") => proxy(&args[1..]),
+        Some("build-eif") => build_eif(&args[1..]),
+        Some("diff-eif") => diff_eif(&args[1..]),
+        Some("edit-cmdline") => edit_cmdline(&args[1..]),
+        Some("-h") | Some("--help") => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn proxy(args: &[String]) -> Result<(), String> {
+    let mut positional = Vec::new();
+    let mut config_path = DEFAULT_PROXY_CONFIG.to_string();
+    let mut ip_version = IpVersion::Any;
+    let mut max_connections = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--config" => config_path = value(&mut args, arg)?,
+            "--ipv4" => ip_version = IpVersion::V4,
+            "--ipv6" => ip_version = IpVersion::V6,
+            "--max-connections" => max_connections = Some(parse(&value(&mut args, arg)?, arg)?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}\n\n{USAGE}")),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    let [local_port, remote_host, remote_port] = &positional[..] else {
+        return Err(USAGE.to_string());
+    };
+
+    let mut config = ProxyConfig::new(remote_host, parse(remote_port, "REMOTE_PORT")?);
+    config.ip_version = ip_version;
+    config.max_connections = max_connections;
+
+    let allowlist = Allowlist::load(&config_path).map_err(|e| e.to_string())?;
+    let proxy = Proxy::new(config, &allowlist).map_err(|e| e.to_string())?;
+
+    let listener =
+        VsockListener::bind_with_cid_port(VMADDR_CID_ANY, parse(local_port, "LOCAL_PORT")?)
+            .map_err(|e| format!("unable to listen on vsock port {local_port}: {e}"))?;
+
+    shutdown_on_signal(proxy.shutdown_handle())?;
+
+    proxy.run(&listener).map_err(|e| e.to_string())
+}
+
+fn build_eif(args: &[String]) -> Result<(), String> {
+    let (mut image, mut kernel, mut init, mut output) = (None, None, None, None);
+    let mut cmdline = None;
+    let mut arch = None;
+    let (mut name, mut version) = (None, "latest".to_string());
+    let mut custom = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--image" => image = Some(value(&mut args, arg)?),
+            "--kernel" => kernel = Some(value(&mut args, arg)?),
+            "--init" => init = Some(value(&mut args, arg)?),
+            "--output" => output = Some(value(&mut args, arg)?),
+            "--cmdline" => cmdline = Some(parse_cmdline(&value(&mut args, arg)?)?),
+            "--arch" => arch = Some(value(&mut args, arg)?),
+            "--name" => name = Some(value(&mut args, arg)?),
+            "--version" => version = value(&mut args, arg)?,
+            "--metadata" => {
+                let field = value(&mut args, arg)?;
+                let (key, value) = field
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid value for {arg}: {field}"))?;
+                custom.push((key.to_string(), value.to_string()));
+            }
+            _ => return Err(format!("unknown option {arg}\n\n{USAGE}")),
+        }
+    }
+
+    let (Some(image), Some(kernel), Some(init), Some(output)) = (image, kernel, init, output)
+    else {
+        return Err(USAGE.to_string());
+    };
+
+    let aarch64 = arch
+        .as_deref()
+        .map_or(cfg!(target_arch = "aarch64"), |arch| arch == "arm64");
+    let cmdline = cmdline.unwrap_or_else(|| match aarch64 {
+        true => KernelCmdline::nitro_aarch64(),
+        false => KernelCmdline::nitro(),
+    });
+
+    let image_path = image;
+    let image = match arch {
+        Some(arch) => Image::load_for(&image_path, &arch),
+        None => Image::load(&image_path),
+    }
+    .map_err(|e| e.to_string())?;
+
+    let read = |path: &str| std::fs::read(path).map_err(|e| format!("unable to read {path}: {e}"));
+    let mut eif = image
+        .to_eif(read(&kernel)?, &cmdline, read(&init)?)
+        .map_err(|e| e.to_string())?;
+
+    if name.is_some() || !custom.is_empty() {
+        let name = name.unwrap_or_else(|| image_path.clone());
+        let metadata = custom
+            .into_iter()
+            .fold(EifMetadata::new(&name, &version), |m, (k, v)| {
+                m.with_custom(&k, v)
+            });
+        eif.set_metadata(&metadata).map_err(|e| e.to_string())?;
+    }
+
+    write_eif(&eif, &output)
+}
+
+fn diff_eif(args: &[String]) -> Result<(), String> {
+    let [old, new] = args else {
+        return Err(USAGE.to_string());
+    };
+
+    let read = |path: &str| {
+        let mut file =
+            std::fs::File::open(path).map_err(|e| format!("unable to open {path}: {e}"))?;
+        Eif::read(&mut file).map_err(|e| format!("unable to read {path}: {e}"))
+    };
+    let diff = read(old)?.diff(&read(new)?);
+
+    match diff.is_empty() {
+        true => println!("images are identical"),
+        false => print!("{diff}"),
+    }
+
+    Ok(())
+}
+
+fn edit_cmdline(args: &[String]) -> Result<(), String> {
+    let (mut path, mut output, mut replacement) = (None, None, None);
+    let mut edits = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--output" => output = Some(value(&mut args, arg)?),
+            "--cmdline" => replacement = Some(parse_cmdline(&value(&mut args, arg)?)?),
+            "--set" | "--remove" => edits.push((arg.clone(), value(&mut args, arg)?)),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}\n\n{USAGE}")),
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let (Some(path), Some(output)) = (path, output) else {
+        return Err(USAGE.to_string());
+    };
+
+    let mut file = std::fs::File::open(&path).map_err(|e| format!("unable to open {path}: {e}"))?;
+    let mut eif = Eif::read(&mut file).map_err(|e| format!("unable to read {path}: {e}"))?;
+
+    let mut cmdline = match replacement {
+        Some(cmdline) => cmdline,
+        None => eif
+            .kernel_cmdline()
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default(),
+    };
+    for (option, edit) in edits {
+        match (option.as_str(), edit.split_once('=')) {
+            ("--set", Some((key, value))) => cmdline.set(key, value),
+            ("--set", None) => cmdline.flag(&edit),
+            _ => cmdline.remove(&edit),
+        };
+    }
+
+    eif.set_cmdline(&cmdline).map_err(|e| e.to_string())?;
+    if eif.sections_of(SectionType::Signature).next().is_some() {
+        eprintln!("warning: the image's signature no longer matches its PCRs");
+    }
+    println!("cmdline: {cmdline}");
+
+    write_eif(&eif, &output)
+}
+
+fn parse_cmdline(cmdline: &str) -> Result<KernelCmdline, String> {
+    cmdline
+        .parse()
+        .map_err(|e| format!("invalid kernel command line: {e}"))
+}
+
+/// Write an image and print its PCRs.
+fn write_eif(eif: &Eif, path: &str) -> Result<(), String> {
+    let mut file =
+        std::fs::File::create(path).map_err(|e| format!("unable to create {path}: {e}"))?;
+    eif.write(&mut file).map_err(|e| e.to_string())?;
+
+    for (index, pcr) in eif.pcrs() {
+        let hex: String = pcr.iter().map(|b| format!("{b:02x}")).collect();
+        println!("PCR{index}: {hex}");
+    }
+
+    Ok(())
+}
+
+/// Shut down on SIGINT or SIGTERM. The signals are blocked in the calling thread (and the threads
+/// it spawns), and instead received by a dedicated thread.
+fn shutdown_on_signal(handle: ShutdownHandle) -> Result<(), String> {
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGINT);
+    signals.add(Signal::SIGTERM);
+    signals
+        .thread_block()
+        .map_err(|e| format!("unable to block signals: {e}"))?;
+
+    thread::spawn(move || {
+        if signals.wait().is_ok() {
+            handle.shutdown();
+        }
+    });
+
+    Ok(())
+}
+
+fn value<'a>(args: &mut impl Iterator<Item = &'a String>, option: &str) -> Result<String, String> {
+    args.next()
+        .cloned()
+        .ok_or_else(|| format!("missing value for {option}"))
+}
+
+fn parse<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {name}: {value}"))
+}
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Enclave-side tunnel forwarding local TCP connections over vsock, to be started by the
+//! enclave's init before the application.
+
+use nitro_enclaves::tunnel::{Tunnel, TunnelMapping};
+use nix::sys::signal::{SigSet, Signal};
+use std::{process::ExitCode, thread};
+
+const USAGE: &str = "\
+Usage: vsock-tunnel [OPTIONS] <LOCAL_ADDR=[CID:]PORT>...
+
+Forward TCP connections accepted on each local address to a vsock port (on the parent instance,
+CID 3, unless a CID is given).
+
+Options:
+  --max-connections <N>    Maximum number of connections forwarded at once per mapping";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut mappings = Vec::new();
+    let mut max_connections = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            "--max-connections" => {
+                let value = args.next().ok_or("missing value for --max-connections")?;
+                let max = value
+                    .parse()
+                    .map_err(|_| format!("invalid value for --max-connections: {value}"))?;
+                max_connections = Some(max);
+            }
+            _ => mappings.push(arg.parse::<TunnelMapping>().map_err(|e| e.to_string())?),
+        }
+    }
+
+    if mappings.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let tunnel = Tunnel::new(mappings, max_connections);
+
+    // Shut down on SIGINT or SIGTERM, received by a dedicated thread.
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGINT);
+    signals.add(Signal::SIGTERM);
+    signals
+        .thread_block()
+        .map_err(|e| format!("unable to block signals: {e}"))?;
+
+    let handle = tunnel.shutdown_handle();
+    thread::spawn(move || {
+        if signals.wait().is_ok() {
+            handle.shutdown();
+        }
+    });
+
+    tunnel.run().map_err(|e| e.to_string())
+}
@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Console output of enclaves started in debug mode, served by the hypervisor on a port derived
+//! from the enclave's CID.
+
+use crate::transport::{Addr, Transport, VMADDR_CID_HYPERVISOR};
+
+use std::{
+    io, thread,
+    time::{Duration, Instant},
+};
+
+/// Offset from an enclave's CID to the hypervisor port serving its console.
+pub const CID_TO_CONSOLE_PORT_OFFSET: u32 = 10000;
+
+// Delay between attempts to connect to the console.
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Get the address of the console of the enclave with the given CID.
+pub fn addr(cid: u32) -> Addr {
+    Addr::new(VMADDR_CID_HYPERVISOR, cid + CID_TO_CONSOLE_PORT_OFFSET)
+}
+
+/// Connect to the console of the enclave with the given CID, retrying until the timeout elapses
+/// as the console only becomes available once the enclave has started. Reading from the returned
+/// stream yields the enclave's console output until it terminates.
+pub fn connect<T: Transport>(transport: &T, cid: u32, timeout: Duration) -> io::Result<T::Stream> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        match transport.connect(addr(cid)) {
+            Ok(stream) => return Ok(stream),
+            Err(e) if Instant::now() + CONNECT_RETRY_DELAY > deadline => return Err(e),
+            Err(_) => thread::sleep(CONNECT_RETRY_DELAY),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt, io, path::PathBuf};
+
+/// Error that may occur when building or parsing a cpio archive.
+#[derive(Debug)]
+pub enum CpioError {
+    /// Error reading the source files or writing the archive.
+    Io(io::Error),
+
+    /// Error reading a tar stream.
+    Tar(io::Error),
+
+    /// A path is empty or escapes the archive root.
+    InvalidPath(String),
+
+    /// A source file is of a type that cannot be archived (e.g. a socket).
+    UnsupportedFileType(PathBuf),
+
+    /// A hard link in a tar stream refers to a file not (yet) in the archive.
+    MissingLinkTarget(String),
+
+    /// An archive being parsed is malformed.
+    Malformed(String),
+
+    /// A file exceeds the maximum size of a newc entry (4 GiB).
+    FileTooLarge(String),
+}
+
+impl fmt::Display for CpioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Io(e) => format!("cpio I/O error: {e}"),
+            Self::Tar(e) => format!("unable to read tar stream: {e}"),
+            Self::InvalidPath(path) => format!("invalid archive path \"{path}\""),
+            Self::UnsupportedFileType(path) => {
+                format!("unsupported file type of {}", path.display())
+            }
+            Self::MissingLinkTarget(path) => format!("hard link target {path} is not archived"),
+            Self::Malformed(e) => format!("malformed cpio archive: {e}"),
+            Self::FileTooLarge(path) => format!("file {path} is too large for a cpio archive"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+impl From<io::Error> for CpioError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
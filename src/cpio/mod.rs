@@ -0,0 +1,413 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reader and writer of cpio archives in the "newc" format, as used by the ramdisk sections of
+//! EIFs (and Linux initramfs in general). Written archives are reproducible: entries are sorted
+//! by path, inode numbers are assigned in that order and all entries share one modification time
+//! (the epoch by default). Files taken from a directory or tar stream are owned by root.
+
+mod error;
+mod types;
+
+pub use error::*;
+pub use types::*;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Read, Write},
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::Path,
+};
+
+type Result<T> = std::result::Result<T, CpioError>;
+
+/// Magic of newc entry headers (without checksums).
+pub const NEWC_MAGIC: &[u8] = b"070701";
+
+// Magic of newc entry headers with checksums, which are accepted but not verified.
+const NEWC_CRC_MAGIC: &[u8] = b"070702";
+
+/// Name of the entry terminating an archive.
+pub const TRAILER: &str = "TRAILER!!!";
+
+// Size of a newc entry header, before the name.
+const HEADER_SIZE: usize = 110;
+
+// Mode of directories created implicitly as parents of other entries.
+const PARENT_MODE: u32 = 0o755;
+
+/// A cpio archive, with entries by path (relative to the archive root, without a leading "/").
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Archive {
+    entries: BTreeMap<String, Entry>,
+    mtime: u32,
+}
+
+impl Archive {
+    /// Create an empty archive.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Archive the contents of a directory (not including the directory itself).
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let mut archive = Self::new();
+        archive.append_dir(dir.as_ref(), "")?;
+
+        Ok(archive)
+    }
+
+    /// Archive the entries of a tar stream.
+    pub fn from_tar<R: Read>(reader: R) -> Result<Self> {
+        let mut archive = Self::new();
+        archive.append_tar(reader)?;
+
+        Ok(archive)
+    }
+
+    /// Parse an archive, or a concatenation of archives as accepted for initramfs. Entries of
+    /// later archives replace earlier ones with the same path. Only the first entry's
+    /// modification time is kept, and hard links are not resolved.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut archive = Self::new();
+        let entries = Self::parse_entries(bytes)?;
+        if let Some(first) = entries.first() {
+            archive.mtime = first.mtime;
+        }
+        for parsed in entries {
+            if parsed.path != "." {
+                archive.entries.insert(parsed.path, parsed.entry);
+            }
+        }
+
+        Ok(archive)
+    }
+
+    /// Parse the entries of an archive (or a concatenation of archives) in order, keeping each
+    /// entry's inode number, link count and modification time. Trailers are skipped.
+    pub fn parse_entries(bytes: &[u8]) -> Result<Vec<ParsedEntry>> {
+        let malformed = |msg: &str| CpioError::Malformed(msg.to_string());
+
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            // Archives may be separated (and followed) by zero padding.
+            if bytes[pos] == 0 {
+                pos += 1;
+                continue;
+            }
+
+            let header = bytes
+                .get(pos..pos + HEADER_SIZE)
+                .ok_or_else(|| malformed("truncated header"))?;
+            if header[..6] != *NEWC_MAGIC && header[..6] != *NEWC_CRC_MAGIC {
+                return Err(malformed("invalid magic"));
+            }
+            let mut fields = [0u32; 13];
+            for (i, field) in fields.iter_mut().enumerate() {
+                let hex = std::str::from_utf8(&header[6 + i * 8..14 + i * 8])
+                    .map_err(|_| malformed("invalid header field"))?;
+                *field =
+                    u32::from_str_radix(hex, 16).map_err(|_| malformed("invalid header field"))?;
+            }
+            let [ino, mode, uid, gid, nlink, mtime, size, _, _, rdev_major, rdev_minor, name_size, _] =
+                fields;
+
+            let name_end = pos + HEADER_SIZE + name_size as usize;
+            let name = bytes
+                .get(pos + HEADER_SIZE..name_end)
+                .and_then(|name| name.strip_suffix(&[0]))
+                .ok_or_else(|| malformed("invalid entry name"))?;
+            let name = String::from_utf8_lossy(name).into_owned();
+            let data_start = align(name_end);
+            let data = bytes
+                .get(data_start..data_start + size as usize)
+                .ok_or_else(|| malformed("truncated entry data"))?;
+            pos = align(data_start + size as usize);
+
+            if name == TRAILER {
+                continue;
+            }
+
+            let (major, minor) = (rdev_major, rdev_minor);
+            let kind = match mode & S_IFMT {
+                S_IFREG => EntryKind::File(data.to_vec()),
+                S_IFDIR => EntryKind::Directory,
+                S_IFLNK => EntryKind::Symlink(String::from_utf8_lossy(data).into_owned()),
+                S_IFCHR => EntryKind::CharDevice { major, minor },
+                S_IFBLK => EntryKind::BlockDevice { major, minor },
+                S_IFIFO => EntryKind::Fifo,
+                _ => return Err(CpioError::UnsupportedFileType(name.into())),
+            };
+            entries.push(ParsedEntry {
+                path: match name.as_str() {
+                    "." => name,
+                    _ => normalize(&name)?,
+                },
+                entry: Entry {
+                    kind,
+                    mode: mode & 0o7777,
+                    uid,
+                    gid,
+                },
+                ino,
+                nlink,
+                mtime,
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Get the modification time of all entries.
+    pub fn mtime(&self) -> u32 {
+        self.mtime
+    }
+
+    /// Set the modification time (in seconds since the epoch) of all entries.
+    pub fn set_mtime(&mut self, mtime: u32) {
+        self.mtime = mtime;
+    }
+
+    /// Add an entry, replacing any entry with the same path. Missing parent directories are
+    /// added too.
+    pub fn add(&mut self, path: &str, entry: Entry) -> Result<()> {
+        let path = normalize(path)?;
+
+        let mut parent = path.as_str();
+        while let Some((dir, _)) = parent.rsplit_once('/') {
+            self.entries
+                .entry(dir.to_string())
+                .or_insert_with(|| Entry::directory(PARENT_MODE));
+            parent = dir;
+        }
+        self.entries.insert(path, entry);
+
+        Ok(())
+    }
+
+    /// Remove an entry and, for a directory, the entries within it.
+    pub fn remove(&mut self, path: &str) -> Option<Entry> {
+        let path = normalize(path).ok()?;
+        let prefix = format!("{path}/");
+        self.entries.retain(|p, _| !p.starts_with(&prefix));
+
+        self.entries.remove(&path)
+    }
+
+    /// Keep only the entries for which f returns true.
+    pub fn retain(&mut self, mut f: impl FnMut(&str, &Entry) -> bool) {
+        self.entries.retain(|path, entry| f(path, entry));
+    }
+
+    /// Get an entry by path.
+    pub fn get(&self, path: &str) -> Option<&Entry> {
+        self.entries.get(&normalize(path).ok()?)
+    }
+
+    /// Iterate over the entries, in archive order.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &Entry)> {
+        self.entries
+            .iter()
+            .map(|(path, entry)| (path.as_str(), entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Add the contents of a directory, recursively, under a prefix path.
+    pub fn append_dir(&mut self, dir: &Path, prefix: &str) -> Result<()> {
+        for dirent in fs::read_dir(dir)? {
+            let dirent = dirent?;
+            let name = dirent.file_name();
+            let name = name.to_string_lossy();
+            let path = match prefix {
+                "" => name.to_string(),
+                _ => format!("{prefix}/{name}"),
+            };
+
+            let src = dirent.path();
+            let meta = fs::symlink_metadata(&src)?;
+            let file_type = meta.file_type();
+            let mode = meta.mode();
+            let (major, minor) = (libc::major(meta.rdev()), libc::minor(meta.rdev()));
+
+            let entry = if file_type.is_file() {
+                Entry::file(fs::read(&src)?, mode)
+            } else if file_type.is_dir() {
+                Entry::directory(mode)
+            } else if file_type.is_symlink() {
+                Entry::symlink(&fs::read_link(&src)?.to_string_lossy())
+            } else if file_type.is_char_device() {
+                Entry::char_device(major, minor, mode)
+            } else if file_type.is_block_device() {
+                Entry::block_device(major, minor, mode)
+            } else if file_type.is_fifo() {
+                Entry::fifo(mode)
+            } else {
+                return Err(CpioError::UnsupportedFileType(src));
+            };
+            self.add(&path, entry)?;
+
+            if file_type.is_dir() {
+                self.append_dir(&src, &path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add the entries of a tar stream, in order, so that later entries replace earlier ones.
+    pub fn append_tar<R: Read>(&mut self, reader: R) -> Result<()> {
+        let mut tar = tar::Archive::new(reader);
+        for entry in tar.entries().map_err(CpioError::Tar)? {
+            let entry = entry.map_err(CpioError::Tar)?;
+            let path = entry.path().map_err(CpioError::Tar)?;
+            let path = path.to_string_lossy().into_owned();
+            if let Some(entry) = self.tar_entry(&path, entry)? {
+                self.add(&path, entry)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Convert a tar entry, skipping the archive root and entries other than files.
+    pub(crate) fn tar_entry<R: Read>(
+        &self,
+        path: &str,
+        mut entry: tar::Entry<R>,
+    ) -> Result<Option<Entry>> {
+        if path.split('/').all(|c| c.is_empty() || c == ".") {
+            return Ok(None);
+        }
+
+        let header = entry.header();
+        let mode = header.mode().map_err(CpioError::Tar)?;
+        let device = || -> Result<(u32, u32)> {
+            let major = header.device_major().map_err(CpioError::Tar)?;
+            let minor = header.device_minor().map_err(CpioError::Tar)?;
+            Ok((major.unwrap_or(0), minor.unwrap_or(0)))
+        };
+        let link_name = || -> Result<String> {
+            let name = entry.link_name().map_err(CpioError::Tar)?;
+            Ok(name.unwrap_or_default().to_string_lossy().into_owned())
+        };
+
+        let entry = match header.entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).map_err(CpioError::Tar)?;
+                Entry::file(data, mode)
+            }
+            tar::EntryType::Directory => Entry::directory(mode),
+            tar::EntryType::Symlink => Entry::symlink(&link_name()?),
+            tar::EntryType::Link => {
+                let target = link_name()?;
+                self.get(&target)
+                    .cloned()
+                    .ok_or(CpioError::MissingLinkTarget(target))?
+            }
+            tar::EntryType::Char => {
+                let (major, minor) = device()?;
+                Entry::char_device(major, minor, mode)
+            }
+            tar::EntryType::Block => {
+                let (major, minor) = device()?;
+                Entry::block_device(major, minor, mode)
+            }
+            tar::EntryType::Fifo => Entry::fifo(mode),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(entry))
+    }
+
+    /// Write the archive.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for (ino, (path, entry)) in self.entries.iter().enumerate() {
+            let data = entry.data();
+            let size =
+                u32::try_from(data.len()).map_err(|_| CpioError::FileTooLarge(path.clone()))?;
+            let (rdev_major, rdev_minor) = match entry.kind {
+                EntryKind::CharDevice { major, minor }
+                | EntryKind::BlockDevice { major, minor } => (major, minor),
+                _ => (0, 0),
+            };
+            let nlink = match entry.kind {
+                EntryKind::Directory => 2,
+                _ => 1,
+            };
+
+            let header = [
+                ino as u32 + 1,
+                entry.newc_mode(),
+                entry.uid,
+                entry.gid,
+                nlink,
+                self.mtime,
+                size,
+                0,
+                0,
+                rdev_major,
+                rdev_minor,
+            ];
+            write_entry(writer, &header, path, data)?;
+        }
+
+        write_entry(writer, &[0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0], TRAILER, &[])
+    }
+
+    /// Get the archive's encoding. This fails for files too large for the format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes)?;
+
+        Ok(bytes)
+    }
+}
+
+/// Write an entry: header fields (up to the name size), name and data, each padded to 4 bytes.
+fn write_entry<W: Write>(
+    writer: &mut W,
+    fields: &[u32; 11],
+    name: &str,
+    data: &[u8],
+) -> Result<()> {
+    let mut header = NEWC_MAGIC.to_vec();
+    let name_size = name.len() as u32 + 1;
+    for field in fields.iter().chain([&name_size, &0]) {
+        header.extend_from_slice(format!("{field:08X}").as_bytes());
+    }
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.resize(align(HEADER_SIZE + name.len() + 1), 0);
+
+    writer.write_all(&header)?;
+    writer.write_all(data)?;
+    writer.write_all(&[0; 3][..align(data.len()) - data.len()])?;
+
+    Ok(())
+}
+
+fn align(len: usize) -> usize {
+    len.next_multiple_of(4)
+}
+
+/// Normalize a path to be relative to the archive root, rejecting paths escaping it.
+fn normalize(path: &str) -> Result<String> {
+    let components: Vec<&str> = path
+        .split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect();
+    if components.is_empty() || components.contains(&"..") {
+        return Err(CpioError::InvalidPath(path.to_string()));
+    }
+
+    Ok(components.join("/"))
+}
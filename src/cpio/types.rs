@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: Apache-2.0
+
+// File type bits of a newc entry's mode.
+pub(crate) const S_IFMT: u32 = 0o170000;
+pub(crate) const S_IFIFO: u32 = 0o010000;
+pub(crate) const S_IFCHR: u32 = 0o020000;
+pub(crate) const S_IFDIR: u32 = 0o040000;
+pub(crate) const S_IFBLK: u32 = 0o060000;
+pub(crate) const S_IFREG: u32 = 0o100000;
+pub(crate) const S_IFLNK: u32 = 0o120000;
+
+/// Type and contents of an archive entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntryKind {
+    File(Vec<u8>),
+    Directory,
+
+    /// Symbolic link to the given target.
+    Symlink(String),
+    CharDevice {
+        major: u32,
+        minor: u32,
+    },
+    BlockDevice {
+        major: u32,
+        minor: u32,
+    },
+    Fifo,
+}
+
+/// An archive entry. The mode holds permission bits only, the file type being given by kind.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub kind: EntryKind,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Entry {
+    /// Regular file owned by root.
+    pub fn file(data: Vec<u8>, mode: u32) -> Self {
+        Self::new(EntryKind::File(data), mode)
+    }
+
+    /// Directory owned by root.
+    pub fn directory(mode: u32) -> Self {
+        Self::new(EntryKind::Directory, mode)
+    }
+
+    /// Symbolic link owned by root.
+    pub fn symlink(target: &str) -> Self {
+        Self::new(EntryKind::Symlink(target.to_string()), 0o777)
+    }
+
+    /// Character device node owned by root.
+    pub fn char_device(major: u32, minor: u32, mode: u32) -> Self {
+        Self::new(EntryKind::CharDevice { major, minor }, mode)
+    }
+
+    /// Block device node owned by root.
+    pub fn block_device(major: u32, minor: u32, mode: u32) -> Self {
+        Self::new(EntryKind::BlockDevice { major, minor }, mode)
+    }
+
+    /// Named pipe owned by root.
+    pub fn fifo(mode: u32) -> Self {
+        Self::new(EntryKind::Fifo, mode)
+    }
+
+    fn new(kind: EntryKind, mode: u32) -> Self {
+        Self {
+            kind,
+            mode: mode & 0o7777,
+            uid: 0,
+            gid: 0,
+        }
+    }
+
+    /// Get the newc mode, combining the file type and permission bits.
+    pub(crate) fn newc_mode(&self) -> u32 {
+        let file_type = match self.kind {
+            EntryKind::File(_) => S_IFREG,
+            EntryKind::Directory => S_IFDIR,
+            EntryKind::Symlink(_) => S_IFLNK,
+            EntryKind::CharDevice { .. } => S_IFCHR,
+            EntryKind::BlockDevice { .. } => S_IFBLK,
+            EntryKind::Fifo => S_IFIFO,
+        };
+
+        file_type | self.mode
+    }
+
+    /// Get the entry's data: a file's contents or a symlink's target.
+    pub(crate) fn data(&self) -> &[u8] {
+        match &self.kind {
+            EntryKind::File(data) => data,
+            EntryKind::Symlink(target) => target.as_bytes(),
+            _ => &[],
+        }
+    }
+}
+
+/// An entry as read from an archive, along with the header fields that an
+/// [`Archive`](super::Archive) does not keep.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsedEntry {
+    /// Path, relative to the archive root ("." for the root itself).
+    pub path: String,
+    pub entry: Entry,
+    pub ino: u32,
+    pub nlink: u32,
+    pub mtime: u32,
+}
@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{
+    error::{CmdlineError, EifError},
+    Eif, Section, SectionType, DEFAULT_CMDLINE,
+};
+
+use std::{fmt, str::FromStr};
+
+/// Maximum size of a kernel command line, including its terminating NUL (COMMAND_LINE_SIZE on
+/// x86_64 and aarch64).
+pub const MAX_CMDLINE_SIZE: usize = 2048;
+
+/// Kernel command line parameters, emitted in order. Arguments following "--" are passed to init
+/// rather than interpreted by the kernel.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KernelCmdline {
+    params: Vec<(String, Option<String>)>,
+    init_args: Vec<String>,
+}
+
+impl KernelCmdline {
+    /// Create an empty command line.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Command line of the x86_64 Nitro Enclaves kernels: reboot (rather than halt) on panic,
+    /// no PCI or modules, a serial console and the CPU trusted as an entropy source.
+    pub fn nitro() -> Self {
+        DEFAULT_CMDLINE.parse().unwrap()
+    }
+
+    /// Command line of the aarch64 Nitro Enclaves kernels, which lack the i8042 controller.
+    pub fn nitro_aarch64() -> Self {
+        let mut cmdline = Self::nitro();
+        cmdline.params.retain(|(key, _)| !key.starts_with("i8042."));
+        cmdline
+    }
+
+    /// Parse a command line, as the kernel does: parameters are separated by whitespace, and
+    /// double quotes may enclose whitespace within a parameter.
+    pub fn parse(cmdline: &str) -> Result<Self, CmdlineError> {
+        let mut tokens = Vec::new();
+        let (mut token, mut in_token, mut in_quote) = (String::new(), false, false);
+        for c in cmdline.chars() {
+            match c {
+                '"' => {
+                    in_quote = !in_quote;
+                    in_token = true;
+                }
+                c if c.is_ascii_whitespace() && !in_quote => {
+                    if in_token {
+                        tokens.push(std::mem::take(&mut token));
+                        in_token = false;
+                    }
+                }
+                c => {
+                    token.push(c);
+                    in_token = true;
+                }
+            }
+        }
+        if in_quote {
+            return Err(CmdlineError::UnbalancedQuotes);
+        }
+        if in_token {
+            tokens.push(token);
+        }
+
+        let mut result = Self::new();
+        let mut tokens = tokens.into_iter();
+        for token in tokens.by_ref() {
+            if token == "--" {
+                break;
+            }
+            match token.split_once('=') {
+                Some((key, value)) => result
+                    .params
+                    .push((key.to_string(), Some(value.to_string()))),
+                None => result.params.push((token, None)),
+            }
+        }
+        result.init_args = tokens.collect();
+        result.validate()?;
+
+        Ok(result)
+    }
+
+    /// Set a parameter's value, replacing any previous values of the parameter.
+    pub fn set(&mut self, key: &str, value: &str) -> &mut Self {
+        self.replace(key, Some(value.to_string()))
+    }
+
+    /// Set a parameter without a value (e.g. "quiet"), replacing any previous values.
+    pub fn flag(&mut self, key: &str) -> &mut Self {
+        self.replace(key, None)
+    }
+
+    /// Remove all values of a parameter.
+    pub fn remove(&mut self, key: &str) -> &mut Self {
+        self.params.retain(|(k, _)| k != key);
+        self
+    }
+
+    /// Append an argument passed to init.
+    pub fn push_init_arg(&mut self, arg: &str) -> &mut Self {
+        self.init_args.push(arg.to_string());
+        self
+    }
+
+    /// Get the last value of a parameter: None if absent, Some(None) if set without a value.
+    pub fn get(&self, key: &str) -> Option<Option<&str>> {
+        self.params
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_deref())
+    }
+
+    /// Get the parameters, in order.
+    pub fn params(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.params.iter().map(|(k, v)| (k.as_str(), v.as_deref()))
+    }
+
+    /// Get the arguments passed to init.
+    pub fn init_args(&self) -> &[String] {
+        &self.init_args
+    }
+
+    /// Check that the command line can be represented, and fits the kernel's limit.
+    pub fn validate(&self) -> Result<(), CmdlineError> {
+        for (key, value) in &self.params {
+            if key.is_empty()
+                || key == "--"
+                || key.contains(|c: char| c.is_whitespace() || c == '=' || c == '"')
+            {
+                return Err(CmdlineError::InvalidKey(key.clone()));
+            }
+            if value.as_ref().is_some_and(|v| v.contains('"')) {
+                return Err(CmdlineError::InvalidValue(key.clone()));
+            }
+        }
+        if let Some(arg) = self
+            .init_args
+            .iter()
+            .find(|a| a.is_empty() || a.contains('"'))
+        {
+            return Err(CmdlineError::InvalidValue(arg.clone()));
+        }
+        let cmdline = self.to_string();
+        if cmdline.contains(|c: char| c.is_control()) {
+            return Err(CmdlineError::ControlCharacter);
+        }
+        if cmdline.len() >= MAX_CMDLINE_SIZE {
+            return Err(CmdlineError::TooLong(cmdline.len()));
+        }
+
+        Ok(())
+    }
+
+    fn replace(&mut self, key: &str, value: Option<String>) -> &mut Self {
+        // Keep the parameter at the position of its first value.
+        let index = self.params.iter().position(|(k, _)| k == key);
+        self.params.retain(|(k, _)| k != key);
+        let index = index.unwrap_or(self.params.len());
+        self.params.insert(index, (key.to_string(), value));
+
+        self
+    }
+}
+
+impl Eif {
+    /// Parse the image's kernel command line, if it has one.
+    pub fn kernel_cmdline(&self) -> Result<Option<KernelCmdline>, EifError> {
+        self.cmdline()
+            .map(|cmdline| {
+                std::str::from_utf8(cmdline)
+                    .map_err(|_| EifError::Cmdline(CmdlineError::InvalidEncoding))?
+                    .trim_end_matches('\0')
+                    .parse()
+                    .map_err(EifError::Cmdline)
+            })
+            .transpose()
+    }
+
+    /// Replace the image's kernel command line (or add one after the kernel). Section headers and
+    /// the CRC are recomputed when the image is written. This changes PCR0 and PCR1, so any
+    /// signature section no longer matches the image.
+    pub fn set_cmdline(&mut self, cmdline: &KernelCmdline) -> Result<(), EifError> {
+        cmdline.validate().map_err(EifError::Cmdline)?;
+        let data = cmdline.to_string().into_bytes();
+
+        match self
+            .sections
+            .iter_mut()
+            .find(|s| s.section_type == SectionType::Cmdline)
+        {
+            Some(section) => section.data = data,
+            None => {
+                let index = self
+                    .sections
+                    .iter()
+                    .position(|s| s.section_type == SectionType::Kernel)
+                    .map_or(0, |i| i + 1);
+                self.sections
+                    .insert(index, Section::new(SectionType::Cmdline, data));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for KernelCmdline {
+    type Err = CmdlineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for KernelCmdline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let quote = |s: &str| match s.contains(|c: char| c.is_ascii_whitespace()) {
+            true => format!("\"{s}\""),
+            false => s.to_string(),
+        };
+
+        let mut words: Vec<String> = self
+            .params
+            .iter()
+            .map(|(key, value)| match value {
+                Some(value) => format!("{key}={}", quote(value)),
+                None => key.clone(),
+            })
+            .collect();
+        if !self.init_args.is_empty() {
+            words.push("--".to_string());
+            words.extend(self.init_args.iter().map(|arg| quote(arg)));
+        }
+
+        write!(f, "{}", words.join(" "))
+    }
+}
@@ -0,0 +1,367 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{Eif, SectionType};
+use crate::cpio::{Archive, ParsedEntry};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// Differences between two images, and their effect on PCRs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EifDiff {
+    /// Header fields that differ: (name, old value, new value).
+    pub header: Vec<(&'static str, u64, u64)>,
+
+    /// Sections that differ, matched by type and position among sections of that type.
+    pub sections: Vec<SectionDiff>,
+
+    /// PCRs that differ.
+    pub pcrs: Vec<PcrDelta>,
+}
+
+impl EifDiff {
+    /// Check whether the images are identical.
+    pub fn is_empty(&self) -> bool {
+        self.header.is_empty() && self.sections.is_empty()
+    }
+}
+
+/// A section that differs between two images.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SectionDiff {
+    pub section_type: SectionType,
+
+    /// Position among the sections of the same type (e.g. 1 for the application ramdisk).
+    pub index: usize,
+    pub change: SectionChange,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SectionChange {
+    /// The section only exists in the new image.
+    Added,
+
+    /// The section only exists in the old image.
+    Removed,
+
+    /// The section's contents differ.
+    Modified {
+        old_size: usize,
+        new_size: usize,
+        content: ContentDiff,
+    },
+}
+
+/// Differences within the contents of a section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContentDiff {
+    /// The section's bytes differ, without further detail.
+    Bytes,
+
+    /// Old and new kernel command lines.
+    Cmdline { old: String, new: String },
+
+    /// Files of a ramdisk that differ.
+    Files(Vec<FileDiff>),
+
+    /// The ramdisk's files are identical, but the archives are encoded differently (e.g. with
+    /// entries in another order).
+    Encoding,
+
+    /// Top-level metadata fields that differ.
+    Metadata(Vec<String>),
+}
+
+/// A file that differs between two ramdisks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileDiff {
+    pub path: String,
+    pub change: FileChange,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileChange {
+    Added,
+    Removed,
+
+    /// The file's type, contents, link target or device numbers differ, and/or its permissions,
+    /// owner, modification time, inode number or link count (as old and new values).
+    Modified {
+        contents: bool,
+        mode: Option<(u32, u32)>,
+        owner: Option<((u32, u32), (u32, u32))>,
+        mtime: Option<(u32, u32)>,
+        ino: Option<(u32, u32)>,
+        nlink: Option<(u32, u32)>,
+    },
+}
+
+/// A PCR that differs between two images, along with the changed sections measured into it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PcrDelta {
+    pub index: usize,
+    pub old: Option<Vec<u8>>,
+    pub new: Option<Vec<u8>>,
+
+    /// Sections, as (type, index), whose changes caused the PCR to differ.
+    pub causes: Vec<(SectionType, usize)>,
+}
+
+impl Eif {
+    /// Compare an image with a newer one.
+    pub fn diff(&self, new: &Eif) -> EifDiff {
+        let header = [
+            ("version", self.version as u64, new.version as u64),
+            ("flags", self.flags as u64, new.flags as u64),
+            ("default_mem", self.default_mem, new.default_mem),
+            ("default_cpus", self.default_cpus, new.default_cpus),
+        ]
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .collect();
+
+        let types: BTreeSet<SectionType> = self
+            .sections
+            .iter()
+            .chain(&new.sections)
+            .map(|s| s.section_type)
+            .collect();
+
+        let mut sections = Vec::new();
+        for section_type in types {
+            let old: Vec<_> = self.sections_of(section_type).collect();
+            let new: Vec<_> = new.sections_of(section_type).collect();
+
+            for index in 0..old.len().max(new.len()) {
+                let change = match (old.get(index), new.get(index)) {
+                    (Some(o), Some(n)) if o.data == n.data => continue,
+                    (Some(o), Some(n)) => SectionChange::Modified {
+                        old_size: o.data.len(),
+                        new_size: n.data.len(),
+                        content: content_diff(section_type, &o.data, &n.data),
+                    },
+                    (None, _) => SectionChange::Added,
+                    (_, None) => SectionChange::Removed,
+                };
+                sections.push(SectionDiff {
+                    section_type,
+                    index,
+                    change,
+                });
+            }
+        }
+
+        let (old_pcrs, new_pcrs) = (self.pcrs(), new.pcrs());
+        let indexes: BTreeSet<usize> = old_pcrs.keys().chain(new_pcrs.keys()).copied().collect();
+        let pcrs = indexes
+            .into_iter()
+            .filter(|i| old_pcrs.get(i) != new_pcrs.get(i))
+            .map(|index| PcrDelta {
+                index,
+                old: old_pcrs.get(&index).cloned(),
+                new: new_pcrs.get(&index).cloned(),
+                causes: sections
+                    .iter()
+                    .filter(|s| measured_into(index, s.section_type, s.index))
+                    .map(|s| (s.section_type, s.index))
+                    .collect(),
+            })
+            .collect();
+
+        EifDiff {
+            header,
+            sections,
+            pcrs,
+        }
+    }
+}
+
+/// Check whether a section is measured into a PCR (see [`Eif::pcrs`]).
+fn measured_into(pcr: usize, section_type: SectionType, index: usize) -> bool {
+    match (pcr, section_type) {
+        (0, SectionType::Kernel | SectionType::Cmdline | SectionType::Ramdisk) => true,
+        (1, SectionType::Kernel | SectionType::Cmdline) => true,
+        (1, SectionType::Ramdisk) => index == 0,
+        (2, SectionType::Ramdisk) => index > 0,
+        (8, SectionType::Signature) => true,
+        _ => false,
+    }
+}
+
+fn content_diff(section_type: SectionType, old: &[u8], new: &[u8]) -> ContentDiff {
+    match section_type {
+        SectionType::Cmdline => ContentDiff::Cmdline {
+            old: String::from_utf8_lossy(old).into_owned(),
+            new: String::from_utf8_lossy(new).into_owned(),
+        },
+        SectionType::Ramdisk => match (Archive::parse_entries(old), Archive::parse_entries(new)) {
+            (Ok(old), Ok(new)) => match file_diffs(&old, &new) {
+                files if files.is_empty() => ContentDiff::Encoding,
+                files => ContentDiff::Files(files),
+            },
+            _ => ContentDiff::Bytes,
+        },
+        SectionType::Metadata => {
+            let parse = |data| serde_json::from_slice::<BTreeMap<String, serde_json::Value>>(data);
+            match (parse(old), parse(new)) {
+                (Ok(old), Ok(new)) => {
+                    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+                    ContentDiff::Metadata(
+                        keys.into_iter()
+                            .filter(|k| old.get(*k) != new.get(*k))
+                            .cloned()
+                            .collect(),
+                    )
+                }
+                _ => ContentDiff::Bytes,
+            }
+        }
+        _ => ContentDiff::Bytes,
+    }
+}
+
+fn file_diffs<'a>(old: &'a [ParsedEntry], new: &'a [ParsedEntry]) -> Vec<FileDiff> {
+    // Later entries replace earlier ones with the same path.
+    let by_path = |entries: &'a [ParsedEntry]| -> BTreeMap<&'a str, &'a ParsedEntry> {
+        entries.iter().map(|e| (e.path.as_str(), e)).collect()
+    };
+    let (old, new) = (by_path(old), by_path(new));
+    let paths: BTreeSet<&str> = old.keys().chain(new.keys()).copied().collect();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let change = match (old.get(path), new.get(path)) {
+                (Some(o), Some(n)) if o == n => return None,
+                (Some(o), Some(n)) => {
+                    let changed = |old, new| (old != new).then_some((old, new));
+                    let (oe, ne) = (&o.entry, &n.entry);
+                    FileChange::Modified {
+                        contents: oe.kind != ne.kind,
+                        mode: changed(oe.mode, ne.mode),
+                        owner: ((oe.uid, oe.gid) != (ne.uid, ne.gid))
+                            .then_some(((oe.uid, oe.gid), (ne.uid, ne.gid))),
+                        mtime: changed(o.mtime, n.mtime),
+                        ino: changed(o.ino, n.ino),
+                        nlink: changed(o.nlink, n.nlink),
+                    }
+                }
+                (None, _) => FileChange::Added,
+                (_, None) => FileChange::Removed,
+            };
+
+            Some(FileDiff {
+                path: path.to_string(),
+                change,
+            })
+        })
+        .collect()
+}
+
+impl fmt::Display for EifDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, old, new) in &self.header {
+            writeln!(f, "header {name}: {old} -> {new}")?;
+        }
+
+        for section in &self.sections {
+            write!(f, "{} {}: ", section.section_type, section.index)?;
+            let content = match &section.change {
+                SectionChange::Added => {
+                    writeln!(f, "added")?;
+                    continue;
+                }
+                SectionChange::Removed => {
+                    writeln!(f, "removed")?;
+                    continue;
+                }
+                SectionChange::Modified {
+                    old_size,
+                    new_size,
+                    content,
+                } => {
+                    writeln!(f, "modified ({old_size} -> {new_size} bytes)")?;
+                    content
+                }
+            };
+
+            match content {
+                ContentDiff::Bytes => (),
+                ContentDiff::Cmdline { old, new } => writeln!(f, "  - {old}\n  + {new}")?,
+                ContentDiff::Encoding => writeln!(f, "  archive encoding differs")?,
+                ContentDiff::Files(files) => {
+                    for file in files {
+                        writeln!(f, "  {file}")?;
+                    }
+                }
+                ContentDiff::Metadata(keys) => {
+                    for key in keys {
+                        writeln!(f, "  M {key}")?;
+                    }
+                }
+            }
+        }
+
+        for pcr in &self.pcrs {
+            let causes: Vec<String> = pcr.causes.iter().map(|(t, i)| format!("{t} {i}")).collect();
+            writeln!(
+                f,
+                "PCR{}: {} -> {} ({})",
+                pcr.index,
+                hex(pcr.old.as_deref()),
+                hex(pcr.new.as_deref()),
+                causes.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for FileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.change {
+            FileChange::Added => write!(f, "A {}", self.path),
+            FileChange::Removed => write!(f, "D {}", self.path),
+            FileChange::Modified {
+                contents,
+                mode,
+                owner,
+                mtime,
+                ino,
+                nlink,
+            } => {
+                write!(f, "M {}", self.path)?;
+                if *contents {
+                    write!(f, " contents")?;
+                }
+                if let Some((old, new)) = mode {
+                    write!(f, " mode {old:o} -> {new:o}")?;
+                }
+                if let Some(((old_uid, old_gid), (new_uid, new_gid))) = owner {
+                    write!(f, " owner {old_uid}:{old_gid} -> {new_uid}:{new_gid}")?;
+                }
+                if let Some((old, new)) = mtime {
+                    write!(f, " mtime {old} -> {new}")?;
+                }
+                if let Some((old, new)) = ino {
+                    write!(f, " ino {old} -> {new}")?;
+                }
+                if let Some((old, new)) = nlink {
+                    write!(f, " nlink {old} -> {new}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+fn hex(pcr: Option<&[u8]>) -> String {
+    match pcr {
+        Some(pcr) => pcr.iter().map(|b| format!("{b:02x}")).collect(),
+        None => "none".to_string(),
+    }
+}
@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt, io};
+
+/// Error that may occur when reading or writing an EIF.
+#[derive(Debug)]
+pub enum EifError {
+    /// Error reading or writing the image.
+    Io(io::Error),
+
+    /// The image does not start with the EIF magic.
+    InvalidMagic,
+
+    /// The image has more sections than the header can describe.
+    TooManySections(usize),
+
+    /// A section header has an unknown type.
+    InvalidSectionType(u16),
+
+    /// A section's size differs between the image header and the section header.
+    SectionSizeMismatch {
+        index: usize,
+        header: u64,
+        section: u64,
+    },
+
+    /// The image's CRC does not match its contents.
+    CrcMismatch { expected: u32, actual: u32 },
+
+    /// The metadata section is not valid JSON of the expected form.
+    Metadata(serde_json::Error),
+
+    /// The kernel command line is invalid.
+    Cmdline(CmdlineError),
+}
+
+impl fmt::Display for EifError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Io(e) => format!("EIF I/O error: {e}"),
+            Self::InvalidMagic => "not an EIF (invalid magic)".to_string(),
+            Self::TooManySections(n) => format!("EIF cannot hold {n} sections"),
+            Self::InvalidSectionType(t) => format!("invalid EIF section type {t}"),
+            Self::SectionSizeMismatch {
+                index,
+                header,
+                section,
+            } => format!("EIF section {index} size {section} does not match header size {header}"),
+            Self::CrcMismatch { expected, actual } => {
+                format!("EIF CRC {actual:#010x} does not match header CRC {expected:#010x}")
+            }
+            Self::Metadata(e) => format!("invalid EIF metadata: {e}"),
+            Self::Cmdline(e) => format!("invalid EIF kernel command line: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+/// Error that may occur when parsing or validating a kernel command line.
+#[derive(Debug)]
+pub enum CmdlineError {
+    /// The command line is not valid UTF-8.
+    InvalidEncoding,
+
+    /// A double quote is not closed.
+    UnbalancedQuotes,
+
+    /// A parameter name is empty or contains whitespace, '=' or '"'.
+    InvalidKey(String),
+
+    /// A value (of the given parameter) or init argument is empty or contains '"'.
+    InvalidValue(String),
+
+    /// The command line contains a control character.
+    ControlCharacter,
+
+    /// The command line (of the given length) exceeds the kernel's limit.
+    TooLong(usize),
+}
+
+impl fmt::Display for CmdlineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::InvalidEncoding => "command line is not valid UTF-8".to_string(),
+            Self::UnbalancedQuotes => "unbalanced quotes".to_string(),
+            Self::InvalidKey(key) => format!("invalid parameter name \"{key}\""),
+            Self::InvalidValue(key) => format!("invalid value of {key}"),
+            Self::ControlCharacter => "control character in command line".to_string(),
+            Self::TooLong(len) => format!(
+                "command line of {len} bytes exceeds the limit of {} bytes",
+                super::MAX_CMDLINE_SIZE - 1
+            ),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+impl From<io::Error> for EifError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{be_u16, be_u64, error::EifError, Eif, Result, Section, SectionType};
+use super::{EIF_HEADER_SIZE, EIF_MAGIC, MAX_NUM_SECTIONS, SECTION_HEADER_SIZE};
+
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Contents of the metadata section, describing the image and how it was built (as shown by
+/// `nitro-cli describe-eif`).
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EifMetadata {
+    pub image_name: String,
+    pub image_version: String,
+
+    #[serde(rename = "BuildMetadata")]
+    pub build_info: BuildInfo,
+
+    /// Description of the container image the EIF was built from, as given by docker.
+    #[serde(default)]
+    pub docker_info: serde_json::Value,
+
+    /// Fields defined by the image's builder.
+    #[serde(
+        default,
+        rename = "CustomMetadata",
+        skip_serializing_if = "serde_json::Map::is_empty",
+        deserialize_with = "null_as_empty"
+    )]
+    pub custom: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Build environment of an image.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BuildInfo {
+    /// Build time, in RFC 3339 format.
+    #[serde(default)]
+    pub build_time: String,
+
+    #[serde(default)]
+    pub build_tool: String,
+
+    #[serde(default)]
+    pub build_tool_version: String,
+
+    #[serde(default, rename = "OperatingSystem")]
+    pub os: String,
+
+    #[serde(default)]
+    pub kernel_version: String,
+}
+
+impl EifMetadata {
+    /// Describe an image built by this crate. The build time is left empty, so that images
+    /// remain reproducible unless the caller sets it.
+    pub fn new(image_name: &str, image_version: &str) -> Self {
+        Self {
+            image_name: image_name.to_string(),
+            image_version: image_version.to_string(),
+            build_info: BuildInfo {
+                build_tool: env!("CARGO_PKG_NAME").to_string(),
+                build_tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                os: std::env::consts::OS.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Add a custom field, replacing any field of the same name.
+    pub fn with_custom(mut self, name: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.custom.insert(name.to_string(), value.into());
+        self
+    }
+}
+
+impl Eif {
+    /// Get the image's metadata, if it has a metadata section.
+    pub fn metadata(&self) -> Result<Option<EifMetadata>> {
+        self.sections_of(SectionType::Metadata)
+            .next()
+            .map(|section| serde_json::from_slice(&section.data).map_err(EifError::Metadata))
+            .transpose()
+    }
+
+    /// Set the image's metadata, replacing its metadata section or adding one after the command
+    /// line, where nitro-cli places it.
+    pub fn set_metadata(&mut self, metadata: &EifMetadata) -> Result<()> {
+        let data = serde_json::to_vec(metadata).map_err(EifError::Metadata)?;
+
+        match self
+            .sections
+            .iter_mut()
+            .find(|s| s.section_type == SectionType::Metadata)
+        {
+            Some(section) => section.data = data,
+            None => {
+                let index = self
+                    .sections
+                    .iter()
+                    .position(|s| s.section_type == SectionType::Cmdline)
+                    .map_or(self.sections.len(), |i| i + 1);
+                self.sections
+                    .insert(index, Section::new(SectionType::Metadata, data));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read only the metadata of an image, without reading its other sections or verifying its
+    /// CRC.
+    pub fn read_metadata<R: Read + Seek>(reader: &mut R) -> Result<Option<EifMetadata>> {
+        let mut header = [0u8; EIF_HEADER_SIZE];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut header)?;
+
+        if header[..4] != EIF_MAGIC {
+            return Err(EifError::InvalidMagic);
+        }
+        let num_sections = be_u16(&header[26..]) as usize;
+        if num_sections > MAX_NUM_SECTIONS {
+            return Err(EifError::TooManySections(num_sections));
+        }
+
+        for index in 0..num_sections {
+            let offset = be_u64(&header[28 + index * 8..]);
+
+            let mut section_header = [0u8; SECTION_HEADER_SIZE];
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut section_header)?;
+            // Sections of types unknown to this library are skipped.
+            if !matches!(
+                SectionType::try_from(be_u16(&section_header)),
+                Ok(SectionType::Metadata)
+            ) {
+                continue;
+            }
+
+            let size = be_u64(&section_header[4..]);
+            let mut data = Vec::new();
+            reader.take(size).read_to_end(&mut data)?;
+            if data.len() as u64 != size {
+                return Err(EifError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+
+            return serde_json::from_slice(&data)
+                .map(Some)
+                .map_err(EifError::Metadata);
+        }
+
+        Ok(None)
+    }
+}
+
+/// nitro-cli writes missing custom metadata as null.
+fn null_as_empty<'de, D>(
+    deserializer: D,
+) -> std::result::Result<serde_json::Map<String, serde_json::Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+}
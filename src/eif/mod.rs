@@ -0,0 +1,321 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Enclave Image Format (EIF). An EIF consists of a header, listing the offset and size of each
+//! section, followed by the sections: the kernel, its command line, one or more ramdisks, and
+//! optionally a signature and build metadata. All integers are big-endian.
+
+mod cmdline;
+mod diff;
+mod error;
+mod metadata;
+mod types;
+
+pub use cmdline::*;
+pub use diff::*;
+pub use error::*;
+pub use metadata::*;
+pub use types::*;
+
+use ring::digest::{self, SHA384};
+use std::{
+    collections::BTreeMap,
+    io::{Read, Seek, SeekFrom, Write},
+};
+
+type Result<T> = std::result::Result<T, EifError>;
+
+/// Magic at the start of every EIF.
+pub const EIF_MAGIC: [u8; 4] = *b".eif";
+
+/// EIF version written by this crate.
+pub const EIF_VERSION: u16 = 4;
+
+/// Maximum number of sections of an EIF.
+pub const MAX_NUM_SECTIONS: usize = 32;
+
+/// Size of the EIF header.
+pub const EIF_HEADER_SIZE: usize = 548;
+
+/// Size of each section header.
+pub const SECTION_HEADER_SIZE: usize = 12;
+
+// Offset of the CRC within the EIF header, which covers all bytes preceding it.
+const CRC_OFFSET: usize = EIF_HEADER_SIZE - 4;
+
+/// Kernel command line of the Nitro Enclaves kernels distributed with nitro-cli.
+pub const DEFAULT_CMDLINE: &str = "reboot=k panic=30 pci=off nomodules console=ttyS0 \
+    i8042.noaux i8042.nomux i8042.nopnp i8042.dumbkbd random.trust_cpu=on";
+
+/// Header flag set for images targeting aarch64.
+pub const EIF_FLAG_ARCH_ARM64: u16 = 0x1;
+
+/// An enclave image.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Eif {
+    /// Format version.
+    pub version: u16,
+
+    /// Header flags.
+    pub flags: u16,
+
+    /// Default enclave memory size, in bytes.
+    pub default_mem: u64,
+
+    /// Default number of enclave vCPUs.
+    pub default_cpus: u64,
+
+    /// Sections, in image order.
+    pub sections: Vec<Section>,
+}
+
+impl Eif {
+    /// Create an image from a kernel, its command line and ramdisks. The first ramdisk holds the
+    /// enclave's init, the others the application. Fails if the command line is invalid (see
+    /// [`KernelCmdline::validate`]).
+    pub fn new(
+        kernel: Vec<u8>,
+        cmdline: &KernelCmdline,
+        ramdisks: Vec<Vec<u8>>,
+    ) -> std::result::Result<Self, CmdlineError> {
+        cmdline.validate()?;
+        let mut sections = vec![
+            Section::new(SectionType::Kernel, kernel),
+            Section::new(SectionType::Cmdline, cmdline.to_string().into_bytes()),
+        ];
+        sections.extend(
+            ramdisks
+                .into_iter()
+                .map(|r| Section::new(SectionType::Ramdisk, r)),
+        );
+
+        Ok(Self {
+            version: EIF_VERSION,
+            flags: 0,
+            default_mem: 0,
+            default_cpus: 0,
+            sections,
+        })
+    }
+
+    /// Read an image, verifying its CRC.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let mut header = [0u8; EIF_HEADER_SIZE];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut header)?;
+
+        if header[..4] != EIF_MAGIC {
+            return Err(EifError::InvalidMagic);
+        }
+        let num_sections = be_u16(&header[26..]) as usize;
+        if num_sections > MAX_NUM_SECTIONS {
+            return Err(EifError::TooManySections(num_sections));
+        }
+
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(&header[..CRC_OFFSET]);
+
+        let mut sections = Vec::with_capacity(num_sections);
+        for index in 0..num_sections {
+            let offset = be_u64(&header[28 + index * 8..]);
+            let size = be_u64(&header[28 + MAX_NUM_SECTIONS * 8 + index * 8..]);
+
+            let mut section_header = [0u8; SECTION_HEADER_SIZE];
+            reader.seek(SeekFrom::Start(offset))?;
+            reader.read_exact(&mut section_header)?;
+
+            let section_size = be_u64(&section_header[4..]);
+            if section_size != size {
+                return Err(EifError::SectionSizeMismatch {
+                    index,
+                    header: size,
+                    section: section_size,
+                });
+            }
+
+            let mut data = Vec::new();
+            reader.take(size).read_to_end(&mut data)?;
+            if data.len() as u64 != size {
+                return Err(EifError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+
+            crc.update(&section_header);
+            crc.update(&data);
+
+            sections.push(Section {
+                section_type: SectionType::try_from(be_u16(&section_header))?,
+                flags: be_u16(&section_header[2..]),
+                data,
+            });
+        }
+
+        let expected = u32::from_be_bytes(header[CRC_OFFSET..].try_into().unwrap());
+        let actual = crc.finalize();
+        if expected != actual {
+            return Err(EifError::CrcMismatch { expected, actual });
+        }
+
+        Ok(Self {
+            version: be_u16(&header[4..]),
+            flags: be_u16(&header[6..]),
+            default_mem: be_u64(&header[8..]),
+            default_cpus: be_u64(&header[16..]),
+            sections,
+        })
+    }
+
+    /// Write the image, computing section offsets and the CRC.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        if self.sections.len() > MAX_NUM_SECTIONS {
+            return Err(EifError::TooManySections(self.sections.len()));
+        }
+
+        let mut header = Vec::with_capacity(EIF_HEADER_SIZE);
+        header.extend_from_slice(&EIF_MAGIC);
+        header.extend_from_slice(&self.version.to_be_bytes());
+        header.extend_from_slice(&self.flags.to_be_bytes());
+        header.extend_from_slice(&self.default_mem.to_be_bytes());
+        header.extend_from_slice(&self.default_cpus.to_be_bytes());
+        header.extend_from_slice(&0u16.to_be_bytes());
+        header.extend_from_slice(&(self.sections.len() as u16).to_be_bytes());
+
+        let mut offsets = [0u64; MAX_NUM_SECTIONS];
+        let mut sizes = [0u64; MAX_NUM_SECTIONS];
+        let mut offset = EIF_HEADER_SIZE as u64;
+        for (i, section) in self.sections.iter().enumerate() {
+            offsets[i] = offset;
+            sizes[i] = section.data.len() as u64;
+            offset += (SECTION_HEADER_SIZE + section.data.len()) as u64;
+        }
+        offsets
+            .iter()
+            .chain(&sizes)
+            .for_each(|v| header.extend_from_slice(&v.to_be_bytes()));
+        header.extend_from_slice(&0u32.to_be_bytes());
+
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(&header);
+        let section_headers: Vec<_> = self.sections.iter().map(section_header).collect();
+        for (section_header, section) in section_headers.iter().zip(&self.sections) {
+            crc.update(section_header);
+            crc.update(&section.data);
+        }
+        header.extend_from_slice(&crc.finalize().to_be_bytes());
+
+        writer.write_all(&header)?;
+        for (section_header, section) in section_headers.iter().zip(&self.sections) {
+            writer.write_all(section_header)?;
+            writer.write_all(&section.data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the sections of a given type, in image order.
+    pub fn sections_of(&self, section_type: SectionType) -> impl Iterator<Item = &Section> {
+        self.sections
+            .iter()
+            .filter(move |s| s.section_type == section_type)
+    }
+
+    /// Get the kernel command line.
+    pub fn cmdline(&self) -> Option<&[u8]> {
+        self.sections_of(SectionType::Cmdline)
+            .next()
+            .map(|s| &s.data[..])
+    }
+
+    /// Compute the PCRs that an enclave booted from the image reports:
+    ///
+    /// - PCR0: the kernel, command line and all ramdisks.
+    /// - PCR1: the kernel, command line and first (init) ramdisk.
+    /// - PCR2: the remaining (application) ramdisks.
+    /// - PCR8: the signing certificate, for signed images.
+    ///
+    /// Each is a SHA-384 PCR extended once from zero with the SHA-384 digest of its data.
+    pub fn pcrs(&self) -> BTreeMap<usize, Vec<u8>> {
+        let mut image = digest::Context::new(&SHA384);
+        let mut boot = digest::Context::new(&SHA384);
+        let mut app = digest::Context::new(&SHA384);
+
+        let mut ramdisks = 0;
+        for section in &self.sections {
+            match section.section_type {
+                SectionType::Kernel | SectionType::Cmdline => {
+                    image.update(&section.data);
+                    boot.update(&section.data);
+                }
+                SectionType::Ramdisk => {
+                    image.update(&section.data);
+                    if ramdisks == 0 {
+                        boot.update(&section.data);
+                    } else {
+                        app.update(&section.data);
+                    }
+                    ramdisks += 1;
+                }
+                SectionType::Signature | SectionType::Metadata => (),
+            }
+        }
+
+        let mut pcrs = BTreeMap::from([
+            (0, pcr_extend(image.finish().as_ref())),
+            (1, pcr_extend(boot.finish().as_ref())),
+            (2, pcr_extend(app.finish().as_ref())),
+        ]);
+
+        if let Some(cert) = self.signing_certificate() {
+            pcrs.insert(8, pcr_extend(digest::digest(&SHA384, &cert).as_ref()));
+        }
+
+        pcrs
+    }
+
+    /// Get the DER-encoded certificate of the signature section's first signature.
+    fn signing_certificate(&self) -> Option<Vec<u8>> {
+        let section = self.sections_of(SectionType::Signature).next()?;
+        let value: ciborium::Value = ciborium::from_reader(&section.data[..]).ok()?;
+
+        let signature = value.into_array().ok()?.into_iter().next()?;
+        let (_, cert) = signature
+            .into_map()
+            .ok()?
+            .into_iter()
+            .find(|(k, _)| k.as_text() == Some("signing_certificate"))?;
+
+        // Byte strings may be encoded as arrays of integers.
+        match cert {
+            ciborium::Value::Bytes(b) => Some(b),
+            ciborium::Value::Array(a) => a
+                .iter()
+                .map(|v| v.as_integer().and_then(|i| u8::try_from(i).ok()))
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+/// Extend a zeroed SHA-384 PCR with a digest.
+pub fn pcr_extend(digest: &[u8]) -> Vec<u8> {
+    let mut ctx = digest::Context::new(&SHA384);
+    ctx.update(&[0u8; 48]);
+    ctx.update(digest);
+
+    ctx.finish().as_ref().to_vec()
+}
+
+fn section_header(section: &Section) -> [u8; SECTION_HEADER_SIZE] {
+    let mut header = [0u8; SECTION_HEADER_SIZE];
+    header[..2].copy_from_slice(&u16::from(section.section_type).to_be_bytes());
+    header[2..4].copy_from_slice(&section.flags.to_be_bytes());
+    header[4..].copy_from_slice(&(section.data.len() as u64).to_be_bytes());
+
+    header
+}
+
+fn be_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes(bytes[..2].try_into().unwrap())
+}
+
+fn be_u64(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes[..8].try_into().unwrap())
+}
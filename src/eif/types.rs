@@ -0,0 +1,88 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::error::EifError;
+
+use std::fmt;
+
+/// Type of an EIF section.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SectionType {
+    /// Linux kernel image.
+    Kernel,
+
+    /// Kernel command line.
+    Cmdline,
+
+    /// Initial ramdisk (cpio archive).
+    Ramdisk,
+
+    /// Signature of the image's PCRs.
+    Signature,
+
+    /// Build metadata.
+    Metadata,
+}
+
+impl TryFrom<u16> for SectionType {
+    type Error = EifError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Kernel),
+            2 => Ok(Self::Cmdline),
+            3 => Ok(Self::Ramdisk),
+            4 => Ok(Self::Signature),
+            5 => Ok(Self::Metadata),
+            _ => Err(EifError::InvalidSectionType(value)),
+        }
+    }
+}
+
+impl From<SectionType> for u16 {
+    fn from(section_type: SectionType) -> Self {
+        match section_type {
+            SectionType::Kernel => 1,
+            SectionType::Cmdline => 2,
+            SectionType::Ramdisk => 3,
+            SectionType::Signature => 4,
+            SectionType::Metadata => 5,
+        }
+    }
+}
+
+impl fmt::Display for SectionType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Kernel => "kernel",
+            Self::Cmdline => "cmdline",
+            Self::Ramdisk => "ramdisk",
+            Self::Signature => "signature",
+            Self::Metadata => "metadata",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// A section of an EIF.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    /// Type of the section.
+    pub section_type: SectionType,
+
+    /// Section flags (unused by current images).
+    pub flags: u16,
+
+    /// Contents of the section.
+    pub data: Vec<u8>,
+}
+
+impl Section {
+    pub fn new(section_type: SectionType, data: Vec<u8>) -> Self {
+        Self {
+            section_type,
+            flags: 0,
+            data,
+        }
+    }
+}
@@ -0,0 +1,164 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bidirectional forwarding of connections between streams.
+
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use std::{
+    collections::HashMap,
+    io,
+    net::Shutdown,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use crate::transport::{Listener, Stream};
+
+// Interval (in milliseconds) at which a forwarder checks whether it has been shut down.
+pub(crate) const SHUTDOWN_POLL_MS: i32 = 100;
+
+/// Handle used to shut down a running forwarder from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct ShutdownHandle(Arc<AtomicBool>);
+
+impl ShutdownHandle {
+    /// Stop accepting connections and close all forwarded connections.
+    pub fn shutdown(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Check whether shutdown was requested.
+    pub fn is_shutdown(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Connections being forwarded, each with functions closing its streams.
+#[derive(Default)]
+struct Connections {
+    next_id: u64,
+    active: HashMap<u64, Vec<Box<dyn Fn() + Send>>>,
+}
+
+impl Connections {
+    /// Start tracking a new connection, unless max connections are already being forwarded.
+    fn add<S: Stream>(&mut self, client: &S, max: Option<usize>) -> Option<u64> {
+        if max.is_some_and(|max| self.active.len() >= max) {
+            return None;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.active.insert(id, Vec::new());
+        if self.track(id, client).is_err() {
+            self.active.remove(&id);
+            return None;
+        }
+
+        Some(id)
+    }
+
+    /// Track a stream of a connection, so that it is closed on shutdown.
+    fn track<S: Stream>(&mut self, id: u64, stream: &S) -> io::Result<()> {
+        let stream = stream.try_clone()?;
+        if let Some(closers) = self.active.get_mut(&id) {
+            closers.push(Box::new(move || {
+                let _ = stream.shutdown(Shutdown::Both);
+            }));
+        }
+
+        Ok(())
+    }
+
+    /// Close the streams of all connections.
+    fn close_all(&self) {
+        self.active.values().flatten().for_each(|close| close());
+    }
+}
+
+/// Accept connections on a listener and forward each to a stream returned by connect (given the
+/// accepted stream), until shutdown is requested. Each connection is handled on its own thread.
+/// Connections accepted while max_connections are already being forwarded, or for which connect
+/// fails, are closed.
+pub(crate) fn run<L, S, C>(
+    listener: &L,
+    shutdown: &ShutdownHandle,
+    max_connections: Option<usize>,
+    connect: C,
+) -> io::Result<()>
+where
+    L: Listener,
+    S: Stream,
+    C: Fn(&L::Stream) -> io::Result<S> + Sync,
+{
+    let connections = Mutex::new(Connections::default());
+
+    // All connection threads are joined before returning.
+    thread::scope(|s| {
+        let result = loop {
+            if shutdown.is_shutdown() {
+                break Ok(());
+            }
+
+            // Wait for a connection, periodically waking up to check for shutdown.
+            let mut fds = [PollFd::new(listener.as_raw_fd(), PollFlags::POLLIN)];
+            match poll(&mut fds, SHUTDOWN_POLL_MS) {
+                Ok(0) | Err(Errno::EINTR) => continue,
+                Ok(_) => (),
+                Err(e) => break Err(e.into()),
+            }
+
+            let client = match listener.accept() {
+                Ok(client) => client,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e),
+            };
+
+            let Some(id) = connections.lock().unwrap().add(&client, max_connections) else {
+                continue;
+            };
+
+            let (connections, connect) = (&connections, &connect);
+            s.spawn(move || {
+                if let Ok(server) = connect(&client) {
+                    if connections.lock().unwrap().track(id, &server).is_ok() {
+                        let _ = splice(client, server);
+                    }
+                }
+
+                connections.lock().unwrap().active.remove(&id);
+            });
+        };
+
+        // Close all connections still being forwarded.
+        connections.lock().unwrap().close_all();
+
+        result
+    })
+}
+
+/// Copy data between two streams in both directions, until both directions are closed.
+fn splice<A: Stream, B: Stream>(a: A, b: B) -> io::Result<()> {
+    let (a_read, b_read) = (a.try_clone()?, b.try_clone()?);
+
+    thread::scope(|s| {
+        s.spawn(|| copy(a_read, b));
+        copy(b_read, a);
+    });
+
+    Ok(())
+}
+
+/// Copy data from one stream to another until the source is closed, then close the destination
+/// for writing so that the peer sees the end of the stream. Each write blocks until the
+/// destination accepts the data, so a slow reader throttles the writer rather than data being
+/// buffered without bound.
+fn copy<R: Stream, W: Stream>(mut src: R, mut dst: W) {
+    let _ = io::copy(&mut src, &mut dst);
+    let _ = dst.shutdown(Shutdown::Write);
+}
@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Enclave readiness heartbeat. Once booted, an enclave connects to the parent instance on
+//! [`ENCLAVE_READY_VSOCK_PORT`], sends [`HEART_BEAT`] and waits for it to be echoed back.
+//!
+//! Once running, an enclave's application may also answer liveness probes: the parent instance
+//! connects to it on [`ENCLAVE_LIVENESS_VSOCK_PORT`] and sends the heartbeat, which is echoed
+//! back.
+
+use crate::{
+    forward::{ShutdownHandle, SHUTDOWN_POLL_MS},
+    launch::{BootImage, BootTimeoutPolicy},
+    transport::{Addr, Listener, Transport, VsockTransport, VMADDR_CID_ANY, VMADDR_CID_PARENT},
+};
+
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use std::{
+    collections::BTreeSet,
+    fmt,
+    io::{self, Read, Write},
+    os::fd::AsRawFd,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+/// Port on the parent instance to which enclaves send their readiness heartbeat.
+pub const ENCLAVE_READY_VSOCK_PORT: u32 = 9000;
+
+/// Port in an enclave on which its application answers liveness probes.
+pub const ENCLAVE_LIVENESS_VSOCK_PORT: u32 = 9002;
+
+/// Time allowed for the parent instance to send its heartbeat once connected for a liveness
+/// probe.
+pub const LIVENESS_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Time allowed for an enclave to send its readiness heartbeat once connected.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Byte sent by an enclave (and echoed back by the parent) to signal readiness.
+pub const HEART_BEAT: u8 = 0xb7;
+
+type Result<T> = std::result::Result<T, HeartbeatError>;
+
+/// Error that may occur when exchanging a readiness heartbeat.
+#[derive(Debug)]
+pub enum HeartbeatError {
+    /// Unable to listen for the heartbeat.
+    Bind(io::Error),
+
+    /// Unable to connect to the parent instance (from an enclave).
+    Connect(io::Error),
+
+    /// The heartbeat was not received before the timeout elapsed.
+    Timeout,
+
+    /// Error while waiting for or accepting the heartbeat connection.
+    Accept(io::Error),
+
+    /// Unable to read the heartbeat.
+    Read(io::Error),
+
+    /// Unable to write the heartbeat.
+    Write(io::Error),
+
+    /// A byte other than the heartbeat was received.
+    Unexpected(u8),
+}
+
+impl fmt::Display for HeartbeatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Bind(e) => format!("unable to listen for heartbeat: {e}"),
+            Self::Connect(e) => format!("unable to connect to parent instance: {e}"),
+            Self::Timeout => "heartbeat not received before timeout".to_string(),
+            Self::Accept(e) => format!("unable to accept heartbeat connection: {e}"),
+            Self::Read(e) => format!("unable to read heartbeat: {e}"),
+            Self::Write(e) => format!("unable to write heartbeat: {e}"),
+            Self::Unexpected(b) => format!("unexpected heartbeat byte {b:#04x}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+/// Parent-side listener for enclaves' readiness heartbeats. It must be bound before an enclave is
+/// started, so that its heartbeat is not missed. As the heartbeat port is fixed, enclaves launched
+/// concurrently share a listener, each waiting for its own CID.
+pub struct ReadyListener<T: Transport> {
+    transport: T,
+    listener: T::Listener,
+    // Serializes accepting connections between waiters.
+    accept: Mutex<()>,
+    // CIDs whose heartbeat was accepted by a waiter for another CID.
+    ready: Mutex<BTreeSet<u32>>,
+}
+
+// Vsock listener shared by concurrent launches, released once none uses it.
+static SHARED: Mutex<Weak<ReadyListener<VsockTransport>>> = Mutex::new(Weak::new());
+
+impl ReadyListener<VsockTransport> {
+    /// Get the vsock listener shared by the process's launches, binding it if no launch is using
+    /// it.
+    pub fn shared() -> Result<Arc<Self>> {
+        let mut shared = SHARED.lock().unwrap();
+        if let Some(listener) = shared.upgrade() {
+            return Ok(listener);
+        }
+
+        let listener = Arc::new(Self::bind(VsockTransport)?);
+        *shared = Arc::downgrade(&listener);
+
+        Ok(listener)
+    }
+}
+
+impl<T: Transport> ReadyListener<T> {
+    /// Listen for the heartbeat on [`ENCLAVE_READY_VSOCK_PORT`].
+    pub fn bind(transport: T) -> Result<Self> {
+        let listener = transport
+            .bind(Addr::new(VMADDR_CID_ANY, ENCLAVE_READY_VSOCK_PORT))
+            .map_err(HeartbeatError::Bind)?;
+
+        Ok(Self {
+            transport,
+            listener,
+            accept: Mutex::new(()),
+            ready: Mutex::new(BTreeSet::new()),
+        })
+    }
+
+    /// Get the transport that the listener was bound with.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Wait up to timeout for the enclave with the given CID to send its heartbeat, and echo it
+    /// back. Heartbeats from other enclaves are echoed too, and kept for their waiters.
+    pub fn wait(&self, timeout: Duration, cid: u32) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if self.ready.lock().unwrap().remove(&cid) {
+                return Ok(());
+            }
+
+            // Accept in short slices, so that heartbeats accepted by other waiters are noticed.
+            let _accept = self.accept.lock().unwrap();
+            let slice =
+                deadline.min(Instant::now() + Duration::from_millis(SHUTDOWN_POLL_MS as u64));
+            match wait_readable(&self.listener, slice, HeartbeatError::Accept) {
+                Err(HeartbeatError::Timeout) if Instant::now() < deadline => continue,
+                Err(HeartbeatError::Timeout) => {
+                    return match self.ready.lock().unwrap().remove(&cid) {
+                        true => Ok(()),
+                        false => Err(HeartbeatError::Timeout),
+                    }
+                }
+                ret => ret?,
+            }
+
+            let (mut stream, peer) = self
+                .transport
+                .accept(&self.listener)
+                .map_err(HeartbeatError::Accept)?;
+
+            match (receive(&mut stream), peer.cid == cid) {
+                (Ok(()), true) => return Ok(()),
+                (Ok(()), false) => {
+                    self.ready.lock().unwrap().insert(peer.cid);
+                }
+                (Err(e), true) => return Err(e),
+                // A misbehaving enclave only affects its own wait.
+                (Err(_), false) => (),
+            }
+        }
+    }
+
+    /// Wait for the enclave with the given CID, started at the given instant, to boot from an
+    /// image in mem_size bytes of memory, until the policy's timeout has elapsed since it was
+    /// started. On success, return the boot time, which is recorded to the policy's calibration.
+    pub fn wait_boot(
+        &self,
+        policy: &BootTimeoutPolicy,
+        image: &BootImage,
+        mem_size: usize,
+        cid: u32,
+        started: Instant,
+    ) -> Result<Duration> {
+        let timeout = policy.timeout(image, mem_size);
+        self.wait(timeout.saturating_sub(started.elapsed()), cid)?;
+
+        let elapsed = started.elapsed();
+        policy.record(image, elapsed);
+
+        Ok(elapsed)
+    }
+}
+
+/// Receive the heartbeat over an accepted connection, and echo it back.
+fn receive<S: Read + Write + AsRawFd>(stream: &mut S) -> Result<()> {
+    wait_readable(
+        stream,
+        Instant::now() + HEARTBEAT_TIMEOUT,
+        HeartbeatError::Read,
+    )?;
+    let mut buf = [0u8];
+    stream.read_exact(&mut buf).map_err(HeartbeatError::Read)?;
+    if buf[0] != HEART_BEAT {
+        return Err(HeartbeatError::Unexpected(buf[0]));
+    }
+
+    stream.write_all(&buf).map_err(HeartbeatError::Write)
+}
+
+/// Enclave-side counterpart of [`ReadyListener`]: signal the parent instance that the enclave is
+/// ready, waiting up to timeout for the heartbeat to be echoed back.
+pub fn signal<T: Transport>(transport: &T, timeout: Duration) -> Result<()> {
+    exchange(
+        transport,
+        Addr::new(VMADDR_CID_PARENT, ENCLAVE_READY_VSOCK_PORT),
+        timeout,
+    )
+}
+
+/// Parent-side liveness probe: send the heartbeat to the application of the enclave with the
+/// given CID, waiting up to timeout for it to be echoed back.
+pub fn probe<T: Transport>(transport: &T, cid: u32, timeout: Duration) -> Result<()> {
+    exchange(
+        transport,
+        Addr::new(cid, ENCLAVE_LIVENESS_VSOCK_PORT),
+        timeout,
+    )
+}
+
+/// Send the heartbeat to an address and wait up to timeout for it to be echoed back.
+fn exchange<T: Transport>(transport: &T, addr: Addr, timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+
+    let mut stream = transport.connect(addr).map_err(HeartbeatError::Connect)?;
+    stream
+        .write_all(&[HEART_BEAT])
+        .map_err(HeartbeatError::Write)?;
+
+    wait_readable(&stream, deadline, HeartbeatError::Read)?;
+    let mut buf = [0u8];
+    stream.read_exact(&mut buf).map_err(HeartbeatError::Read)?;
+    if buf[0] != HEART_BEAT {
+        return Err(HeartbeatError::Unexpected(buf[0]));
+    }
+
+    Ok(())
+}
+
+/// Enclave-side responder to the parent instance's liveness probes, echoing each heartbeat on
+/// [`ENCLAVE_LIVENESS_VSOCK_PORT`] for as long as the application runs.
+pub struct LivenessResponder<T: Transport> {
+    listener: T::Listener,
+    shutdown: ShutdownHandle,
+}
+
+impl<T: Transport> LivenessResponder<T> {
+    /// Listen for liveness probes.
+    pub fn bind(transport: &T) -> Result<Self> {
+        let listener = transport
+            .bind(Addr::new(VMADDR_CID_ANY, ENCLAVE_LIVENESS_VSOCK_PORT))
+            .map_err(HeartbeatError::Bind)?;
+
+        Ok(Self {
+            listener,
+            shutdown: ShutdownHandle::default(),
+        })
+    }
+
+    /// Get a handle that can be used to stop answering probes.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Answer probes until shut down. A probe that is not completed within
+    /// [`LIVENESS_PROBE_TIMEOUT`] is dropped.
+    pub fn run(&self) -> Result<()> {
+        while !self.shutdown.is_shutdown() {
+            let deadline = Instant::now() + Duration::from_millis(SHUTDOWN_POLL_MS as u64);
+            match wait_readable(&self.listener, deadline, HeartbeatError::Accept) {
+                Err(HeartbeatError::Timeout) => continue,
+                ret => ret?,
+            }
+
+            let mut stream = match self.listener.accept() {
+                Ok(stream) => stream,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(HeartbeatError::Accept(e)),
+            };
+
+            // A misbehaving peer only affects its own probe.
+            let deadline = Instant::now() + LIVENESS_PROBE_TIMEOUT;
+            let mut buf = [0u8];
+            if wait_readable(&stream, deadline, HeartbeatError::Read).is_ok()
+                && stream.read_exact(&mut buf).is_ok()
+                && buf[0] == HEART_BEAT
+            {
+                let _ = stream.write_all(&buf);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Wait until a file descriptor is readable, failing with a timeout error at the deadline. Poll
+/// errors are converted with err.
+fn wait_readable(
+    fd: &impl AsRawFd,
+    deadline: Instant,
+    err: fn(io::Error) -> HeartbeatError,
+) -> Result<()> {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let timeout = remaining.as_millis().min(i32::MAX as u128) as i32;
+
+        let mut fds = [PollFd::new(fd.as_raw_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, timeout) {
+            Ok(0) => return Err(HeartbeatError::Timeout),
+            Ok(_) => return Ok(()),
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(err(e.into())),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt, io};
+
+/// Error that may occur when running an inbound forwarder.
+#[derive(Debug)]
+pub enum InboundError {
+    /// Error while waiting for or accepting connections.
+    Accept(io::Error),
+}
+
+impl fmt::Display for InboundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Accept(e) => format!("unable to accept connection: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Host-side forwarding of TCP connections on the parent instance into an enclave over vsock,
+//! exposing enclave services to the parent's network.
+
+mod error;
+mod types;
+
+pub use error::*;
+pub use types::*;
+
+use crate::{
+    forward::{self, ShutdownHandle},
+    transport::{Addr, Transport, VsockTransport},
+};
+use std::{
+    io::{self, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+type Result<T> = std::result::Result<T, InboundError>;
+
+/// Forwards connections accepted on a TCP listener to a vsock port of an enclave.
+pub struct InboundForwarder {
+    cid: EnclaveCid,
+    config: InboundConfig,
+    shutdown: ShutdownHandle,
+}
+
+impl InboundForwarder {
+    /// Create a forwarder to the enclave with the given CID. The CID may be updated while the
+    /// forwarder is running if the enclave is restarted.
+    pub fn new(cid: EnclaveCid, config: InboundConfig) -> Self {
+        Self {
+            cid,
+            config,
+            shutdown: ShutdownHandle::default(),
+        }
+    }
+
+    /// Get a handle that can be used to shut down the forwarder.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Accept and forward connections over vsock until the forwarder is shut down.
+    pub fn run(&self, listener: &TcpListener) -> Result<()> {
+        self.run_with(listener, &VsockTransport)
+    }
+
+    /// Accept and forward connections over the given transport until the forwarder is shut
+    /// down.
+    pub fn run_with<T: Transport>(&self, listener: &TcpListener, transport: &T) -> Result<()> {
+        forward::run(
+            listener,
+            &self.shutdown,
+            self.config.max_connections,
+            |client| self.connect(client, transport),
+        )
+        .map_err(InboundError::Accept)
+    }
+
+    /// Connect to the enclave on behalf of a client, retrying while the enclave is unavailable
+    /// (e.g. restarting), and send the PROXY protocol header if configured.
+    fn connect<T: Transport>(&self, client: &TcpStream, transport: &T) -> io::Result<T::Stream> {
+        let mut attempt = 0;
+        let mut server = loop {
+            let result = match self.cid.get().map(u32::try_from) {
+                Some(Ok(cid)) => transport.connect(Addr::new(cid, self.config.port)),
+                Some(Err(_)) => return Err(io::ErrorKind::InvalidInput.into()),
+                None => Err(io::ErrorKind::NotConnected.into()),
+            };
+
+            match result {
+                Ok(server) => break server,
+                Err(e) if attempt >= self.config.connect_retries || self.shutdown.is_shutdown() => {
+                    return Err(e)
+                }
+                Err(_) => {
+                    attempt += 1;
+                    thread::sleep(self.config.retry_delay);
+                }
+            }
+        };
+
+        if let Some(proxy_protocol) = self.config.proxy_protocol {
+            let header = proxy_protocol.header(client.peer_addr()?, client.local_addr()?);
+            server.write_all(&header)?;
+        }
+
+        Ok(server)
+    }
+}
@@ -0,0 +1,136 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// PROXY protocol version 2 signature.
+const PROXY_V2_SIGNATURE: [u8; 12] = [
+    0x0d, 0x0a, 0x0d, 0x0a, 0x00, 0x0d, 0x0a, 0x51, 0x55, 0x49, 0x54, 0x0a,
+];
+
+// PROXY protocol version 2 with the PROXY command.
+const PROXY_V2_VERSION_COMMAND: u8 = 0x21;
+
+// PROXY protocol version 2 address families over TCP.
+const PROXY_V2_TCP4: u8 = 0x11;
+const PROXY_V2_TCP6: u8 = 0x21;
+
+/// The CID of a running enclave, shared between forwarders and whatever (re)starts the enclave,
+/// so that new connections reach the enclave across restarts.
+#[derive(Clone, Debug, Default)]
+pub struct EnclaveCid(Arc<AtomicU64>);
+
+impl EnclaveCid {
+    /// Track the CID of a running enclave, as returned by starting it.
+    pub fn new(cid: u64) -> Self {
+        Self(Arc::new(AtomicU64::new(cid)))
+    }
+
+    /// Get the CID of the enclave, if it is running.
+    pub fn get(&self) -> Option<u64> {
+        Some(self.0.load(Ordering::SeqCst)).filter(|cid| *cid != 0)
+    }
+
+    /// Update the CID after the enclave was (re)started.
+    pub fn set(&self, cid: u64) {
+        self.0.store(cid, Ordering::SeqCst);
+    }
+
+    /// Mark the enclave as not running.
+    pub fn clear(&self) {
+        self.set(0);
+    }
+}
+
+/// Version of the PROXY protocol header sent to the enclave ahead of each connection's data,
+/// conveying the original client address.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ProxyProtocol {
+    /// Human-readable header (version 1).
+    V1,
+
+    /// Binary header (version 2).
+    V2,
+}
+
+impl ProxyProtocol {
+    /// Encode the header for a connection from the client at src to the forwarder at dst.
+    pub fn header(&self, src: SocketAddr, dst: SocketAddr) -> Vec<u8> {
+        // Both addresses must be of the same family. Convert IPv4 addresses to IPv6 if mixed.
+        let (src_ip, dst_ip) = match (src.ip(), dst.ip()) {
+            (IpAddr::V4(s), IpAddr::V6(d)) => (IpAddr::V6(s.to_ipv6_mapped()), IpAddr::V6(d)),
+            (IpAddr::V6(s), IpAddr::V4(d)) => (IpAddr::V6(s), IpAddr::V6(d.to_ipv6_mapped())),
+            (s, d) => (s, d),
+        };
+
+        match self {
+            Self::V1 => {
+                let family = if src_ip.is_ipv4() { "TCP4" } else { "TCP6" };
+                format!(
+                    "PROXY {family} {src_ip} {dst_ip} {} {}\r\n",
+                    src.port(),
+                    dst.port()
+                )
+                .into_bytes()
+            }
+            Self::V2 => {
+                let (family, mut addrs) = match (src_ip, dst_ip) {
+                    (IpAddr::V4(s), IpAddr::V4(d)) => {
+                        (PROXY_V2_TCP4, [s.octets(), d.octets()].concat())
+                    }
+                    (IpAddr::V6(s), IpAddr::V6(d)) => {
+                        (PROXY_V2_TCP6, [s.octets(), d.octets()].concat())
+                    }
+                    _ => unreachable!(),
+                };
+                addrs.extend_from_slice(&src.port().to_be_bytes());
+                addrs.extend_from_slice(&dst.port().to_be_bytes());
+
+                let mut header = PROXY_V2_SIGNATURE.to_vec();
+                header.push(PROXY_V2_VERSION_COMMAND);
+                header.push(family);
+                header.extend_from_slice(&(addrs.len() as u16).to_be_bytes());
+                header.extend_from_slice(&addrs);
+                header
+            }
+        }
+    }
+}
+
+/// Configuration of an inbound forwarder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InboundConfig {
+    /// vsock port of the enclave service to forward connections to.
+    pub port: u32,
+
+    /// PROXY protocol header to send ahead of each connection's data, if any.
+    pub proxy_protocol: Option<ProxyProtocol>,
+
+    /// Maximum number of connections forwarded at once. Connections accepted beyond the limit
+    /// are closed immediately.
+    pub max_connections: Option<usize>,
+
+    /// Number of times to retry connecting to the enclave, e.g. while it is restarting.
+    pub connect_retries: usize,
+
+    /// Delay between attempts to connect to the enclave.
+    pub retry_delay: Duration,
+}
+
+impl InboundConfig {
+    pub fn new(port: u32) -> Self {
+        Self {
+            port,
+            proxy_protocol: None,
+            max_connections: None,
+            connect_retries: 10,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
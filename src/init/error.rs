@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{heartbeat::HeartbeatError, nsm::NsmError};
+
+use std::{fmt, io, path::PathBuf};
+
+/// Error that may occur while initializing an enclave.
+#[derive(Debug)]
+pub enum InitError {
+    /// Unable to create a mount point.
+    MountPoint(PathBuf, io::Error),
+
+    /// Unable to mount a filesystem.
+    Mount(PathBuf, nix::Error),
+
+    /// Unable to bring up the loopback interface.
+    Loopback(io::Error),
+
+    /// Unable to get random bytes from the NSM.
+    Nsm(NsmError),
+
+    /// Unable to add entropy to the kernel RNG.
+    Entropy(io::Error),
+
+    /// Unable to read the application's command or environment.
+    CommandRead(PathBuf, io::Error),
+
+    /// No application command was provided.
+    EmptyCommand,
+
+    /// An environment entry is not of the form KEY=VALUE.
+    InvalidEnv(String),
+
+    /// Unable to change the root directory.
+    Chroot(PathBuf, io::Error),
+
+    /// Unable to change to the application's working directory.
+    WorkDir(PathBuf, io::Error),
+
+    /// Unable to signal readiness to the parent instance.
+    Heartbeat(HeartbeatError),
+
+    /// Unable to execute the application.
+    Exec(String, io::Error),
+
+    /// Unable to wait for the application or reap orphaned processes.
+    Wait(nix::Error),
+
+    /// Unable to power off the enclave once the application exited.
+    PowerOff(nix::Error),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::MountPoint(p, e) => {
+                format!("unable to create mount point {}: {e}", p.display())
+            }
+            Self::Mount(p, e) => format!("unable to mount {}: {e}", p.display()),
+            Self::Loopback(e) => format!("unable to bring up loopback interface: {e}"),
+            Self::Nsm(e) => format!("unable to seed RNG: {e}"),
+            Self::Entropy(e) => format!("unable to add entropy to kernel RNG: {e}"),
+            Self::CommandRead(p, e) => format!("unable to read {}: {e}", p.display()),
+            Self::EmptyCommand => "no application command provided".to_string(),
+            Self::InvalidEnv(s) => format!("invalid environment entry \"{s}\""),
+            Self::Chroot(p, e) => format!("unable to change root to {}: {e}", p.display()),
+            Self::WorkDir(p, e) => {
+                format!("unable to change to working directory {}: {e}", p.display())
+            }
+            Self::Heartbeat(e) => format!("unable to signal readiness: {e}"),
+            Self::Exec(c, e) => format!("unable to execute {c}: {e}"),
+            Self::Wait(e) => format!("unable to wait for application: {e}"),
+            Self::PowerOff(e) => format!("unable to power off enclave: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
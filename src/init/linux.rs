@@ -0,0 +1,76 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    fs::OpenOptions,
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+// Add entropy to the kernel RNG, crediting it to the entropy count.
+const RNDADDENTROPY: u64 = nix::request_code_write!(b'R', 0x03, 2 * size_of::<libc::c_int>()) as _;
+
+// Get and set the flags of a network interface.
+const SIOCGIFFLAGS: u64 = 0x8913;
+const SIOCSIFFLAGS: u64 = 0x8914;
+
+/// Network interface request, restricted to its flags.
+#[repr(C)]
+struct IfReq {
+    name: [libc::c_char; libc::IFNAMSIZ],
+    flags: libc::c_short,
+
+    // Pad to the size of the kernel's union of request fields.
+    _pad: [u8; 22],
+}
+
+/// Set the IFF_UP flag of a network interface.
+pub fn interface_up(name: &str) -> io::Result<()> {
+    if name.len() >= libc::IFNAMSIZ {
+        return Err(io::ErrorKind::InvalidInput.into());
+    }
+
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let sock = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut req = IfReq {
+        name: [0; libc::IFNAMSIZ],
+        flags: 0,
+        _pad: [0; 22],
+    };
+    for (dst, src) in req.name.iter_mut().zip(name.bytes()) {
+        *dst = src as libc::c_char;
+    }
+
+    if unsafe { libc::ioctl(sock.as_raw_fd(), SIOCGIFFLAGS as _, &mut req) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    req.flags |= libc::IFF_UP as libc::c_short;
+    if unsafe { libc::ioctl(sock.as_raw_fd(), SIOCSIFFLAGS as _, &req) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Mix bytes into the kernel RNG, crediting them with full entropy.
+pub fn add_entropy(bytes: &[u8]) -> io::Result<()> {
+    let random = OpenOptions::new().write(true).open("/dev/random")?;
+
+    // struct rand_pool_info: entropy count (in bits), buffer size (in bytes), then the buffer.
+    let mut info: Vec<libc::c_int> = vec![(bytes.len() * 8) as _, bytes.len() as _];
+    info.extend(bytes.chunks(size_of::<libc::c_int>()).map(|chunk| {
+        let mut word = [0u8; size_of::<libc::c_int>()];
+        word[..chunk.len()].copy_from_slice(chunk);
+        libc::c_int::from_ne_bytes(word)
+    }));
+
+    if unsafe { libc::ioctl(random.as_raw_fd(), RNDADDENTROPY as _, info.as_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
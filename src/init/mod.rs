@@ -0,0 +1,213 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Enclave-side initialization, replacing the init process of enclave images. The image's
+//! ramdisk holds the application's root filesystem at [`ROOTFS`], along with its command and
+//! environment in the [`CMD_FILE`] and [`ENV_FILE`] files (one entry per line), and optionally
+//! its working directory in the [`WORKDIR_FILE`] file.
+//!
+//! As PID 1, init runs the application as its child rather than replacing itself with it: it
+//! reaps the processes orphaned within the enclave, and powers the enclave off once the
+//! application exits (the kernel panics if PID 1 exits).
+
+mod error;
+mod linux;
+
+pub use error::*;
+
+use crate::{heartbeat, nsm::Nsm, transport::VsockTransport};
+
+use nix::{
+    errno::Errno,
+    mount::{mount, MsFlags},
+    sys::{
+        reboot::{reboot, RebootMode},
+        wait::{waitpid, WaitStatus},
+    },
+    unistd::{sync, Pid},
+};
+use std::{
+    convert::Infallible, fs, os::unix::process::CommandExt, path::Path, process, time::Duration,
+};
+
+type Result<T> = std::result::Result<T, InitError>;
+
+/// Directory of the application's root filesystem.
+pub const ROOTFS: &str = "/rootfs";
+
+/// File holding the application's command line.
+pub const CMD_FILE: &str = "/cmd";
+
+/// File holding the application's environment.
+pub const ENV_FILE: &str = "/env";
+
+/// File holding the application's working directory, within its root filesystem.
+pub const WORKDIR_FILE: &str = "/workdir";
+
+// Time to wait for the parent instance to acknowledge the readiness heartbeat.
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Filesystems mounted for the application: (type, mount point, flags, data).
+const MOUNTS: [(&str, &str, MsFlags, &str); 3] = [
+    (
+        "proc",
+        "proc",
+        MsFlags::MS_NOSUID
+            .union(MsFlags::MS_NODEV)
+            .union(MsFlags::MS_NOEXEC),
+        "",
+    ),
+    (
+        "sysfs",
+        "sys",
+        MsFlags::MS_NOSUID
+            .union(MsFlags::MS_NODEV)
+            .union(MsFlags::MS_NOEXEC),
+        "",
+    ),
+    (
+        "devtmpfs",
+        "dev",
+        MsFlags::MS_NOSUID.union(MsFlags::MS_NOEXEC),
+        "mode=0755",
+    ),
+];
+
+/// Initialize the enclave and run its application: mount filesystems into the application's
+/// root, switch to it, bring up the loopback interface, seed the RNG from the NSM, signal
+/// readiness to the parent instance and run the application's command. Orphaned processes are
+/// reaped until the application exits, and the enclave is then powered off. Only returns on
+/// failure.
+pub fn init() -> Result<Infallible> {
+    let command = Command::load(Path::new(CMD_FILE), Path::new(ENV_FILE))?;
+    let workdir = fs::read_to_string(WORKDIR_FILE).unwrap_or_default();
+    let workdir = match workdir.trim_end_matches('\n') {
+        "" => "/",
+        dir => dir,
+    };
+
+    let root = Path::new(ROOTFS);
+    mount_filesystems(root)?;
+    std::os::unix::fs::chroot(root).map_err(|e| InitError::Chroot(root.into(), e))?;
+    std::env::set_current_dir(workdir).map_err(|e| InitError::WorkDir(workdir.into(), e))?;
+
+    loopback_up()?;
+    seed_rng(&Nsm::open().map_err(InitError::Nsm)?)?;
+
+    heartbeat::signal(&VsockTransport, HEARTBEAT_TIMEOUT).map_err(InitError::Heartbeat)?;
+
+    reap_until(command.spawn()?)?;
+
+    sync();
+    reboot(RebootMode::RB_POWER_OFF).map_err(InitError::PowerOff)
+}
+
+/// Reap child processes, including those orphaned and reparented to this process, until the
+/// process with the given PID ends. Returns how it ended.
+pub fn reap_until(pid: u32) -> Result<WaitStatus> {
+    let pid = Pid::from_raw(pid as i32);
+    loop {
+        match waitpid(None, None) {
+            Ok(status) if status.pid() == Some(pid) => return Ok(status),
+            Ok(_) | Err(Errno::EINTR) => continue,
+            Err(e) => return Err(InitError::Wait(e)),
+        }
+    }
+}
+
+/// Mount /proc, /sys and /dev within a root directory, creating their mount points if needed.
+pub fn mount_filesystems(root: &Path) -> Result<()> {
+    for (fstype, dir, flags, data) in MOUNTS {
+        let target = root.join(dir);
+        fs::create_dir_all(&target).map_err(|e| InitError::MountPoint(target.clone(), e))?;
+
+        let data = (!data.is_empty()).then_some(data);
+        mount(Some(fstype), &target, Some(fstype), flags, data)
+            .map_err(|e| InitError::Mount(target, e))?;
+    }
+
+    Ok(())
+}
+
+/// Bring up the loopback interface, which enclaves rely on for local networking.
+pub fn loopback_up() -> Result<()> {
+    linux::interface_up("lo").map_err(InitError::Loopback)
+}
+
+/// Seed the kernel RNG with random bytes from the NSM, as enclaves have no other entropy source
+/// early in boot.
+pub fn seed_rng(nsm: &Nsm) -> Result<()> {
+    let bytes = nsm.get_random().map_err(InitError::Nsm)?;
+
+    linux::add_entropy(&bytes).map_err(InitError::Entropy)
+}
+
+/// The application's command line and environment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Command {
+    /// Program and arguments.
+    pub argv: Vec<String>,
+
+    /// Environment variables, as (key, value) pairs.
+    pub env: Vec<(String, String)>,
+}
+
+impl Command {
+    /// Read a command from its command line and environment files.
+    pub fn load(cmd: &Path, env: &Path) -> Result<Self> {
+        let read = |path: &Path| {
+            fs::read_to_string(path).map_err(|e| InitError::CommandRead(path.into(), e))
+        };
+
+        Self::parse(&read(cmd)?, &read(env)?)
+    }
+
+    /// Parse a command line and environment, each with one entry per line.
+    pub fn parse(cmd: &str, env: &str) -> Result<Self> {
+        let argv: Vec<String> = cmd.lines().map(String::from).collect();
+        if argv.first().is_none_or(|program| program.is_empty()) {
+            return Err(InitError::EmptyCommand);
+        }
+
+        let env = env
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.split_once('=')
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .ok_or_else(|| InitError::InvalidEnv(line.to_string()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { argv, env })
+    }
+
+    /// Run the command as a child process, with only its environment set, returning its PID. The
+    /// process is to be reaped with [`reap_until`].
+    pub fn spawn(&self) -> Result<u32> {
+        let program = self.argv.first().ok_or(InitError::EmptyCommand)?;
+
+        process::Command::new(program)
+            .args(&self.argv[1..])
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .spawn()
+            .map(|child| child.id())
+            .map_err(|e| InitError::Exec(program.clone(), e))
+    }
+
+    /// Replace the current process with the command, with only its environment set. Only returns
+    /// on failure.
+    pub fn exec(&self) -> InitError {
+        if self.argv.is_empty() {
+            return InitError::EmptyCommand;
+        }
+
+        let e = process::Command::new(&self.argv[0])
+            .args(&self.argv[1..])
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)))
+            .exec();
+
+        InitError::Exec(self.argv[0].clone(), e)
+    }
+}
@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! CMS EnvelopedData (RFC 5652) as returned in KMS CiphertextForRecipient: content encrypted with
+//! AES-256-CBC, under a key encrypted to the recipient's RSA key with RSAES-OAEP (SHA-256).
+
+use super::{error::KmsError, Result};
+
+use aes::Aes256;
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use cms::{
+    cert::x509::{
+        ext::pkix::SubjectKeyIdentifier,
+        spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoRef},
+    },
+    content_info::{CmsVersion, ContentInfo},
+    enveloped_data::{
+        EncryptedContentInfo, EnvelopedData, KeyTransRecipientInfo, RecipientIdentifier,
+        RecipientInfo, RecipientInfos,
+    },
+};
+use der::{
+    asn1::{ObjectIdentifier, OctetString, SetOfVec},
+    Any, Decode, Encode,
+};
+use ring::{
+    digest::{digest, SHA256},
+    rand::{SecureRandom, SystemRandom},
+};
+use rsa::{pkcs8::DecodePublicKey, Oaep, RsaPrivateKey, RsaPublicKey};
+use sha2::Sha256;
+
+const ID_ENVELOPED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.3");
+const ID_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.1");
+const ID_RSAES_OAEP: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.7");
+const ID_AES256_CBC: ObjectIdentifier = ObjectIdentifier::new_unwrap("2.16.840.1.101.3.4.1.42");
+
+// RSAES-OAEP-params selecting SHA-256 for both the hash and MGF1.
+const OAEP_SHA256_PARAMS: [u8; 49] = [
+    0x30, 0x2f, 0xa0, 0x0f, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+    0x01, 0x05, 0x00, 0xa1, 0x1c, 0x30, 0x1a, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01,
+    0x01, 0x08, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00,
+];
+
+/// Decrypt the content of an EnvelopedData structure with the recipient's key. BER encodings,
+/// such as the indefinite lengths produced by KMS, are accepted.
+pub fn open(envelope: &[u8], key: &RsaPrivateKey) -> Result<Vec<u8>> {
+    let err = |e: der::Error| KmsError::Envelope(e.to_string());
+
+    let der = ber_to_der(envelope)?;
+    let info = ContentInfo::from_der(&der).map_err(err)?;
+    if info.content_type != ID_ENVELOPED_DATA {
+        return Err(KmsError::Envelope("not EnvelopedData".to_string()));
+    }
+    let data: EnvelopedData = info.content.decode_as().map_err(err)?;
+
+    let content_key = data
+        .recip_infos
+        .0
+        .iter()
+        .find_map(|info| match info {
+            RecipientInfo::Ktri(ktri) => key
+                .decrypt(Oaep::new::<Sha256>(), ktri.enc_key.as_bytes())
+                .ok(),
+            _ => None,
+        })
+        .ok_or_else(|| KmsError::Envelope("no recipient matches key".to_string()))?;
+
+    let content = &data.encrypted_content;
+    if content.content_enc_alg.oid != ID_AES256_CBC {
+        return Err(KmsError::UnsupportedAlgorithm(
+            content.content_enc_alg.oid.to_string(),
+        ));
+    }
+    let iv: OctetString = content
+        .content_enc_alg
+        .parameters
+        .as_ref()
+        .ok_or_else(|| KmsError::Envelope("missing IV".to_string()))?
+        .decode_as()
+        .map_err(err)?;
+    let ciphertext = content
+        .encrypted_content
+        .as_ref()
+        .ok_or_else(|| KmsError::Envelope("missing encrypted content".to_string()))?;
+
+    cbc::Decryptor::<Aes256>::new_from_slices(&content_key, iv.as_bytes())
+        .map_err(|_| KmsError::Envelope("invalid key or IV size".to_string()))?
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext.as_bytes())
+        .map_err(|_| KmsError::Envelope("unable to decrypt content".to_string()))
+}
+
+/// Encrypt content to the holder of an RSA public key (DER-encoded SubjectPublicKeyInfo).
+pub fn seal(content: &[u8], public_key: &[u8]) -> Result<Vec<u8>> {
+    let err = |e: der::Error| KmsError::Envelope(e.to_string());
+
+    let rsa_key =
+        RsaPublicKey::from_public_key_der(public_key).map_err(|e| KmsError::Key(e.to_string()))?;
+    let spki = SubjectPublicKeyInfoRef::from_der(public_key).map_err(err)?;
+
+    let rng = SystemRandom::new();
+    let (mut content_key, mut iv) = ([0u8; 32], [0u8; 16]);
+    rng.fill(&mut content_key)
+        .and_then(|()| rng.fill(&mut iv))
+        .map_err(|_| KmsError::Key("unable to generate content key".to_string()))?;
+
+    let enc_key = rsa_key
+        .encrypt(
+            &mut rsa::rand_core::OsRng,
+            Oaep::new::<Sha256>(),
+            &content_key,
+        )
+        .map_err(|e| KmsError::Key(e.to_string()))?;
+    let ciphertext = cbc::Encryptor::<Aes256>::new(&content_key.into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(content);
+
+    // Identify the recipient by the SHA-256 digest of its public key.
+    let key_id = digest(&SHA256, spki.subject_public_key.raw_bytes());
+    let ktri = KeyTransRecipientInfo {
+        version: CmsVersion::V2,
+        rid: RecipientIdentifier::SubjectKeyIdentifier(SubjectKeyIdentifier(
+            OctetString::new(key_id.as_ref()).map_err(err)?,
+        )),
+        key_enc_alg: AlgorithmIdentifierOwned {
+            oid: ID_RSAES_OAEP,
+            parameters: Some(Any::from_der(&OAEP_SHA256_PARAMS).map_err(err)?),
+        },
+        enc_key: OctetString::new(enc_key).map_err(err)?,
+    };
+
+    let data = EnvelopedData {
+        version: CmsVersion::V2,
+        originator_info: None,
+        recip_infos: RecipientInfos(
+            SetOfVec::try_from(vec![RecipientInfo::Ktri(ktri)]).map_err(err)?,
+        ),
+        encrypted_content: EncryptedContentInfo {
+            content_type: ID_DATA,
+            content_enc_alg: AlgorithmIdentifierOwned {
+                oid: ID_AES256_CBC,
+                parameters: Some(
+                    Any::encode_from(&OctetString::new(iv).map_err(err)?).map_err(err)?,
+                ),
+            },
+            encrypted_content: Some(OctetString::new(ciphertext).map_err(err)?),
+        },
+        unprotected_attrs: None,
+    };
+
+    ContentInfo {
+        content_type: ID_ENVELOPED_DATA,
+        content: Any::encode_from(&data).map_err(err)?,
+    }
+    .to_der()
+    .map_err(err)
+}
+
+/// Convert a BER encoding to DER: indefinite lengths are made definite, and constructed
+/// (segmented) OCTET STRINGs are joined. Segmented content of the implicitly-tagged
+/// encryptedContent field ([0], holding only OCTET STRING segments) is joined likewise.
+pub fn ber_to_der(ber: &[u8]) -> Result<Vec<u8>> {
+    let (der, len) = convert(ber, 0)?;
+    if len != ber.len() {
+        return Err(KmsError::Envelope("trailing data".to_string()));
+    }
+
+    Ok(der)
+}
+
+// Maximum nesting of BER structures.
+const MAX_DEPTH: usize = 32;
+
+/// Convert the TLV at the start of input, returning its DER encoding and BER length.
+fn convert(input: &[u8], depth: usize) -> Result<(Vec<u8>, usize)> {
+    let malformed = || KmsError::Envelope("malformed BER".to_string());
+    if depth > MAX_DEPTH {
+        return Err(malformed());
+    }
+
+    // Identifier octets: low-tag-number form, or high-tag-number form continued while bit 8 is
+    // set.
+    let mut pos = 1;
+    if input.first().ok_or_else(malformed)? & 0x1f == 0x1f {
+        while input.get(pos).ok_or_else(malformed)? & 0x80 != 0 {
+            pos += 1;
+        }
+        pos += 1;
+    }
+    let tag = &input[..pos];
+    let constructed = tag[0] & 0x20 != 0;
+
+    let first = *input.get(pos).ok_or_else(malformed)?;
+    pos += 1;
+    let length = match first {
+        0x80 => None,
+        l if l & 0x80 == 0 => Some(l as usize),
+        l => {
+            let n = (l & 0x7f) as usize;
+            let bytes = input.get(pos..pos + n).ok_or_else(malformed)?;
+            pos += n;
+            Some(bytes.iter().try_fold(0usize, |len, b| {
+                len.checked_mul(256)
+                    .map(|len| len + *b as usize)
+                    .ok_or_else(malformed)
+            })?)
+        }
+    };
+
+    if !constructed {
+        let len = length.ok_or_else(malformed)?;
+        let end = pos.checked_add(len).ok_or_else(malformed)?;
+        let content = input.get(pos..end).ok_or_else(malformed)?;
+        return Ok((tlv(tag, content), end));
+    }
+
+    // Convert children, until the end of the content or an end-of-contents marker.
+    let end = length
+        .map(|len| pos.checked_add(len).ok_or_else(malformed))
+        .transpose()?;
+    if end.is_some_and(|end| end > input.len()) {
+        return Err(malformed());
+    }
+    let mut children = Vec::new();
+    loop {
+        match end {
+            Some(end) if pos == end => break,
+            None if input.get(pos..pos + 2) == Some(&[0, 0]) => {
+                pos += 2;
+                break;
+            }
+            _ => (),
+        }
+        let (child, len) = convert(&input[pos..end.unwrap_or(input.len())], depth + 1)?;
+        children.push(child);
+        pos += len;
+    }
+
+    // Segmented OCTET STRING (universal 4, or encryptedContent [0] implicitly tagged).
+    let segmented = tag == [0x24]
+        || (tag == [0xa0] && !children.is_empty()) && children.iter().all(|c| c[0] == 0x04);
+    if segmented {
+        let mut content = Vec::new();
+        for child in &children {
+            let (_, header) = header_len(child);
+            content.extend_from_slice(&child[header..]);
+        }
+        let primitive = if tag == [0x24] { 0x04 } else { 0x80 };
+
+        return Ok((tlv(&[primitive], &content), pos));
+    }
+
+    Ok((tlv(tag, &children.concat()), pos))
+}
+
+/// Encode a tag, definite length and content.
+fn tlv(tag: &[u8], content: &[u8]) -> Vec<u8> {
+    let mut out = tag.to_vec();
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+
+    out
+}
+
+/// Get the (tag, total header) lengths of a DER TLV with a single-byte tag.
+fn header_len(der: &[u8]) -> (usize, usize) {
+    match der[1] {
+        l if l & 0x80 == 0 => (1, 2),
+        l => (1, 2 + (l & 0x7f) as usize),
+    }
+}
@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::nsm::NsmError;
+
+use std::{fmt, io};
+
+/// Error that may occur while calling KMS.
+#[derive(Debug)]
+pub enum KmsError {
+    /// Unable to connect to KMS through the proxy.
+    Connect(io::Error),
+
+    /// Error reading or writing a message.
+    Io(io::Error),
+
+    /// Unable to establish a TLS session with KMS.
+    Tls(rustls::Error),
+
+    /// An HTTP message could not be parsed.
+    Http(String),
+
+    /// KMS rejected the request.
+    Service {
+        status: u16,
+        error_type: String,
+        message: String,
+    },
+
+    /// A request or response body is not valid JSON of the expected form.
+    Json(serde_json::Error),
+
+    /// A response field is not valid base64.
+    Base64(base64::DecodeError),
+
+    /// The enclave was unable to get an attestation document.
+    Attest(NsmError),
+
+    /// Unable to generate, parse or use an RSA key.
+    Key(String),
+
+    /// The CMS envelope could not be decoded or decrypted.
+    Envelope(String),
+
+    /// The CMS envelope uses an unsupported content encryption algorithm.
+    UnsupportedAlgorithm(String),
+}
+
+impl fmt::Display for KmsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Connect(e) => format!("unable to connect to KMS: {e}"),
+            Self::Io(e) => format!("KMS connection error: {e}"),
+            Self::Tls(e) => format!("KMS TLS error: {e}"),
+            Self::Http(e) => format!("invalid HTTP message: {e}"),
+            Self::Service {
+                status,
+                error_type,
+                message,
+            } => format!("KMS request failed ({status} {error_type}): {message}"),
+            Self::Json(e) => format!("invalid KMS message body: {e}"),
+            Self::Base64(e) => format!("invalid base64 in KMS message: {e}"),
+            Self::Attest(e) => format!("unable to attest enclave: {e}"),
+            Self::Key(e) => format!("RSA key error: {e}"),
+            Self::Envelope(e) => format!("invalid CMS envelope: {e}"),
+            Self::UnsupportedAlgorithm(oid) => {
+                format!("unsupported content encryption algorithm {oid}")
+            }
+        };
+
+        write!(f, "{}", msg)
+    }
+}
@@ -0,0 +1,356 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{
+    envelope, error::KmsError, http, sigv4, Credentials, DecryptInput, DecryptOutput, ErrorOutput,
+    Result, CONTENT_TYPE, DECRYPT_TARGET, KEY_ENCRYPTION_ALGORITHM,
+};
+use crate::{
+    attestation::{AttestationDoc, VerifyPolicy},
+    forward::{ShutdownHandle, SHUTDOWN_POLL_MS},
+    transport::{Listener, Stream},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, BufReader},
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+// Bound on the time a connection may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A symmetric key, usable only by enclaves with the given PCRs if any are given.
+struct FakeKey {
+    key: [u8; 32],
+    pcrs: BTreeMap<usize, Vec<u8>>,
+}
+
+/// Local stand-in for KMS, serving the Decrypt operation over plain HTTP. Ciphertexts are
+/// produced by [`FakeKms::encrypt`] and, like those of KMS, can only be decrypted by enclaves
+/// satisfying the key's PCR policy, to which plaintexts are returned as CMS envelopes.
+pub struct FakeKms {
+    root: Vec<u8>,
+    credentials: Credentials,
+    region: String,
+    keys: Mutex<HashMap<String, FakeKey>>,
+    shutdown: ShutdownHandle,
+}
+
+impl FakeKms {
+    /// Accept requests signed with credentials for region, with attestation documents chaining up
+    /// to root.
+    pub fn new(root: Vec<u8>, credentials: Credentials, region: &str) -> Self {
+        Self {
+            root,
+            credentials,
+            region: region.to_string(),
+            keys: Mutex::new(HashMap::new()),
+            shutdown: ShutdownHandle::default(),
+        }
+    }
+
+    /// Create a key, returning its ID. If PCRs are given, the key policy only allows decryption
+    /// by enclaves with these PCR values.
+    pub fn create_key(&self, pcrs: BTreeMap<usize, Vec<u8>>) -> Result<String> {
+        let mut key = [0u8; 32];
+        let mut id = [0u8; 16];
+        let rng = SystemRandom::new();
+        rng.fill(&mut key)
+            .and_then(|()| rng.fill(&mut id))
+            .map_err(|_| KmsError::Key("unable to generate key".to_string()))?;
+
+        let id: String = id.iter().map(|b| format!("{b:02x}")).collect();
+        self.keys
+            .lock()
+            .unwrap()
+            .insert(id.clone(), FakeKey { key, pcrs });
+
+        Ok(id)
+    }
+
+    /// Encrypt plaintext with a key, binding the encryption context to the ciphertext.
+    pub fn encrypt(
+        &self,
+        key_id: &str,
+        plaintext: &[u8],
+        context: &BTreeMap<String, String>,
+    ) -> Result<Vec<u8>> {
+        let keys = self.keys.lock().unwrap();
+        let key = keys
+            .get(key_id)
+            .ok_or_else(|| KmsError::Key(format!("no key {key_id}")))?;
+
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| KmsError::Key("unable to generate nonce".to_string()))?;
+
+        let mut ciphertext = plaintext.to_vec();
+        aead_key(&key.key)
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad(context)),
+                &mut ciphertext,
+            )
+            .map_err(|_| KmsError::Key("unable to encrypt".to_string()))?;
+
+        Ok([
+            &[key_id.len() as u8],
+            key_id.as_bytes(),
+            &nonce,
+            &ciphertext,
+        ]
+        .concat())
+    }
+
+    /// Get a handle that can be used to shut down the service.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Accept and serve connections, each carrying one request, until the service is shut down.
+    pub fn run<L: Listener>(&self, listener: &L) -> Result<()> {
+        thread::scope(|s| loop {
+            if self.shutdown.is_shutdown() {
+                return Ok(());
+            }
+
+            let mut fds = [PollFd::new(listener.as_raw_fd(), PollFlags::POLLIN)];
+            match poll(&mut fds, SHUTDOWN_POLL_MS) {
+                Ok(0) | Err(Errno::EINTR) => continue,
+                Ok(_) => (),
+                Err(e) => return Err(KmsError::Io(e.into())),
+            }
+
+            match listener.accept() {
+                Ok(stream) => {
+                    s.spawn(move || self.serve(stream));
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(KmsError::Io(e)),
+            }
+        })
+    }
+
+    /// Serve a request received over a connection.
+    pub fn serve<S: Stream>(&self, mut stream: S) -> Result<()> {
+        http::set_timeout(stream.as_raw_fd(), REQUEST_TIMEOUT)?;
+        let request = http::Message::read_from(&mut BufReader::new(
+            stream.try_clone().map_err(KmsError::Io)?,
+        ))?;
+
+        let (status, body) = match self.handle(&request) {
+            Ok(body) => (200, body),
+            Err((status, error_type, message)) => {
+                let error = ErrorOutput {
+                    error_type: error_type.to_string(),
+                    message,
+                };
+                (status, serde_json::to_vec(&error).map_err(KmsError::Json)?)
+            }
+        };
+
+        let reason = if status == 200 { "OK" } else { "Bad Request" };
+        http::Message {
+            start: format!("HTTP/1.1 {status} {reason}"),
+            headers: vec![("Content-Type".to_string(), CONTENT_TYPE.to_string())],
+            body,
+        }
+        .write_to(&mut stream)
+    }
+
+    /// Handle a request, returning the response body or the error status, type and message.
+    fn handle(
+        &self,
+        request: &http::Message,
+    ) -> std::result::Result<Vec<u8>, (u16, &'static str, String)> {
+        self.authenticate(request)?;
+
+        let target = request.header("X-Amz-Target").unwrap_or_default();
+        if target != DECRYPT_TARGET {
+            return Err((
+                400,
+                "UnknownOperationException",
+                format!("unsupported operation {target}"),
+            ));
+        }
+
+        let input: DecryptInput = serde_json::from_slice(&request.body)
+            .map_err(|e| (400, "SerializationException", e.to_string()))?;
+        let output = self.decrypt(input)?;
+
+        serde_json::to_vec(&output).map_err(|e| (500, "KMSInternalException", e.to_string()))
+    }
+
+    /// Check the request's signature.
+    fn authenticate(
+        &self,
+        request: &http::Message,
+    ) -> std::result::Result<(), (u16, &'static str, String)> {
+        let invalid = |msg: &str| (400, "InvalidSignatureException", msg.to_string());
+
+        let authorization = request
+            .header("Authorization")
+            .ok_or_else(|| invalid("missing Authorization header"))?;
+        let signed_headers = authorization
+            .split(", ")
+            .find_map(|part| part.strip_prefix("SignedHeaders="))
+            .ok_or_else(|| invalid("missing SignedHeaders"))?;
+        let headers = signed_headers
+            .split(';')
+            .map(|name| {
+                request
+                    .header(name)
+                    .map(|value| (name, value))
+                    .ok_or_else(|| invalid("missing signed header"))
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let (method, path) = request
+            .start
+            .split_once(' ')
+            .map(|(method, rest)| (method, rest.split(' ').next().unwrap_or_default()))
+            .ok_or_else(|| invalid("invalid request line"))?;
+        let expected = sigv4::authorization(
+            &self.credentials,
+            &self.region,
+            "kms",
+            &sigv4::SigningRequest {
+                method,
+                path,
+                query: "",
+                headers: &headers,
+                body: &request.body,
+            },
+        );
+
+        if authorization != expected {
+            return Err(invalid("signature does not match"));
+        }
+
+        Ok(())
+    }
+
+    fn decrypt(
+        &self,
+        input: DecryptInput,
+    ) -> std::result::Result<DecryptOutput, (u16, &'static str, String)> {
+        let invalid_ciphertext = || (400, "InvalidCiphertextException", String::new());
+        let denied = |msg: String| (400, "AccessDeniedException", msg);
+
+        let blob = BASE64
+            .decode(&input.ciphertext_blob)
+            .map_err(|_| invalid_ciphertext())?;
+        let (&id_len, rest) = blob.split_first().ok_or_else(invalid_ciphertext)?;
+        if rest.len() < id_len as usize + NONCE_LEN {
+            return Err(invalid_ciphertext());
+        }
+        let (key_id, rest) = rest.split_at(id_len as usize);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let key_id = String::from_utf8_lossy(key_id).into_owned();
+
+        if input.key_id.as_ref().is_some_and(|id| *id != key_id) {
+            return Err((400, "IncorrectKeyException", String::new()));
+        }
+
+        let keys = self.keys.lock().unwrap();
+        let key = keys
+            .get(&key_id)
+            .ok_or_else(|| (400, "NotFoundException", format!("no key {key_id}")))?;
+
+        // Enforce the key policy before decrypting.
+        let recipient_key = match &input.recipient {
+            Some(recipient) => {
+                if recipient.key_encryption_algorithm != KEY_ENCRYPTION_ALGORITHM {
+                    return Err((
+                        400,
+                        "ValidationException",
+                        format!(
+                            "unsupported key encryption algorithm {}",
+                            recipient.key_encryption_algorithm
+                        ),
+                    ));
+                }
+                let document = BASE64
+                    .decode(&recipient.attestation_document)
+                    .map_err(|e| (400, "ValidationException", e.to_string()))?;
+
+                let mut policy = VerifyPolicy::new(self.root.clone());
+                policy.pcrs = key.pcrs.clone();
+                let doc = AttestationDoc::verify(&document, &policy, SystemTime::now())
+                    .map_err(|e| denied(e.to_string()))?;
+                Some(doc.public_key.ok_or_else(|| {
+                    (
+                        400,
+                        "ValidationException",
+                        "attestation document has no public key".to_string(),
+                    )
+                })?)
+            }
+            None if !key.pcrs.is_empty() => {
+                return Err(denied(format!(
+                    "key {key_id} requires an attested recipient"
+                )))
+            }
+            None => None,
+        };
+
+        let mut plaintext = ciphertext.to_vec();
+        let len = aead_key(&key.key)
+            .open_in_place(
+                Nonce::try_assume_unique_for_key(nonce).map_err(|_| invalid_ciphertext())?,
+                Aad::from(aad(&input.encryption_context)),
+                &mut plaintext,
+            )
+            .map_err(|_| invalid_ciphertext())?
+            .len();
+        plaintext.truncate(len);
+
+        let mut output = DecryptOutput {
+            key_id,
+            encryption_algorithm: "SYMMETRIC_DEFAULT".to_string(),
+            ..Default::default()
+        };
+        match recipient_key {
+            Some(public_key) => {
+                let envelope = envelope::seal(&plaintext, &public_key)
+                    .map_err(|e| (400, "ValidationException", e.to_string()))?;
+                output.ciphertext_for_recipient = Some(BASE64.encode(indefinite(envelope)));
+            }
+            None => output.plaintext = Some(BASE64.encode(plaintext)),
+        }
+
+        Ok(output)
+    }
+}
+
+fn aead_key(key: &[u8; 32]) -> LessSafeKey {
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).unwrap())
+}
+
+/// Encode an encryption context as additional authenticated data.
+fn aad(context: &BTreeMap<String, String>) -> Vec<u8> {
+    serde_json::to_vec(context).unwrap()
+}
+
+/// Re-encode the outermost length of a DER structure as indefinite, as KMS does, so that clients
+/// must handle BER.
+fn indefinite(der: Vec<u8>) -> Vec<u8> {
+    let header = match der[1] {
+        l if l & 0x80 == 0 => 2,
+        l => 2 + (l & 0x7f) as usize,
+    };
+
+    [&[der[0], 0x80], &der[header..], &[0, 0]].concat()
+}
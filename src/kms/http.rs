@@ -0,0 +1,143 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Minimal HTTP/1.1 messages, one per connection, as exchanged with KMS.
+
+use super::{error::KmsError, Result};
+
+use std::{
+    io::{BufRead, Read, Write},
+    os::fd::RawFd,
+    time::Duration,
+};
+
+// Maximum size of a message's head (start line and headers) and of its body.
+const MAX_HEAD_SIZE: usize = 64 << 10;
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+/// An HTTP request or response.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Message {
+    /// Request line or status line.
+    pub start: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Message {
+    /// Get the value of a header, by case-insensitive name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Get the status code of a response.
+    pub fn status(&self) -> Result<u16> {
+        self.start
+            .split(' ')
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| KmsError::Http(format!("invalid status line: {}", self.start)))
+    }
+
+    /// Read a message, with a body of Content-Length bytes or, lacking that header, extending to
+    /// the end of the stream.
+    pub fn read_from<R: BufRead>(reader: &mut R) -> Result<Self> {
+        let mut head = reader.take(MAX_HEAD_SIZE as u64);
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if head.read_line(&mut line).map_err(KmsError::Io)? == 0 {
+                return Err(KmsError::Http("truncated message head".to_string()));
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line.to_string());
+        }
+        let reader = head.into_inner();
+
+        let mut lines = lines.into_iter();
+        let start = lines
+            .next()
+            .ok_or_else(|| KmsError::Http("missing start line".to_string()))?;
+        let headers = lines
+            .map(|line| {
+                line.split_once(':')
+                    .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+                    .ok_or_else(|| KmsError::Http(format!("invalid header: {line}")))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut message = Self {
+            start,
+            headers,
+            body: Vec::new(),
+        };
+        if message.header("Transfer-Encoding").is_some() {
+            return Err(KmsError::Http("unsupported transfer encoding".to_string()));
+        }
+
+        match message.header("Content-Length") {
+            Some(len) => {
+                let len: usize = len
+                    .parse()
+                    .map_err(|_| KmsError::Http(format!("invalid content length: {len}")))?;
+                if len > MAX_BODY_SIZE {
+                    return Err(KmsError::Http(format!("body of {len} bytes is too large")));
+                }
+                message.body.resize(len, 0);
+                reader.read_exact(&mut message.body).map_err(KmsError::Io)?;
+            }
+            None => {
+                reader
+                    .take(MAX_BODY_SIZE as u64)
+                    .read_to_end(&mut message.body)
+                    .map_err(KmsError::Io)?;
+            }
+        }
+
+        Ok(message)
+    }
+
+    /// Write the message, adding its Content-Length header.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let mut head = format!("{}\r\n", self.start);
+        for (k, v) in &self.headers {
+            head.push_str(&format!("{k}: {v}\r\n"));
+        }
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", self.body.len()));
+
+        writer
+            .write_all(&[head.as_bytes(), &self.body].concat())
+            .and_then(|()| writer.flush())
+            .map_err(KmsError::Io)
+    }
+}
+
+/// Bound the time blocking reads and writes may take on a socket.
+pub(crate) fn set_timeout(fd: RawFd, timeout: Duration) -> Result<()> {
+    let tv = libc::timeval {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+    };
+
+    for opt in [libc::SO_RCVTIMEO, libc::SO_SNDTIMEO] {
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                opt,
+                &tv as *const _ as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            return Err(KmsError::Connect(std::io::Error::last_os_error()));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Client of the AWS KMS Decrypt operation for enclaves. Requests carry an attestation document
+//! binding an ephemeral RSA key, so that KMS (whose key policy may require specific PCRs)
+//! returns the plaintext encrypted to that key as a CMS envelope, which only the enclave can
+//! open. Enclaves have no network, so requests are sent over vsock to a proxy on the parent
+//! instance forwarding them to KMS (see [`crate::proxy`]).
+
+pub(crate) mod envelope;
+mod error;
+mod fake;
+mod http;
+pub mod sigv4;
+mod types;
+
+pub use envelope::{ber_to_der, open as open_envelope};
+pub use error::*;
+pub use fake::FakeKms;
+pub use types::*;
+
+use crate::{
+    nsm::{AttestationRequest, Attester},
+    transport::{Addr, Transport},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rsa::{pkcs8::EncodePublicKey, RsaPrivateKey};
+use rustls::{pki_types::ServerName, ClientConnection, StreamOwned};
+use std::{
+    io::{BufReader, Read, Write},
+    os::fd::AsRawFd,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+type Result<T> = std::result::Result<T, KmsError>;
+
+/// Key encryption algorithm of the envelopes returned to recipients.
+pub const KEY_ENCRYPTION_ALGORITHM: &str = "RSAES_OAEP_SHA_256";
+
+// Size of the recipient's ephemeral RSA key, in bits.
+const RECIPIENT_KEY_BITS: usize = 2048;
+
+// Default bound on the time taken by each read or write.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Target of the Decrypt operation in the JSON 1.1 protocol.
+const DECRYPT_TARGET: &str = "TrentService.Decrypt";
+const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+/// KMS client sending requests through a vsock proxy to the configured endpoint.
+pub struct KmsClient<T: Transport> {
+    transport: T,
+    proxy: Addr,
+    config: KmsConfig,
+    tls: Option<Arc<rustls::ClientConfig>>,
+    timeout: Duration,
+}
+
+impl<T: Transport> KmsClient<T> {
+    /// Send requests in plaintext to the proxy at the given address. KMS endpoints only accept
+    /// HTTPS, so this is only suitable for local services unless TLS is enabled with
+    /// [`KmsClient::with_tls`].
+    pub fn new(transport: T, proxy: Addr, config: KmsConfig) -> Self {
+        Self {
+            transport,
+            proxy,
+            config,
+            tls: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Establish a TLS session with the endpoint, through the proxy, for each request.
+    pub fn with_tls(mut self, tls: Arc<rustls::ClientConfig>) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Set the bound on the time taken by each read or write.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Decrypt a ciphertext, with the plaintext returned encrypted to a fresh key bound into an
+    /// attestation document from attester.
+    pub fn decrypt<A: Attester + ?Sized>(
+        &self,
+        attester: &A,
+        request: &DecryptRequest,
+    ) -> Result<Vec<u8>> {
+        let key = RsaPrivateKey::new(&mut rsa::rand_core::OsRng, RECIPIENT_KEY_BITS)
+            .map_err(|e| KmsError::Key(e.to_string()))?;
+        let public_key = key
+            .to_public_key()
+            .to_public_key_der()
+            .map_err(|e| KmsError::Key(e.to_string()))?;
+
+        let document = attester
+            .attest(&AttestationRequest {
+                public_key: Some(public_key.as_bytes()),
+                ..Default::default()
+            })
+            .map_err(KmsError::Attest)?;
+
+        let input = DecryptInput {
+            ciphertext_blob: BASE64.encode(&request.ciphertext_blob),
+            key_id: request.key_id.clone(),
+            encryption_context: request.encryption_context.clone(),
+            recipient: Some(Recipient {
+                key_encryption_algorithm: KEY_ENCRYPTION_ALGORITHM.to_string(),
+                attestation_document: BASE64.encode(document),
+            }),
+        };
+        let body = serde_json::to_vec(&input).map_err(KmsError::Json)?;
+
+        let output: DecryptOutput =
+            serde_json::from_slice(&self.call(DECRYPT_TARGET, body)?).map_err(KmsError::Json)?;
+        let envelope = output
+            .ciphertext_for_recipient
+            .ok_or_else(|| KmsError::Http("response has no CiphertextForRecipient".to_string()))?;
+
+        envelope::open(&BASE64.decode(envelope).map_err(KmsError::Base64)?, &key)
+    }
+
+    /// Make a signed call to an operation, returning the response body.
+    fn call(&self, target: &str, body: Vec<u8>) -> Result<Vec<u8>> {
+        let config = &self.config;
+        let date = sigv4::amz_date(SystemTime::now());
+
+        let mut headers = vec![
+            ("Host", config.host.as_str()),
+            ("X-Amz-Date", date.as_str()),
+            ("X-Amz-Target", target),
+            ("Content-Type", CONTENT_TYPE),
+        ];
+        if let Some(token) = &config.credentials.session_token {
+            headers.push(("X-Amz-Security-Token", token));
+        }
+        let authorization = sigv4::authorization(
+            &config.credentials,
+            &config.region,
+            "kms",
+            &sigv4::SigningRequest {
+                method: "POST",
+                path: "/",
+                query: "",
+                headers: &headers,
+                body: &body,
+            },
+        );
+
+        let mut request = http::Message {
+            start: "POST / HTTP/1.1".to_string(),
+            headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body,
+        };
+        request.headers.extend([
+            ("Authorization".to_string(), authorization),
+            ("Connection".to_string(), "close".to_string()),
+        ]);
+
+        let stream = self
+            .transport
+            .connect(self.proxy)
+            .map_err(KmsError::Connect)?;
+        http::set_timeout(stream.as_raw_fd(), self.timeout)?;
+
+        let response = match &self.tls {
+            Some(tls) => {
+                let name = ServerName::try_from(config.host.clone())
+                    .map_err(|e| KmsError::Http(format!("invalid host name: {e}")))?;
+                let conn = ClientConnection::new(tls.clone(), name).map_err(KmsError::Tls)?;
+                exchange(StreamOwned::new(conn, stream), &request)?
+            }
+            None => exchange(stream, &request)?,
+        };
+
+        match response.status()? {
+            200 => Ok(response.body),
+            status => Err(service_error(status, &response.body)),
+        }
+    }
+}
+
+/// Send a request and read the response.
+fn exchange<S: Read + Write>(mut stream: S, request: &http::Message) -> Result<http::Message> {
+    request.write_to(&mut stream)?;
+    http::Message::read_from(&mut BufReader::new(stream))
+}
+
+/// Convert an error response to an error.
+fn service_error(status: u16, body: &[u8]) -> KmsError {
+    match serde_json::from_slice::<ErrorOutput>(body) {
+        Ok(e) => KmsError::Service {
+            status,
+            // The type may be qualified by its namespace (e.g. com.amazonaws.kms#...).
+            error_type: e
+                .error_type
+                .rsplit('#')
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            message: e.message,
+        },
+        Err(_) => KmsError::Service {
+            status,
+            error_type: String::new(),
+            message: String::from_utf8_lossy(body).into_owned(),
+        },
+    }
+}
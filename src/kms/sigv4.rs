@@ -0,0 +1,116 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! AWS Signature Version 4 request signing.
+
+use super::Credentials;
+
+use ring::{
+    digest::{digest, SHA256},
+    hmac::{self, HMAC_SHA256},
+};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A request to be signed.
+#[derive(Clone, Debug)]
+pub struct SigningRequest<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+
+    /// Canonical (sorted and encoded) query string.
+    pub query: &'a str,
+
+    /// Headers to sign, including host and x-amz-date.
+    pub headers: &'a [(&'a str, &'a str)],
+    pub body: &'a [u8],
+}
+
+/// Format a time as the ISO 8601 basic format used in x-amz-date (e.g. 20150830T123600Z).
+pub fn amz_date(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // Convert days since the epoch to a civil date (Howard Hinnant's algorithm).
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}T{:02}{:02}{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Compute the Authorization header of a request to a service in a region, signed at the time
+/// given by the request's x-amz-date header.
+pub fn authorization(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    request: &SigningRequest,
+) -> String {
+    let mut headers: Vec<(String, &str)> = request
+        .headers
+        .iter()
+        .map(|(k, v)| (k.to_ascii_lowercase(), v.trim()))
+        .collect();
+    headers.sort();
+
+    let amz_date = headers
+        .iter()
+        .find(|(k, _)| k == "x-amz-date")
+        .map_or("", |(_, v)| v);
+    let date = &amz_date[..amz_date.len().min(8)];
+
+    let canonical_headers: String = headers.iter().map(|(k, v)| format!("{k}:{v}\n")).collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{canonical_headers}\n{signed_headers}\n{}",
+        request.method,
+        request.path,
+        request.query,
+        hex(digest(&SHA256, request.body).as_ref())
+    );
+
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex(digest(&SHA256, canonical_request.as_bytes()).as_ref())
+    );
+
+    let key = [date, region, service, "aws4_request"].iter().fold(
+        format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+        |key, part| sign(&key, part.as_bytes()),
+    );
+    let signature = hex(&sign(&key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        credentials.access_key_id
+    )
+}
+
+fn sign(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(HMAC_SHA256, key), data)
+        .as_ref()
+        .to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
@@ -0,0 +1,104 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt};
+
+/// AWS credentials used to sign requests.
+#[derive(Clone, Default)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+
+    /// Token of temporary credentials, such as those of the parent instance's role.
+    pub session_token: Option<String>,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("access_key_id", &self.access_key_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Location of and credentials for a KMS endpoint.
+#[derive(Clone, Debug)]
+pub struct KmsConfig {
+    pub region: String,
+
+    /// Host name of the endpoint, as sent in the Host header and used for TLS.
+    pub host: String,
+    pub credentials: Credentials,
+}
+
+impl KmsConfig {
+    /// Use the regional KMS endpoint.
+    pub fn new(region: &str, credentials: Credentials) -> Self {
+        Self {
+            region: region.to_string(),
+            host: format!("kms.{region}.amazonaws.com"),
+            credentials,
+        }
+    }
+}
+
+/// Parameters of a Decrypt request.
+#[derive(Clone, Debug, Default)]
+pub struct DecryptRequest {
+    pub ciphertext_blob: Vec<u8>,
+
+    /// Key expected to have encrypted the ciphertext.
+    pub key_id: Option<String>,
+
+    /// Encryption context given when the ciphertext was encrypted.
+    pub encryption_context: BTreeMap<String, String>,
+}
+
+/// Body of a Decrypt request, with binary fields base64-encoded.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct DecryptInput {
+    pub ciphertext_blob: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub encryption_context: BTreeMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<Recipient>,
+}
+
+/// Enclave to which the plaintext is to be encrypted, rather than returned.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct Recipient {
+    pub key_encryption_algorithm: String,
+    pub attestation_document: String,
+}
+
+/// Body of a Decrypt response.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct DecryptOutput {
+    pub key_id: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plaintext: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ciphertext_for_recipient: Option<String>,
+
+    pub encryption_algorithm: String,
+}
+
+/// Body of an error response.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct ErrorOutput {
+    #[serde(rename = "__type")]
+    pub error_type: String,
+
+    #[serde(default, alias = "Message")]
+    pub message: String,
+}
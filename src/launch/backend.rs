@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{hugepage, linux::huge_page_flag, types::HugePageCount};
+
+use std::{
+    ffi::CString,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io,
+    os::fd::{AsRawFd, FromRawFd, RawFd},
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Provides the huge pages backing enclave memory regions.
+pub trait MemoryBackend: fmt::Debug {
+    /// Get the huge page sizes the backend is able to map, along with the number of free pages of
+    /// each size.
+    fn available(&self) -> io::Result<Vec<HugePageCount>>;
+
+    /// Map size bytes of readable and writable memory backed by huge pages of page_size bytes,
+    /// returning the address of the mapping. The mapping is released with munmap(2).
+    fn map(&self, page_size: usize, size: usize) -> io::Result<*mut libc::c_void>;
+}
+
+/// Anonymous private huge page mappings (MAP_HUGETLB).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AnonymousBackend;
+
+impl MemoryBackend for AnonymousBackend {
+    fn available(&self) -> io::Result<Vec<HugePageCount>> {
+        hugepage::available()
+    }
+
+    fn map(&self, page_size: usize, size: usize) -> io::Result<*mut libc::c_void> {
+        let flag = huge_page_flag(page_size).ok_or(io::ErrorKind::Unsupported)?;
+
+        mmap(
+            size,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB | flag,
+            -1,
+        )
+    }
+}
+
+/// Files created on a mounted hugetlbfs. The page size is set by the mount's pagesize option, and
+/// its size and min_size options allow pages to be reserved for (and limited to) a service.
+#[derive(Clone, Debug)]
+pub struct HugetlbfsBackend {
+    /// Directory on the hugetlbfs mount in which to create the backing files.
+    pub dir: PathBuf,
+}
+
+impl HugetlbfsBackend {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Get the mount's page size and the number of pages free on it.
+    fn statfs(&self) -> io::Result<libc::statfs> {
+        let dir = File::open(&self.dir)?;
+
+        let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::fstatfs(dir.as_raw_fd(), &mut stat) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        if stat.f_type != libc::HUGETLBFS_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not on a hugetlbfs mount", self.dir.display()),
+            ));
+        }
+
+        Ok(stat)
+    }
+}
+
+impl MemoryBackend for HugetlbfsBackend {
+    fn available(&self) -> io::Result<Vec<HugePageCount>> {
+        let stat = self.statfs()?;
+        let page_size = stat.f_bsize as usize;
+
+        // Mounts without a size limit report no blocks, and draw from the host's free pages.
+        let count = if stat.f_blocks == 0 {
+            hugepage::available()?
+                .into_iter()
+                .find(|a| a.page_size == page_size)
+                .map(|a| a.count)
+                .unwrap_or(0)
+        } else {
+            stat.f_bfree as usize
+        };
+
+        Ok(vec![HugePageCount { page_size, count }])
+    }
+
+    fn map(&self, page_size: usize, size: usize) -> io::Result<*mut libc::c_void> {
+        if self.statfs()?.f_bsize as usize != page_size {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+
+        let path = self.dir.join(unique_name());
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        // The pages remain allocated for as long as they are mapped, so the file is unlinked
+        // right away, before any mapping that an unlink error would leak.
+        fs::remove_file(&path)?;
+        file.set_len(size as u64)?;
+
+        mmap(size, libc::MAP_SHARED, file.as_raw_fd())
+    }
+}
+
+/// Anonymous files created with memfd_create(MFD_HUGETLB).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MemfdBackend;
+
+impl MemoryBackend for MemfdBackend {
+    fn available(&self) -> io::Result<Vec<HugePageCount>> {
+        hugepage::available()
+    }
+
+    fn map(&self, page_size: usize, size: usize) -> io::Result<*mut libc::c_void> {
+        let flag = huge_page_flag(page_size).ok_or(io::ErrorKind::Unsupported)?;
+
+        let name = CString::new(unique_name()).unwrap();
+        let fd = unsafe {
+            libc::memfd_create(
+                name.as_ptr(),
+                libc::MFD_CLOEXEC | libc::MFD_HUGETLB | flag as libc::c_uint,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Take ownership of the descriptor so that it is closed once the memory is mapped.
+        let file = unsafe { File::from_raw_fd(fd) };
+        file.set_len(size as u64)?;
+
+        mmap(size, libc::MAP_SHARED, file.as_raw_fd())
+    }
+}
+
+/// Map size bytes of memory with the given flags. The pages are populated up front so that their
+/// physical addresses can be inspected.
+fn mmap(size: usize, flags: libc::c_int, fd: RawFd) -> io::Result<*mut libc::c_void> {
+    let addr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            flags | libc::MAP_POPULATE,
+            fd,
+            0,
+        )
+    };
+
+    if addr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(addr)
+}
+
+/// Generate a name for a backing file that is unique to this process.
+fn unique_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    format!(
+        "nitro-enclaves-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::error::*;
+
+use rand::{rngs::OsRng, TryRngCore};
+use std::{
+    collections::BTreeSet,
+    ops::RangeInclusive,
+    sync::{Mutex, MutexGuard},
+};
+
+const VMADDR_CID_PARENT: u32 = 3;
+
+/// CIDs reserved by this process: those of the enclaves it started (or is starting), and any
+/// marked with [`reserve`]. The driver does not expose the CIDs of enclaves started by other
+/// processes, so this is not a system-wide registry.
+static RESERVED_CIDS: Mutex<BTreeSet<u64>> = Mutex::new(BTreeSet::new());
+
+/// Policy for choosing the CID of an enclave when starting it.
+///
+/// CIDs are reserved within this process while the enclaves using them run, so that policies
+/// skip them. The reservations are per process, as the driver does not expose which CIDs other
+/// processes' enclaves use: those CIDs are only found to be in use when the driver rejects them,
+/// unless reserved with [`reserve_cid`].
+///
+/// [`reserve_cid`]: crate::launch::reserve_cid
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CidPolicy {
+    /// Use the supplied CID if it is valid. Otherwise, use a randomly-generated CID.
+    Preferred(Option<u64>),
+
+    /// Use the supplied CID, failing if it is invalid.
+    Strict(u64),
+
+    /// Use a randomly-generated CID, retrying with a new CID up to the given number of times if
+    /// the chosen CID is already in use by another enclave.
+    Random { retries: usize },
+
+    /// Use the first valid CID within the range that is not in use by another enclave.
+    Range(RangeInclusive<u64>),
+}
+
+impl Default for CidPolicy {
+    fn default() -> Self {
+        Self::Preferred(None)
+    }
+}
+
+impl From<Option<u64>> for CidPolicy {
+    fn from(cid: Option<u64>) -> Self {
+        Self::Preferred(cid)
+    }
+}
+
+impl CidPolicy {
+    /// Start an enclave with a CID chosen according to the policy. start is called with each
+    /// candidate CID, reserved beforehand, until the policy is satisfied or gives up, and returns
+    /// the CID the enclave was started with. That CID remains reserved.
+    pub fn start_with(
+        self,
+        mut start: impl FnMut(u64) -> Result<u64, LaunchError>,
+    ) -> Result<u64, LaunchError> {
+        let mut start = |cid| start_reserved(cid, &mut start);
+
+        match self {
+            Self::Preferred(cid) => {
+                // Ensure that a valid CID is used. If the supplied CID is invalid,
+                // randomly-generate a valid one.
+                let cid = match cid {
+                    Some(cid) if is_valid(cid) => cid,
+                    _ => random()?,
+                };
+
+                start(cid)
+            }
+            Self::Strict(cid) => {
+                if !is_valid(cid) {
+                    return Err(LaunchError::InvalidCid(cid));
+                }
+
+                start(cid)
+            }
+            Self::Random { retries } => {
+                let mut attempt = 0;
+                loop {
+                    match start(random()?) {
+                        Err(LaunchError::Ioctl(IoctlError::InvalidEnclaveCid))
+                            if attempt < retries =>
+                        {
+                            attempt += 1
+                        }
+                        ret => break ret,
+                    }
+                }
+            }
+            Self::Range(range) => {
+                for cid in range.filter(|cid| is_valid(*cid)) {
+                    match start(cid) {
+                        // The CID is in use, either by an enclave of this process or another.
+                        Err(LaunchError::CidInUse(_))
+                        | Err(LaunchError::Ioctl(IoctlError::InvalidEnclaveCid)) => continue,
+                        ret => return ret,
+                    }
+                }
+
+                Err(LaunchError::CidRangeExhausted)
+            }
+        }
+    }
+}
+
+/// Start an enclave with a CID reserved for as long as it runs.
+fn start_reserved(
+    cid: u64,
+    start: &mut impl FnMut(u64) -> Result<u64, LaunchError>,
+) -> Result<u64, LaunchError> {
+    if !reserve(cid) {
+        return Err(LaunchError::CidInUse(cid));
+    }
+
+    match start(cid) {
+        Ok(started) => {
+            // The driver may report a different CID than requested.
+            if started != cid {
+                release(cid);
+                reserve(started);
+            }
+
+            Ok(started)
+        }
+        Err(e) => {
+            release(cid);
+            Err(e)
+        }
+    }
+}
+
+/// Check whether a CID can be assigned to an enclave.
+pub(crate) fn is_valid(cid: u64) -> bool {
+    cid > VMADDR_CID_PARENT as u64 && cid <= i32::MAX as u64
+}
+
+/// Reserve a CID within this process, such as one used by an enclave started by another process.
+/// Returns false if the CID is already reserved.
+pub fn reserve(cid: u64) -> bool {
+    reserved().insert(cid)
+}
+
+/// Release a CID reserved within this process.
+pub fn release(cid: u64) {
+    reserved().remove(&cid);
+}
+
+/// Randomly-generate a valid CID that is not reserved by this process.
+pub(crate) fn random() -> Result<u64, LaunchError> {
+    loop {
+        let cid = OsRng
+            .try_next_u32()
+            .map_err(|_| LaunchError::CidRandomGenerate)? as u64;
+
+        if is_valid(cid) && !reserved().contains(&cid) {
+            return Ok(cid);
+        }
+    }
+}
+
+fn reserved() -> MutexGuard<'static, BTreeSet<u64>> {
+    // The set is always left consistent, so it remains usable if a holder of the lock panicked.
+    RESERVED_CIDS.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -1,6 +1,9 @@
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{fmt, io};
+use super::{transaction::LaunchAborted, types::HugePagePlan};
+use crate::heartbeat::HeartbeatError;
+
+use std::{fmt, io, path::PathBuf};
 
 const NE_ERR_VCPU_ALREADY_USED: i32 = 256;
 const NE_ERR_VCPU_NOT_IN_CPU_POOL: i32 = 257;
@@ -34,6 +37,27 @@ pub enum LaunchError {
 
     /// Error occuring when randomly-generating an enclave CID.
     CidRandomGenerate,
+
+    /// Unable to close the enclave VM file descriptor.
+    VmClose(io::Error),
+
+    /// The supplied enclave CID is invalid.
+    InvalidCid(u64),
+
+    /// The enclave CID is reserved within this process, such as by another of its enclaves.
+    CidInUse(u64),
+
+    /// No enclave CID in the configured range is available.
+    CidRangeExhausted,
+
+    /// Unable to open the enclave image.
+    ImageOpen(PathBuf, io::Error),
+
+    /// The enclave did not signal readiness.
+    Ready(HeartbeatError),
+
+    /// A launch step failed, and the launch was rolled back.
+    Aborted(Box<LaunchAborted>),
 }
 
 impl LaunchError {
@@ -41,6 +65,14 @@ impl LaunchError {
     pub fn ioctl_err_from_errno() -> Self {
         Self::Ioctl(IoctlError::from_errno())
     }
+
+    /// Get the error of the failed step, if a launch was rolled back, or this error otherwise.
+    pub fn cause(&self) -> &Self {
+        match self {
+            Self::Aborted(aborted) => aborted.error.cause(),
+            _ => self,
+        }
+    }
 }
 
 impl fmt::Display for LaunchError {
@@ -49,6 +81,15 @@ impl fmt::Display for LaunchError {
             Self::Ioctl(e) => format!("ioctl error: {e}"),
             Self::MemInit(e) => format!("memory initialization error: {e}"),
             Self::CidRandomGenerate => "unable to randomly-generate enclave CID".to_string(),
+            Self::VmClose(e) => format!("unable to close enclave VM file descriptor: {e}"),
+            Self::InvalidCid(cid) => format!("invalid enclave CID {cid}"),
+            Self::CidInUse(cid) => format!("enclave CID {cid} is already in use"),
+            Self::CidRangeExhausted => {
+                "no enclave CID in the configured range is available".to_string()
+            }
+            Self::ImageOpen(p, e) => format!("unable to open enclave image {}: {e}", p.display()),
+            Self::Ready(e) => format!("enclave did not signal readiness: {e}"),
+            Self::Aborted(aborted) => aborted.to_string(),
         };
 
         write!(f, "{}", msg)
@@ -261,6 +302,26 @@ pub enum MemInitError {
 
     /// Overflow when calculating end of image region in guest memory.
     ImagePlacementOverflow,
+
+    /// Scrubbed memory region contained non-zero bytes on verification.
+    ScrubVerify,
+
+    /// Unable to bind memory allocations to a NUMA node.
+    NumaBind(u32, io::Error),
+
+    /// Unable to unmap a memory region.
+    Unmap(io::Error),
+
+    /// The requested huge pages (plan size, in bytes) do not add up to the enclave's memory size.
+    HugePagePlanMismatch { plan: usize, size: usize },
+
+    /// The allocated memory requires more regions than the enclave can hold. Includes the huge
+    /// pages that would need to be reserved on the host to allocate the memory in fewer regions.
+    MaxRegionsExceeded {
+        regions: usize,
+        max: usize,
+        suggestion: Option<HugePagePlan>,
+    },
 }
 
 impl fmt::Display for MemInitError {
@@ -285,6 +346,29 @@ impl fmt::Display for MemInitError {
             Self::ImagePlacementOverflow => {
                 "overflow when calculating end of image region in guest memory".to_string()
             }
+            Self::ScrubVerify => {
+                "scrubbed memory region contained non-zero bytes on verification".to_string()
+            }
+            Self::NumaBind(node, e) => {
+                format!("unable to bind memory allocations to NUMA node {node}: {e}")
+            }
+            Self::Unmap(e) => format!("unable to unmap memory region: {e}"),
+            Self::HugePagePlanMismatch { plan, size } => {
+                format!("requested huge pages hold {plan} bytes, not the enclave's {size} bytes")
+            }
+            Self::MaxRegionsExceeded {
+                regions,
+                max,
+                suggestion,
+            } => {
+                let mut msg = format!(
+                    "allocated memory requires {regions} regions, more than the maximum of {max}"
+                );
+                if let Some(plan) = suggestion {
+                    msg.push_str(&format!(" (consider reserving huge pages: {plan})"));
+                }
+                msg
+            }
         };
 
         write!(f, "{}", msg)
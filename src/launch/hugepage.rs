@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::types::*;
+
+use std::{
+    fs::{self, File},
+    io,
+    os::unix::fs::FileExt,
+    path::Path,
+};
+
+const HUGEPAGES_SYSFS_DIR: &str = "/sys/kernel/mm/hugepages";
+
+const PAGEMAP_PATH: &str = "/proc/self/pagemap";
+
+// Size of a base page, the granularity of the pagemap.
+const BASE_PAGE_SIZE: usize = 4096;
+
+// Bit indicating that a pagemap entry's page is present in memory.
+const PAGEMAP_PRESENT: u64 = 1 << 63;
+
+// Bits of a pagemap entry holding the page frame number.
+const PAGEMAP_PFN_MASK: u64 = (1 << 55) - 1;
+
+// Memory policies of set_mempolicy(2).
+const MPOL_DEFAULT: libc::c_int = 0;
+const MPOL_BIND: libc::c_int = 2;
+
+// Number of NUMA nodes in the masks of saved memory policies, the kernel's largest MAX_NUMNODES.
+const MAX_NUMA_NODES: usize = 1024;
+
+/// Query the huge page sizes configured on the host, along with the number of free pages of each
+/// size.
+pub fn available() -> io::Result<Vec<HugePageCount>> {
+    available_in(Path::new(HUGEPAGES_SYSFS_DIR))
+}
+
+/// Query the huge page sizes, and the number of free pages of each size, from a sysfs hugepages
+/// directory (that of the host, or of a NUMA node).
+pub fn available_in(dir: &Path) -> io::Result<Vec<HugePageCount>> {
+    let mut pages = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+
+        // Directories are named after the page size, e.g. "hugepages-2048kB".
+        let name = entry.file_name();
+        let Some(size_kb) = name
+            .to_str()
+            .and_then(|n| n.strip_prefix("hugepages-"))
+            .and_then(|n| n.strip_suffix("kB"))
+            .and_then(|n| n.parse::<usize>().ok())
+        else {
+            continue;
+        };
+
+        let free = fs::read_to_string(entry.path().join("free_hugepages"))?;
+        let count = free
+            .trim()
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        pages.push(HugePageCount {
+            page_size: size_kb << 10,
+            count,
+        });
+    }
+
+    Ok(pages)
+}
+
+/// Binds the memory allocations of the calling thread to a NUMA node. The thread's previous
+/// memory policy is restored when dropped.
+pub struct NodeBinding {
+    mode: libc::c_int,
+    mask: Vec<libc::c_ulong>,
+}
+
+impl NodeBinding {
+    pub fn new(node: u32) -> io::Result<Self> {
+        let bits = libc::c_ulong::BITS as usize;
+
+        let mut mode: libc::c_int = 0;
+        let mut saved = vec![0 as libc::c_ulong; MAX_NUMA_NODES / bits];
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_get_mempolicy,
+                &mut mode,
+                saved.as_mut_ptr(),
+                MAX_NUMA_NODES as libc::c_ulong,
+                std::ptr::null::<libc::c_void>(),
+                0 as libc::c_ulong,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut mask = vec![0 as libc::c_ulong; node as usize / bits + 1];
+        mask[node as usize / bits] |= 1 << (node as usize % bits);
+        set_mempolicy(MPOL_BIND, &mask)?;
+
+        Ok(Self { mode, mask: saved })
+    }
+}
+
+impl Drop for NodeBinding {
+    fn drop(&mut self) {
+        // The default policy takes no nodes.
+        let mask = match self.mode {
+            MPOL_DEFAULT => &[][..],
+            _ => &self.mask[..],
+        };
+        let _ = set_mempolicy(self.mode, mask);
+    }
+}
+
+/// Set the memory policy of the calling thread, with the nodes set in mask.
+fn set_mempolicy(mode: libc::c_int, mask: &[libc::c_ulong]) -> io::Result<()> {
+    // The kernel reads one less than maxnode bits of the mask.
+    let (nodes, maxnode) = match mask.is_empty() {
+        true => (std::ptr::null(), 0),
+        false => (mask.as_ptr(), mask.len() * libc::c_ulong::BITS as usize + 1),
+    };
+
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_set_mempolicy,
+            mode,
+            nodes,
+            maxnode as libc::c_ulong,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Reads physical page frame numbers of the calling process's virtual memory.
+pub struct Pagemap(File);
+
+impl Pagemap {
+    /// Open the calling process's pagemap.
+    pub fn open() -> io::Result<Self> {
+        Ok(Self(File::open(PAGEMAP_PATH)?))
+    }
+
+    /// Get the physical page frame number backing a virtual address. Returns None if the page is
+    /// not present, or the caller lacks the privilege (CAP_SYS_ADMIN) to read frame numbers.
+    pub fn pfn(&self, addr: u64) -> Option<u64> {
+        let mut entry = [0u8; 8];
+        let offset = (addr / BASE_PAGE_SIZE as u64) * entry.len() as u64;
+        self.0.read_exact_at(&mut entry, offset).ok()?;
+
+        let entry = u64::from_ne_bytes(entry);
+        let pfn = entry & PAGEMAP_PFN_MASK;
+
+        (entry & PAGEMAP_PRESENT != 0 && pfn != 0).then_some(pfn)
+    }
+
+    /// Check whether the huge page of page_size bytes at uaddr physically follows the huge page
+    /// immediately preceding it in virtual memory.
+    pub fn contiguous(&self, uaddr: u64, page_size: usize) -> bool {
+        let frames = (page_size / BASE_PAGE_SIZE) as u64;
+
+        match (self.pfn(uaddr - page_size as u64), self.pfn(uaddr)) {
+            (Some(prev), Some(pfn)) => prev + frames == pfn,
+            _ => false,
+        }
+    }
+
+    /// Count the physically-contiguous runs of memory within a virtual range backed by huge pages
+    /// of page_size bytes. Returns None if any frame number cannot be read.
+    pub fn contiguous_runs(&self, uaddr: u64, size: u64, page_size: usize) -> Option<usize> {
+        let frames = (page_size / BASE_PAGE_SIZE) as u64;
+        let mut runs = 0;
+        let mut next = None;
+
+        for addr in (uaddr..uaddr + size).step_by(page_size) {
+            let pfn = self.pfn(addr)?;
+            if next != Some(pfn) {
+                runs += 1;
+            }
+            next = Some(pfn + frames);
+        }
+
+        Some(runs)
+    }
+}
@@ -1,11 +1,12 @@
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{error::*, types::*};
+use super::{backend::MemoryBackend, error::*, hugepage::Pagemap, types::*};
 
 use std::{
     cmp::min,
     fs::File,
-    io::{Read, Seek},
+    io::{self, Read, Seek},
+    sync::atomic::{compiler_fence, Ordering},
 };
 
 pub const NE_MAGIC: u64 = 0xAE;
@@ -43,6 +44,14 @@ const HUGE_FLAG_SIZE: [(libc::c_int, usize); 9] = [
     (libc::MAP_HUGE_2MB, 2 << 20),
 ];
 
+/// Get the mmap(2) flag selecting huge pages of page_size bytes.
+pub fn huge_page_flag(page_size: usize) -> Option<libc::c_int> {
+    HUGE_FLAG_SIZE
+        .iter()
+        .find(|(_, size)| *size == page_size)
+        .map(|(flag, _)| *flag)
+}
+
 /// Info necessary for in-memory enclave image.
 #[derive(Debug, Default)]
 #[repr(C)]
@@ -122,46 +131,126 @@ impl UserMemoryRegion {
 }
 
 /// Allocated enclave memory regions.
-pub struct UserMemoryRegions(Vec<UserMemoryRegion>);
+pub struct UserMemoryRegions {
+    regions: Vec<UserMemoryRegion>,
+    page_sizes: Vec<usize>,
+    scrub: MemoryScrub,
+}
 
 impl UserMemoryRegions {
-    /// Allocate huge pages for enclave memory from the requested size (in MiB).
-    pub fn new(size_mib: usize) -> Result<Self, MemInitError> {
-        let mut regions = Vec::new();
-        let mut size = size_mib << 20;
+    /// Allocate huge pages for enclave memory from the requested size, using as few memory regions
+    /// as possible, or exactly the huge pages requested (which must add up to the requested size).
+    /// If a maximum number of regions is given, fail if the allocated memory would exceed it.
+    pub fn new(info: &MemoryInfo) -> Result<Self, MemInitError> {
+        // Regions are collected directly into the returned value so that any pages mapped before
+        // an allocation failure are unmapped when it is dropped.
+        let mut mem = Self {
+            regions: Vec::new(),
+            page_sizes: Vec::new(),
+            scrub: info.scrub,
+        };
+        let backend = info.backend.as_ref();
+        let size = info.size_mib << 20;
+        let mut remaining = size;
+
+        // Prefer the largest huge pages the backend has free, mapping all pages of each size at
+        // once.
+        let available = backend.available().unwrap_or_default();
+        let plan = match &info.hugepages {
+            Some(plan) if plan.size() != size => {
+                return Err(MemInitError::HugePagePlanMismatch {
+                    plan: plan.size(),
+                    size,
+                });
+            }
+            Some(plan) => Some(plan.clone()),
+            None => HugePagePlan::new(size, &available),
+        };
+        for p in plan.map(|plan| plan.0).unwrap_or_default() {
+            if mem.map(backend, p.page_size, p.count) {
+                remaining = remaining.saturating_sub(p.page_size * p.count);
+            } else if info.hugepages.is_some() {
+                // Other pages than those requested would not be accounted for.
+                return Err(MemInitError::NoHugePageFound);
+            }
+        }
+
+        // Fall back to mapping individual huge pages for any memory that could not be mapped in
+        // bulk (e.g. if free pages are reserved by other processes in the meantime).
+        mem.map_pages(backend, remaining)?;
+
+        let pagemap = Pagemap::open().ok();
+        if let Some(pagemap) = &pagemap {
+            mem.merge(pagemap);
+        }
+
+        if let Some(max) = info.max_regions {
+            let regions = mem.phys_regions(pagemap.as_ref());
+            if regions > max {
+                // Suggest reserving the largest huge pages supported by the host.
+                let supported: Vec<HugePageCount> = available
+                    .iter()
+                    .map(|a| HugePageCount {
+                        page_size: a.page_size,
+                        count: usize::MAX,
+                    })
+                    .collect();
+                let suggestion =
+                    HugePagePlan::new(size, &supported).filter(|plan| plan.pages() <= max);
+
+                return Err(MemInitError::MaxRegionsExceeded {
+                    regions,
+                    max,
+                    suggestion,
+                });
+            }
+        }
+
+        Ok(mem)
+    }
+
+    /// Map count huge pages of page_size bytes as a single memory region. Returns false if the
+    /// pages could not be mapped.
+    fn map(&mut self, backend: &dyn MemoryBackend, page_size: usize, count: usize) -> bool {
+        let Some(reg_size) = page_size.checked_mul(count) else {
+            return false;
+        };
+
+        let Ok(addr) = backend.map(page_size, reg_size) else {
+            return false;
+        };
+
+        self.regions.push(UserMemoryRegion {
+            flags: NE_DEFAULT_MEMORY_REGION,
+            size: reg_size as _,
+            uaddr: addr as _,
+        });
+        self.page_sizes.push(page_size);
+
+        true
+    }
+
+    /// Map size bytes of memory one huge page at a time.
+    fn map_pages(
+        &mut self,
+        backend: &dyn MemoryBackend,
+        mut size: usize,
+    ) -> Result<(), MemInitError> {
         let mut found: bool;
 
         while size > 0 {
             found = false;
-            for (hp_flag, reg_size) in HUGE_FLAG_SIZE {
+            for (_, reg_size) in HUGE_FLAG_SIZE {
                 // Prevent wasting memory by only allocating huge pages that are smaller in size
                 // than the remaining memory needing to be allocated.
                 if size < reg_size {
                     continue;
                 }
 
-                let addr = unsafe {
-                    libc::mmap(
-                        std::ptr::null_mut(),
-                        reg_size,
-                        libc::PROT_READ | libc::PROT_WRITE,
-                        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB | hp_flag,
-                        -1,
-                        0,
-                    )
-                };
-
-                if addr == libc::MAP_FAILED {
+                if !self.map(backend, reg_size, 1) {
                     continue;
                 }
 
-                let region = UserMemoryRegion {
-                    flags: NE_DEFAULT_MEMORY_REGION,
-                    size: reg_size as _,
-                    uaddr: addr as _,
-                };
-
-                regions.push(region);
                 size -= reg_size;
                 found = true;
             }
@@ -172,11 +261,54 @@ impl UserMemoryRegions {
             }
         }
 
-        Ok(Self(regions))
+        Ok(())
+    }
+
+    /// Merge regions of the same page size that are both virtually and physically contiguous, as
+    /// each region added to an enclave counts towards its maximum number of regions.
+    fn merge(&mut self, pagemap: &Pagemap) {
+        let mut regions: Vec<(UserMemoryRegion, usize)> = std::mem::take(&mut self.regions)
+            .into_iter()
+            .zip(std::mem::take(&mut self.page_sizes))
+            .collect();
+        regions.sort_by_key(|(r, _)| r.uaddr);
+
+        for (region, page_size) in regions {
+            if let (Some(prev), Some(prev_page_size)) =
+                (self.regions.last_mut(), self.page_sizes.last())
+            {
+                let contiguous = *prev_page_size == page_size
+                    && prev.uaddr + prev.size == region.uaddr
+                    && pagemap.contiguous(region.uaddr, page_size);
+
+                if contiguous {
+                    prev.size += region.size;
+                    continue;
+                }
+            }
+
+            self.regions.push(region);
+            self.page_sizes.push(page_size);
+        }
+    }
+
+    /// Estimate the number of physically-contiguous regions the driver will create for the
+    /// memory. If physical addresses are unavailable, each region is assumed to be contiguous.
+    fn phys_regions(&self, pagemap: Option<&Pagemap>) -> usize {
+        self.regions
+            .iter()
+            .zip(&self.page_sizes)
+            .map(|(r, page_size)| {
+                pagemap
+                    .and_then(|p| p.contiguous_runs(r.uaddr, r.size, *page_size))
+                    .unwrap_or(1)
+            })
+            .sum()
     }
 
-    /// Populate the memory regions with the enclave image.
-    pub fn image_fill(&mut self, offset: usize, image: ImageType) -> Result<(), MemInitError> {
+    /// Populate the memory regions with the enclave image. On success, return the size of the
+    /// image (in bytes).
+    pub fn image_fill(&mut self, offset: usize, image: ImageType) -> Result<usize, MemInitError> {
         // Only EIF images are supported at the moment.
         let ImageType::Eif(image) = image;
 
@@ -192,7 +324,7 @@ impl UserMemoryRegions {
 
         // Write the enclave image to the memory regions.
         let mut written: usize = 0;
-        for region in &mut self.0 {
+        for region in &mut self.regions {
             region.image_fill(image, offset, image_size, &mut written)?;
             if written >= limit {
                 break;
@@ -204,12 +336,94 @@ impl UserMemoryRegions {
             return Err(MemInitError::ImageWriteIncomplete);
         }
 
+        Ok(image_size)
+    }
+
+    /// Describe the memory regions, with the enclave image located at offset (in bytes) from the
+    /// start of enclave memory and spanning image_size bytes.
+    pub fn layout(&self, offset: usize, image_size: usize) -> MemoryLayout {
+        let mut layout = MemoryLayout::new(offset as u64, image_size as u64);
+        for (region, page_size) in self.regions.iter().zip(&self.page_sizes) {
+            layout.push(region.size, *page_size as u64, region.uaddr);
+        }
+
+        layout
+    }
+
+    /// Zero the contents of the memory regions that lie outside of the enclave image.
+    pub fn scrub(&mut self, layout: &MemoryLayout) -> Result<(), MemInitError> {
+        if self.scrub == MemoryScrub::None {
+            return Ok(());
+        }
+
+        for region in &layout.regions {
+            let bytes = unsafe {
+                std::slice::from_raw_parts_mut(region.uaddr as *mut u8, region.size as usize)
+            };
+
+            match &region.image {
+                Some(image) => {
+                    self.scrub_bytes(&mut bytes[..image.start as usize])?;
+                    self.scrub_bytes(&mut bytes[image.end as usize..])?;
+                }
+                None => self.scrub_bytes(bytes)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Zero (and, if requested, verify) the contents of all memory regions and unmap them. This
+    /// must only be called once the enclave using the regions has terminated.
+    pub fn release(&mut self) -> Result<(), MemInitError> {
+        let mut result = Ok(());
+
+        self.page_sizes.clear();
+        for region in std::mem::take(&mut self.regions) {
+            let bytes = unsafe {
+                std::slice::from_raw_parts_mut(region.uaddr as *mut u8, region.size as usize)
+            };
+
+            // Continue unmapping the remaining regions on failure, reporting the first error.
+            if self.scrub != MemoryScrub::None {
+                if let Err(e) = self.scrub_bytes(bytes) {
+                    result = result.and(Err(e));
+                }
+            }
+
+            let ret = unsafe { libc::munmap(region.uaddr as *mut _, region.size as usize) };
+            if ret < 0 {
+                result = result.and(Err(MemInitError::Unmap(io::Error::last_os_error())));
+            }
+        }
+
+        result
+    }
+
+    /// Zero a range of enclave memory, verifying the result if requested.
+    fn scrub_bytes(&self, bytes: &mut [u8]) -> Result<(), MemInitError> {
+        unsafe { std::ptr::write_bytes(bytes.as_mut_ptr(), 0, bytes.len()) };
+
+        // Prevent the compiler from treating the zeroing as a dead store to memory that is about
+        // to be unmapped.
+        compiler_fence(Ordering::SeqCst);
+
+        if self.scrub == MemoryScrub::Verify && bytes.iter().any(|b| *b != 0) {
+            return Err(MemInitError::ScrubVerify);
+        }
+
         Ok(())
     }
 
     /// Get a reference to the inner vector of memory regions.
     pub fn inner_ref(&self) -> &Vec<UserMemoryRegion> {
-        &self.0
+        &self.regions
+    }
+}
+
+impl Drop for UserMemoryRegions {
+    fn drop(&mut self) {
+        let _ = self.release();
     }
 }
 
@@ -1,27 +1,50 @@
 // SPDX-License-Identifier: Apache-2.0
 
+mod backend;
+mod cid;
 mod error;
+pub(crate) mod hugepage;
 mod linux;
+mod timeout;
+mod transaction;
+mod typed;
 mod types;
 
+pub use backend::*;
+pub use cid::{release as release_cid, reserve as reserve_cid, CidPolicy};
 pub use error::*;
+pub use linux::{UserMemoryRegion, UserMemoryRegions};
+pub use timeout::*;
+pub use transaction::*;
+pub use typed::{state, TypedLauncher};
 pub use types::*;
 
-use crate::device::Device;
+use crate::{
+    device::Device,
+    eif::{Eif, EifMetadata},
+    scheduler::Reservation,
+    systemd::{Notifier, State},
+};
 use linux::*;
-use rand::{rngs::OsRng, TryRngCore};
 use std::os::fd::{AsRawFd, RawFd};
 
 type Result<T> = std::result::Result<T, LaunchError>;
 
-const VMADDR_CID_PARENT: u32 = 3;
-
 /// Facilitates the execution of the nitro enclaves launch process.
-#[derive(Default)]
+///
+/// The launch steps must be performed in order: set memory, add vCPUs, then start. Violations are
+/// only reported by the driver at runtime. See [`TypedLauncher`] for a launcher that enforces the
+/// ordering at compile time.
 pub struct Launcher {
     vm_fd: RawFd,
     slot_uid: u64,
     cpu_ids: Vec<u32>,
+    regions: Option<UserMemoryRegions>,
+    layout: Option<MemoryLayout>,
+    metadata: Option<EifMetadata>,
+    cid: Option<u64>,
+    reservation: Option<Reservation>,
+    notifier: Option<Notifier>,
 }
 
 impl Launcher {
@@ -38,9 +61,23 @@ impl Launcher {
             vm_fd,
             slot_uid,
             cpu_ids: Vec::new(),
+            regions: None,
+            layout: None,
+            metadata: None,
+            cid: None,
+            reservation: None,
+            notifier: None,
         })
     }
 
+    /// Launch an enclave from a configuration: populate its memory with the image, add its
+    /// vCPUs and start it, choosing its CID according to the supplied policy. If the
+    /// configuration has a boot timeout, wait for the enclave's readiness heartbeat. The launch is
+    /// rolled back if any step fails (see [`LaunchTransaction`]).
+    pub fn launch(dev: &Device, config: &EnclaveConfig, policy: CidPolicy) -> Result<Self> {
+        LaunchTransaction::begin(dev)?.launch(config, policy)
+    }
+
     /// Get the enclave's file descriptor.
     pub fn vm_fd(&self) -> RawFd {
         self.vm_fd
@@ -51,8 +88,24 @@ impl Launcher {
         self.slot_uid
     }
 
+    /// Get the enclave's CID, if it has been started.
+    pub fn cid(&self) -> Option<u64> {
+        self.cid
+    }
+
+    /// Get the layout of the enclave's memory, if it has been set.
+    pub fn memory_layout(&self) -> Option<&MemoryLayout> {
+        self.layout.as_ref()
+    }
+
+    /// Get the metadata of the enclave's image, if memory has been set and the image has a
+    /// metadata section.
+    pub fn image_metadata(&self) -> Option<&EifMetadata> {
+        self.metadata.as_ref()
+    }
+
     /// Allocate enclave memory and populate it with the enclave image.
-    pub fn set_memory(&mut self, mem: MemoryInfo) -> Result<()> {
+    pub fn set_memory(&mut self, mut mem: MemoryInfo) -> Result<()> {
         // Load the VM's enclave image type and fetch the offset in enclave memory of where to
         // start placing the enclave image.
         let mut load_info = ImageLoadInfo::from(&mem.image_type);
@@ -70,19 +123,37 @@ impl Launcher {
             return Err(LaunchError::ioctl_err_from_errno());
         }
 
+        // Record the image's metadata, identifying the application running in the slot. This is
+        // best effort: images the driver can boot are not rejected for unreadable metadata.
+        let ImageType::Eif(image) = &mut mem.image_type;
+        self.metadata = Eif::read_metadata(*image).ok().flatten();
+
         // Allocate the memory regions from the requested size.
-        let mut regions = UserMemoryRegions::new(mem.size_mib).map_err(LaunchError::MemInit)?;
+        let mut regions = UserMemoryRegions::new(&mem).map_err(LaunchError::MemInit)?;
 
         // Populate the memory regions with the contents of the enclave image.
-        regions
-            .image_fill(load_info.memory_offset as usize, mem.image_type)
+        let offset = load_info.memory_offset as usize;
+        let image_size = regions
+            .image_fill(offset, mem.image_type)
             .map_err(LaunchError::MemInit)?;
 
+        // Clear any memory outside of the enclave image (if requested).
+        let layout = regions.layout(offset, image_size);
+        regions.scrub(&layout).map_err(LaunchError::MemInit)?;
+
+        // Record the layout before adding the regions, so that it remains available for
+        // inspection if the driver rejects them.
+        self.layout = Some(layout);
+
+        // Keep the regions until the enclave terminates, as some may already be in use by the
+        // enclave if adding a later region fails.
+        let regions = self.regions.insert(regions);
+
         // Add each memory region.
         for r in regions.inner_ref() {
             let ret = unsafe { libc::ioctl(self.vm_fd, NE_SET_USER_MEMORY_REGION as _, r) };
             if ret < 0 {
-                panic!();
+                return Err(LaunchError::ioctl_err_from_errno());
             }
         }
 
@@ -109,22 +180,21 @@ impl Launcher {
 
     /// Start running an enclave. Supply start flags and optional enclave CID. If successful, will
     /// return the actual enclave's CID (which may be different than the supplied CID).
-    pub fn start(&self, flags: StartFlags, cid: Option<u64>) -> Result<u64> {
-        let mut cid = cid.unwrap_or(0);
-
-        // Ensure that a valid CID is used. If the current CID is invalid, randomly-generate a
-        // valid one.
-        loop {
-            if cid > VMADDR_CID_PARENT as u64 && cid <= i32::MAX as u64 {
-                break;
-            }
+    pub fn start(&mut self, flags: StartFlags, cid: Option<u64>) -> Result<u64> {
+        self.start_with_policy(flags, CidPolicy::Preferred(cid))
+    }
 
-            cid = OsRng
-                .try_next_u32()
-                .map_err(|_| LaunchError::CidRandomGenerate)? as u64;
-        }
+    /// Start running an enclave, choosing its CID according to the supplied policy. If
+    /// successful, will return the enclave's CID.
+    pub fn start_with_policy(&mut self, flags: StartFlags, policy: CidPolicy) -> Result<u64> {
+        let cid = policy.start_with(|cid| self.start_cid(flags, cid))?;
+        self.cid = Some(cid);
+
+        Ok(cid)
+    }
 
-        // Start the enclave VM.
+    /// Start the enclave VM with the given CID.
+    fn start_cid(&self, flags: StartFlags, cid: u64) -> Result<u64> {
         let mut start_info = StartInfo::new(flags, cid);
 
         let ret = unsafe { libc::ioctl(self.vm_fd, NE_START_ENCLAVE as _, &mut start_info) };
@@ -135,4 +205,85 @@ impl Launcher {
 
         Ok(start_info.cid)
     }
+
+    /// Terminate the enclave by closing its file descriptor, then scrub (if requested) and unmap
+    /// its memory regions. Dropping the launcher does the same, but ignores any errors. If the
+    /// enclave was launched with a [`Notifier`], systemd is told that the service is stopping.
+    pub fn terminate(mut self) -> Result<()> {
+        if let Some(notifier) = self.notifier.take() {
+            let status = match self.cid {
+                Some(cid) => format!("Stopping enclave (CID {cid})"),
+                None => "Stopping enclave".to_string(),
+            };
+            let _ = notifier.notify(&[State::Stopping, State::Status(status)]);
+        }
+
+        self.teardown()
+    }
+
+    /// Stop reporting the enclave's termination to systemd, returning the notifier.
+    pub(crate) fn take_notifier(&mut self) -> Option<Notifier> {
+        self.notifier.take()
+    }
+
+    fn teardown(&mut self) -> Result<()> {
+        let mut result = Ok(());
+
+        // A zero slot UID indicates that the enclave VM was already closed.
+        if self.slot_uid != 0 {
+            self.slot_uid = 0;
+
+            let ret = unsafe { libc::close(self.vm_fd) };
+            if let Some(cid) = self.cid.take() {
+                cid::release(cid);
+            }
+
+            if ret < 0 {
+                result = Err(LaunchError::VmClose(std::io::Error::last_os_error()));
+            }
+        }
+
+        // The enclave's memory can only be accessed again after it has terminated. It is
+        // released even if closing the enclave VM reported an error, as the descriptor is
+        // closed regardless.
+        if let Some(mut regions) = self.regions.take() {
+            result = result.and(regions.release().map_err(LaunchError::MemInit));
+        }
+
+        // Free the enclave's CPUs and memory for other enclaves.
+        self.reservation = None;
+
+        result
+    }
+
+    /// Tear down a partially-launched enclave, recording the resources released.
+    fn rollback(&mut self) -> Rollback {
+        let regions = self.regions.as_ref().map(|r| r.inner_ref());
+
+        let mut rollback = Rollback {
+            vm_closed: self.slot_uid != 0,
+            vcpus: std::mem::take(&mut self.cpu_ids),
+            regions: regions.map_or(0, Vec::len),
+            memory: regions.map_or(0, |r| r.iter().map(|r| r.size).sum()),
+            cid: self.cid,
+            reserved_cpus: self
+                .reservation
+                .as_ref()
+                .map(|r| r.cpu_ids().to_vec())
+                .unwrap_or_default(),
+            errors: Vec::new(),
+        };
+
+        if let Err(e) = self.teardown() {
+            rollback.errors.push(e);
+        }
+
+        rollback
+    }
+}
+
+impl Drop for Launcher {
+    fn drop(&mut self) {
+        let _ = self.teardown();
+    }
 }
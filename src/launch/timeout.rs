@@ -0,0 +1,201 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use ring::digest::{self, SHA384};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read, Seek},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// Number of observed boot times kept for each image.
+pub const CALIBRATION_SAMPLES: usize = 8;
+
+const GIB: f64 = (1u64 << 30) as f64;
+
+/// Policy bounding how long to wait for an enclave to boot and send its readiness heartbeat.
+///
+/// The timeout is estimated from a per-GiB model of the image and memory sizes. If a calibration
+/// is attached and holds boot times observed for the image, the longest of them (scaled by the
+/// margin) is used instead. Either estimate is clamped to the minimum and maximum.
+#[derive(Clone, Debug)]
+pub struct BootTimeoutPolicy {
+    /// Time allowed regardless of the image and memory sizes.
+    pub base: Duration,
+
+    /// Time allowed per GiB of enclave image.
+    pub per_image_gib: Duration,
+
+    /// Time allowed per GiB of enclave memory.
+    pub per_memory_gib: Duration,
+
+    /// Lower bound of the timeout.
+    pub min: Duration,
+
+    /// Upper bound of the timeout.
+    pub max: Duration,
+
+    /// Factor applied to the longest observed boot time of an image. A negative or NaN margin
+    /// yields the maximum timeout.
+    pub margin: f64,
+
+    /// Boot times observed for each image, if calibration is enabled.
+    pub calibration: Option<BootCalibration>,
+}
+
+impl Default for BootTimeoutPolicy {
+    fn default() -> Self {
+        Self {
+            base: Duration::from_secs(15),
+            per_image_gib: Duration::from_secs(30),
+            per_memory_gib: Duration::from_secs(1),
+            min: Duration::from_secs(10),
+            max: Duration::from_secs(300),
+            margin: 2.0,
+            calibration: None,
+        }
+    }
+}
+
+impl BootTimeoutPolicy {
+    /// Predict boot times from (and record them to) a calibration.
+    pub fn with_calibration(mut self, calibration: BootCalibration) -> Self {
+        self.calibration = Some(calibration);
+        self
+    }
+
+    /// Get the timeout for booting an image in mem_size bytes of enclave memory.
+    pub fn timeout(&self, image: &BootImage, mem_size: usize) -> Duration {
+        let predicted = self
+            .calibration
+            .as_ref()
+            .and_then(|c| c.longest(&image.hash))
+            .map(|observed| {
+                Duration::try_from_secs_f64(observed.as_secs_f64() * self.margin)
+                    .unwrap_or(self.max)
+            });
+
+        // A model whose estimate overflows allows the maximum timeout.
+        let timeout = predicted.unwrap_or_else(|| {
+            let per_gib = |time: Duration, size: f64| {
+                Duration::try_from_secs_f64(time.as_secs_f64() * size / GIB).ok()
+            };
+            per_gib(self.per_image_gib, image.size as f64)
+                .zip(per_gib(self.per_memory_gib, mem_size as f64))
+                .and_then(|(image, memory)| self.base.checked_add(image)?.checked_add(memory))
+                .unwrap_or(self.max)
+        });
+
+        timeout.clamp(self.min, self.min.max(self.max))
+    }
+
+    /// Record the time an image took to boot, if calibration is enabled.
+    pub fn record(&self, image: &BootImage, elapsed: Duration) {
+        if let Some(calibration) = &self.calibration {
+            calibration.record(&image.hash, elapsed);
+        }
+    }
+}
+
+/// An enclave image, as identified for boot time predictions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BootImage {
+    /// Image size (in bytes).
+    pub size: u64,
+
+    /// Hex-encoded SHA-384 digest of the image.
+    pub hash: String,
+}
+
+impl BootImage {
+    /// Hash an image file, rewinding it afterwards.
+    pub fn from_file(file: &mut File) -> io::Result<Self> {
+        file.rewind()?;
+
+        let mut ctx = digest::Context::new(&SHA384);
+        let mut size = 0;
+        let mut buf = vec![0u8; 1 << 20];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            ctx.update(&buf[..n]);
+            size += n as u64;
+        }
+        file.rewind()?;
+
+        let hash = ctx
+            .finish()
+            .as_ref()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        Ok(Self { size, hash })
+    }
+}
+
+/// Boot times observed for each image, keyed by image hash. Clones share the same observations,
+/// so that a calibration can be used by several enclaves at once.
+#[derive(Clone, Debug, Default)]
+pub struct BootCalibration(Arc<Mutex<BTreeMap<String, Vec<u64>>>>);
+
+impl BootCalibration {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load observations saved with [`BootCalibration::save`]. A missing file yields an empty
+    /// calibration.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let json = match std::fs::read(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e),
+        };
+
+        let samples = serde_json::from_slice(&json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        Ok(Self(Arc::new(Mutex::new(samples))))
+    }
+
+    /// Save the observations as JSON, mapping each image hash to its boot times in milliseconds.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_vec(&*self.samples())?;
+
+        std::fs::write(path, json)
+    }
+
+    /// Record the time an image took to boot, keeping the latest [`CALIBRATION_SAMPLES`].
+    pub fn record(&self, hash: &str, elapsed: Duration) {
+        let mut samples = self.samples();
+        let observed = samples.entry(hash.to_string()).or_default();
+
+        observed.push(elapsed.as_millis() as u64);
+        if observed.len() > CALIBRATION_SAMPLES {
+            observed.remove(0);
+        }
+    }
+
+    /// Get the boot times observed for an image, oldest first.
+    pub fn observed(&self, hash: &str) -> Vec<Duration> {
+        self.samples()
+            .get(hash)
+            .map(|o| o.iter().map(|ms| Duration::from_millis(*ms)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Get the longest boot time observed for an image.
+    pub fn longest(&self, hash: &str) -> Option<Duration> {
+        self.observed(hash).into_iter().max()
+    }
+
+    fn samples(&self) -> MutexGuard<'_, BTreeMap<String, Vec<u64>>> {
+        // The map is always left consistent, so it remains usable if a holder of the lock panicked.
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
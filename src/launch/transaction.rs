@@ -0,0 +1,392 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{error::*, hugepage::NodeBinding, timeout::*, types::*, CidPolicy, Launcher, Result};
+use crate::{
+    device::Device,
+    heartbeat::ReadyListener,
+    scheduler::{format_cpu_list, Reservation},
+    systemd::{Notifier, State},
+    transport::Transport,
+};
+
+use std::{fmt, fs::File, time::Instant};
+
+/// Step of the launch process.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LaunchStep {
+    /// Create the enclave VM.
+    CreateVm,
+
+    /// Open the enclave image.
+    OpenImage,
+
+    /// Allocate enclave memory and populate it with the image.
+    SetMemory,
+
+    /// Add a vCPU, chosen by the caller or auto-chosen from the NE CPU pool.
+    AddVcpu(Option<u32>),
+
+    /// Start the enclave.
+    Start,
+
+    /// Wait for the enclave's readiness heartbeat.
+    Ready,
+}
+
+impl fmt::Display for LaunchStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::CreateVm => "create enclave VM".to_string(),
+            Self::OpenImage => "open enclave image".to_string(),
+            Self::SetMemory => "set enclave memory".to_string(),
+            Self::AddVcpu(Some(id)) => format!("add vCPU {id}"),
+            Self::AddVcpu(None) => "add vCPU".to_string(),
+            Self::Start => "start enclave".to_string(),
+            Self::Ready => "wait for enclave readiness".to_string(),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+/// Resources released when rolling back a failed launch.
+#[derive(Debug, Default)]
+pub struct Rollback {
+    /// The enclave VM's file descriptor was closed, freeing its slot and vCPUs (and terminating
+    /// the enclave, if started).
+    pub vm_closed: bool,
+
+    /// vCPUs that had been added to the enclave.
+    pub vcpus: Vec<u32>,
+
+    /// Number of memory regions unmapped.
+    pub regions: usize,
+
+    /// Amount of memory (in bytes) unmapped.
+    pub memory: u64,
+
+    /// CID released, if the enclave was started.
+    pub cid: Option<u64>,
+
+    /// CPUs whose reservation was released.
+    pub reserved_cpus: Vec<u32>,
+
+    /// Errors that occurred while releasing resources.
+    pub errors: Vec<LaunchError>,
+}
+
+impl fmt::Display for Rollback {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut released = Vec::new();
+        if self.vm_closed {
+            released.push("closed enclave VM".to_string());
+        }
+        if !self.vcpus.is_empty() {
+            released.push(format!("freed vCPUs {}", format_cpu_list(&self.vcpus)));
+        }
+        if self.regions > 0 {
+            released.push(format!(
+                "unmapped {} memory region(s) ({} MiB)",
+                self.regions,
+                self.memory >> 20
+            ));
+        }
+        if let Some(cid) = self.cid {
+            released.push(format!("released CID {cid}"));
+        }
+        if !self.reserved_cpus.is_empty() {
+            released.push(format!(
+                "released reserved CPUs {}",
+                format_cpu_list(&self.reserved_cpus)
+            ));
+        }
+        if released.is_empty() {
+            released.push("nothing to release".to_string());
+        }
+
+        write!(f, "{}", released.join(", "))?;
+        for e in &self.errors {
+            write!(f, "; cleanup error: {e}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A launch that failed at a step, and was rolled back.
+#[derive(Debug)]
+pub struct LaunchAborted {
+    /// Step that failed.
+    pub step: LaunchStep,
+
+    /// Error of the step.
+    pub error: LaunchError,
+
+    /// Resources released by the rollback.
+    pub rollback: Rollback,
+}
+
+impl fmt::Display for LaunchAborted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unable to {}: {} (rolled back: {})",
+            self.step, self.error, self.rollback
+        )
+    }
+}
+
+/// A launch in progress. If any step fails, the launch is rolled back: the enclave VM is closed,
+/// its memory unmapped and its CPU reservation (if any) released, and the step fails with
+/// [`LaunchError::Aborted`]. Dropping an uncommitted transaction rolls it back too, reporting the
+/// abandoned launch to systemd.
+///
+/// With a [`Notifier`], each step is reported to systemd as the service's status, and the
+/// service is reported ready once the enclave's readiness heartbeat is received.
+///
+/// ```no_run
+/// # use nitro_enclaves::{launch::*, Device};
+/// # use std::fs::File;
+/// let device = Device::open().unwrap();
+/// let mut eif = File::open("hello.eif").unwrap();
+///
+/// let launcher = LaunchTransaction::begin(&device)
+///     .and_then(|t| t.set_memory(MemoryInfo::new(ImageType::Eif(&mut eif), 128)))
+///     .and_then(|t| t.add_vcpu(None))
+///     .and_then(|t| t.start(StartFlags::DEBUG, CidPolicy::default()))
+///     .map(LaunchTransaction::commit)
+///     .unwrap();
+/// ```
+pub struct LaunchTransaction {
+    // Only taken when the transaction is committed or rolled back.
+    launcher: Option<Launcher>,
+    notifier: Option<Notifier>,
+    started: Option<Instant>,
+}
+
+impl LaunchTransaction {
+    /// Begin a launch by creating a new enclave VM.
+    pub fn begin(dev: &Device) -> Result<Self> {
+        let launcher = Launcher::new(dev).map_err(|error| {
+            LaunchError::Aborted(Box::new(LaunchAborted {
+                step: LaunchStep::CreateVm,
+                error,
+                rollback: Rollback::default(),
+            }))
+        })?;
+
+        Ok(Self {
+            launcher: Some(launcher),
+            notifier: None,
+            started: None,
+        })
+    }
+
+    /// Hold the CPUs and memory reserved for the enclave, releasing them if the launch fails or,
+    /// once committed, when the enclave terminates.
+    pub fn with_reservation(mut self, reservation: Reservation) -> Self {
+        self.launcher_mut().reservation = Some(reservation);
+        self
+    }
+
+    /// Report the launch's progress to systemd.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Get the launcher of the enclave being launched.
+    pub fn launcher(&self) -> &Launcher {
+        self.launcher
+            .as_ref()
+            .expect("launch already committed or rolled back")
+    }
+
+    /// Allocate enclave memory and populate it with the enclave image.
+    pub fn set_memory(self, mem: MemoryInfo) -> Result<Self> {
+        self.step(LaunchStep::SetMemory, |l| l.set_memory(mem))
+    }
+
+    /// Add a vCPU to the enclave. See [`Launcher::add_vcpu`].
+    pub fn add_vcpu(self, id: Option<u32>) -> Result<Self> {
+        self.step(LaunchStep::AddVcpu(id), |l| l.add_vcpu(id))
+    }
+
+    /// Start the enclave, choosing its CID according to the supplied policy.
+    pub fn start(self, flags: StartFlags, policy: CidPolicy) -> Result<Self> {
+        let started = Instant::now();
+        let mut tx = self.step(LaunchStep::Start, |l| {
+            l.start_with_policy(flags, policy).map(|_| ())
+        })?;
+        tx.started = Some(started);
+
+        Ok(tx)
+    }
+
+    /// Wait for the started enclave to boot from an image in mem_size bytes of memory, timing the
+    /// boot from the start step. See [`ReadyListener::wait_boot`].
+    pub fn wait_ready<T: Transport>(
+        self,
+        listener: &ReadyListener<T>,
+        policy: &BootTimeoutPolicy,
+        image: &BootImage,
+        mem_size: usize,
+    ) -> Result<Self> {
+        let started = self.started.unwrap_or_else(Instant::now);
+        let tx = self.step(LaunchStep::Ready, |l| {
+            let cid = l.cid().unwrap_or_default() as u32;
+            listener
+                .wait_boot(policy, image, mem_size, cid, started)
+                .map(|_| ())
+                .map_err(LaunchError::Ready)
+        })?;
+
+        let cid = tx.launcher().cid().unwrap_or_default();
+        tx.notify(&[
+            State::Ready,
+            State::Status(format!("Enclave running (CID {cid})")),
+        ]);
+
+        Ok(tx)
+    }
+
+    /// Launch the enclave from a configuration. See [`Launcher::launch`].
+    pub fn launch(mut self, config: &EnclaveConfig, policy: CidPolicy) -> Result<Launcher> {
+        if let Some(notifier) = &config.notifier {
+            self.notifier = Some(notifier.clone());
+        }
+
+        let mut image = match File::open(&config.image) {
+            Ok(image) => image,
+            Err(e) => {
+                let error = LaunchError::ImageOpen(config.image.clone(), e);
+                return Err(self.abort(LaunchStep::OpenImage, error));
+            }
+        };
+
+        let mut tx = self.step(LaunchStep::SetMemory, |l| {
+            // Allocate the memory from the NUMA node of the enclave's CPUs, if known.
+            let _binding = config
+                .numa_node
+                .map(|node| {
+                    NodeBinding::new(node)
+                        .map_err(|e| LaunchError::MemInit(MemInitError::NumaBind(node, e)))
+                })
+                .transpose()?;
+
+            let mut mem = MemoryInfo::new(ImageType::Eif(&mut image), config.memory_mib);
+            mem.scrub = config.scrub;
+            mem.hugepages = config.hugepages.clone();
+            l.set_memory(mem)
+        })?;
+
+        match &config.cpu_ids {
+            Some(ids) => {
+                for id in ids {
+                    tx = tx.add_vcpu(Some(*id))?;
+                }
+            }
+            None => {
+                for _ in 0..config.cpu_count {
+                    tx = tx.add_vcpu(None)?;
+                }
+            }
+        }
+
+        // The listener must be bound before the enclave starts, so that the heartbeat is not
+        // missed.
+        let ready = match &config.boot_timeout {
+            Some(policy) => {
+                // It is shared with concurrent launches, as the heartbeat port is fixed.
+                let listener = match ReadyListener::shared() {
+                    Ok(listener) => listener,
+                    Err(e) => return Err(tx.abort(LaunchStep::Ready, LaunchError::Ready(e))),
+                };
+                // The image is hashed beforehand too, so that its boot is timed from the start.
+                let boot_image = match BootImage::from_file(&mut image) {
+                    Ok(boot_image) => boot_image,
+                    Err(e) => {
+                        let error = LaunchError::ImageOpen(config.image.clone(), e);
+                        return Err(tx.abort(LaunchStep::Ready, error));
+                    }
+                };
+                Some((listener, policy, boot_image))
+            }
+            None => None,
+        };
+
+        tx = tx.start(config.flags, policy)?;
+
+        if let Some((listener, policy, boot_image)) = ready {
+            tx = tx.wait_ready(&listener, policy, &boot_image, config.memory_mib << 20)?;
+        }
+
+        Ok(tx.commit())
+    }
+
+    /// Complete the launch, handing over the enclave (and its reservation) to the launcher. The
+    /// notifier, if any, is kept to report the enclave's termination.
+    pub fn commit(mut self) -> Launcher {
+        let mut launcher = self
+            .launcher
+            .take()
+            .expect("launch already committed or rolled back");
+        launcher.notifier = self.notifier.take();
+
+        launcher
+    }
+
+    fn launcher_mut(&mut self) -> &mut Launcher {
+        self.launcher
+            .as_mut()
+            .expect("launch already committed or rolled back")
+    }
+
+    /// Perform a launch step, rolling back the launch if it fails.
+    fn step(
+        mut self,
+        step: LaunchStep,
+        f: impl FnOnce(&mut Launcher) -> Result<()>,
+    ) -> Result<Self> {
+        self.notify(&[State::Status(format!("Launching enclave: {step}"))]);
+
+        match f(self.launcher_mut()) {
+            Ok(()) => Ok(self),
+            Err(error) => Err(self.abort(step, error)),
+        }
+    }
+
+    /// Roll back the launch after a step failed.
+    fn abort(mut self, step: LaunchStep, error: LaunchError) -> LaunchError {
+        let rollback = match self.launcher.take() {
+            Some(mut launcher) => launcher.rollback(),
+            None => Rollback::default(),
+        };
+        let aborted = LaunchAborted {
+            step,
+            error,
+            rollback,
+        };
+        self.notify(&[State::Status(format!("Enclave launch failed: {aborted}"))]);
+
+        LaunchError::Aborted(Box::new(aborted))
+    }
+
+    /// Notify systemd, if enabled. Notifications are best-effort and do not fail the launch.
+    fn notify(&self, states: &[State]) {
+        if let Some(notifier) = &self.notifier {
+            let _ = notifier.notify(states);
+        }
+    }
+}
+
+impl Drop for LaunchTransaction {
+    fn drop(&mut self) {
+        if let Some(mut launcher) = self.launcher.take() {
+            let rollback = launcher.rollback();
+            self.notify(&[State::Status(format!(
+                "Enclave launch abandoned (rolled back: {rollback})"
+            ))]);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{CidPolicy, LaunchError, Launcher, MemoryInfo, MemoryLayout, StartFlags};
+use crate::{device::Device, eif::EifMetadata};
+
+use std::os::fd::RawFd;
+
+type Result<T> = std::result::Result<T, LaunchError>;
+
+/// States of the enclave launch process.
+pub mod state {
+    mod sealed {
+        pub trait Sealed {}
+    }
+
+    /// A state of the enclave launch process.
+    pub trait State: sealed::Sealed {}
+
+    /// The enclave VM was created.
+    pub struct Created;
+
+    /// The enclave's memory was set.
+    pub struct MemoryConfigured;
+
+    /// At least one vCPU was added to the enclave.
+    pub struct VcpusAdded;
+
+    /// The enclave was started.
+    pub struct Running;
+
+    impl sealed::Sealed for Created {}
+    impl sealed::Sealed for MemoryConfigured {}
+    impl sealed::Sealed for VcpusAdded {}
+    impl sealed::Sealed for Running {}
+
+    impl State for Created {}
+    impl State for MemoryConfigured {}
+    impl State for VcpusAdded {}
+    impl State for Running {}
+}
+
+use state::*;
+
+/// A [`Launcher`] that enforces the driver's launch ordering at compile time. Each launch step
+/// consumes the launcher and returns it in the next state, so that steps cannot be skipped or
+/// repeated:
+///
+/// Created -> MemoryConfigured -> VcpusAdded -> Running
+///
+/// If a step fails, the launcher is dropped, terminating the enclave VM.
+///
+/// ```no_run
+/// # use nitro_enclaves::{launch::*, Device};
+/// # use std::fs::File;
+/// let device = Device::open().unwrap();
+/// let mut eif = File::open("hello.eif").unwrap();
+///
+/// let launcher = TypedLauncher::new(&device)
+///     .and_then(|l| l.set_memory(MemoryInfo::new(ImageType::Eif(&mut eif), 128)))
+///     .and_then(|l| l.add_vcpu(None))
+///     .and_then(|l| l.start(StartFlags::DEBUG, CidPolicy::default()))
+///     .unwrap();
+///
+/// println!("enclave started with CID {}", launcher.cid());
+/// ```
+///
+/// Starting an enclave before its memory and vCPUs are set does not compile:
+///
+/// ```compile_fail
+/// # use nitro_enclaves::{launch::*, Device};
+/// let device = Device::open().unwrap();
+///
+/// let launcher = TypedLauncher::new(&device)
+///     .and_then(|l| l.start(StartFlags::DEBUG, CidPolicy::default()))
+///     .unwrap();
+/// ```
+pub struct TypedLauncher<S: State> {
+    launcher: Launcher,
+    state: std::marker::PhantomData<S>,
+}
+
+impl<S: State> TypedLauncher<S> {
+    fn transition<T: State>(self) -> TypedLauncher<T> {
+        TypedLauncher {
+            launcher: self.launcher,
+            state: std::marker::PhantomData,
+        }
+    }
+
+    /// Get the enclave's file descriptor.
+    pub fn vm_fd(&self) -> RawFd {
+        self.launcher.vm_fd()
+    }
+
+    /// Get the enclave's slot UID.
+    pub fn slot_uid(&self) -> u64 {
+        self.launcher.slot_uid()
+    }
+
+    /// Get the metadata of the enclave's image. See [`Launcher::image_metadata`].
+    pub fn image_metadata(&self) -> Option<&EifMetadata> {
+        self.launcher.image_metadata()
+    }
+
+    /// Terminate the enclave. See [`Launcher::terminate`].
+    pub fn terminate(self) -> Result<()> {
+        self.launcher.terminate()
+    }
+
+    /// Convert into a launcher without compile-time checking of the launch ordering.
+    pub fn into_inner(self) -> Launcher {
+        self.launcher
+    }
+}
+
+impl TypedLauncher<Created> {
+    /// Begin the nitro enclaves launch process by creating a new enclave VM.
+    pub fn new(dev: &Device) -> Result<Self> {
+        Ok(Self {
+            launcher: Launcher::new(dev)?,
+            state: std::marker::PhantomData,
+        })
+    }
+
+    /// Allocate enclave memory and populate it with the enclave image.
+    pub fn set_memory(mut self, mem: MemoryInfo) -> Result<TypedLauncher<MemoryConfigured>> {
+        self.launcher.set_memory(mem)?;
+
+        Ok(self.transition())
+    }
+}
+
+impl TypedLauncher<MemoryConfigured> {
+    /// Get the layout of the enclave's memory.
+    pub fn memory_layout(&self) -> &MemoryLayout {
+        self.launcher.memory_layout().unwrap()
+    }
+
+    /// Set the first vCPU for an enclave. See [`Launcher::add_vcpu`].
+    pub fn add_vcpu(mut self, id: Option<u32>) -> Result<TypedLauncher<VcpusAdded>> {
+        self.launcher.add_vcpu(id)?;
+
+        Ok(self.transition())
+    }
+}
+
+impl TypedLauncher<VcpusAdded> {
+    /// Get the layout of the enclave's memory.
+    pub fn memory_layout(&self) -> &MemoryLayout {
+        self.launcher.memory_layout().unwrap()
+    }
+
+    /// Set an additional vCPU for an enclave. See [`Launcher::add_vcpu`].
+    pub fn add_vcpu(&mut self, id: Option<u32>) -> Result<()> {
+        self.launcher.add_vcpu(id)
+    }
+
+    /// Start running an enclave, choosing its CID according to the supplied policy.
+    pub fn start(mut self, flags: StartFlags, policy: CidPolicy) -> Result<TypedLauncher<Running>> {
+        self.launcher.start_with_policy(flags, policy)?;
+
+        Ok(self.transition())
+    }
+}
+
+impl TypedLauncher<Running> {
+    /// Get the layout of the enclave's memory.
+    pub fn memory_layout(&self) -> &MemoryLayout {
+        self.launcher.memory_layout().unwrap()
+    }
+
+    /// Get the enclave's CID.
+    pub fn cid(&self) -> u64 {
+        self.launcher.cid().unwrap()
+    }
+}
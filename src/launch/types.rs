@@ -1,9 +1,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
-use super::error::*;
+use super::{backend::*, error::*, timeout::*};
+use crate::systemd::Notifier;
 
 use bitflags::bitflags;
-use std::fs::File;
+use std::{cmp::min, fmt, fs::File, ops::Range, path::PathBuf};
 
 /// The image type of the enclave.
 #[derive(Debug)]
@@ -20,6 +21,21 @@ pub struct MemoryInfo<'a> {
 
     /// Amount of memory (in MiB) to allocate to the enclave.
     pub size_mib: usize,
+
+    /// Scrubbing applied to enclave memory before it is added and after the enclave terminates.
+    pub scrub: MemoryScrub,
+
+    /// Maximum number of memory regions the enclave can hold. The limit is set by the hypervisor
+    /// and is not exposed by the driver. If provided, allocating memory fails early when the
+    /// limit would be exceeded rather than when the regions are added to the enclave.
+    pub max_regions: Option<usize>,
+
+    /// Provider of the huge pages backing enclave memory.
+    pub backend: Box<dyn MemoryBackend>,
+
+    /// Huge pages to allocate the memory with, such as those reserved by the scheduler. If None,
+    /// the largest pages the backend has free are preferred.
+    pub hugepages: Option<HugePagePlan>,
 }
 
 impl<'a> MemoryInfo<'a> {
@@ -27,29 +43,274 @@ impl<'a> MemoryInfo<'a> {
         Self {
             image_type,
             size_mib,
+            scrub: MemoryScrub::default(),
+            max_regions: None,
+            backend: Box::new(AnonymousBackend),
+            hugepages: None,
+        }
+    }
+}
+
+/// Scrubbing policy for enclave memory regions.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MemoryScrub {
+    /// Rely on the kernel zeroing freshly-mapped huge pages. Regions are unmapped without being
+    /// cleared when the enclave terminates.
+    #[default]
+    None,
+
+    /// Zero all region contents outside of the enclave image before adding the regions to the
+    /// enclave, and zero the regions again once the enclave has terminated.
+    Zero,
+
+    /// Like [`MemoryScrub::Zero`], but also read back each scrubbed region and fail if any
+    /// non-zero byte remains.
+    Verify,
+}
+
+/// A number of huge pages of a given size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HugePageCount {
+    /// Huge page size (in bytes).
+    pub page_size: usize,
+
+    /// Number of huge pages.
+    pub count: usize,
+}
+
+/// A combination of huge pages covering an amount of enclave memory.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HugePagePlan(pub Vec<HugePageCount>);
+
+impl HugePagePlan {
+    /// Cover size bytes with the fewest huge pages, preferring the largest page sizes, from the
+    /// pages available. Returns None if the available pages are insufficient.
+    pub fn new(size: usize, available: &[HugePageCount]) -> Option<Self> {
+        let mut available = available.to_vec();
+        available.sort_by_key(|a| std::cmp::Reverse(a.page_size));
+
+        let mut plan = Vec::new();
+        let mut remaining = size;
+        for a in available {
+            if a.page_size == 0 {
+                continue;
+            }
+
+            let count = min(remaining / a.page_size, a.count);
+            if count > 0 {
+                plan.push(HugePageCount {
+                    page_size: a.page_size,
+                    count,
+                });
+                remaining -= count * a.page_size;
+            }
+        }
+
+        (remaining == 0).then_some(Self(plan))
+    }
+
+    /// Total number of huge pages in the plan.
+    pub fn pages(&self) -> usize {
+        self.0.iter().map(|p| p.count).sum()
+    }
+
+    /// Total amount of memory (in bytes) in the plan, saturating at usize::MAX.
+    pub fn size(&self) -> usize {
+        self.0
+            .iter()
+            .map(|p| p.count.saturating_mul(p.page_size))
+            .fold(0, usize::saturating_add)
+    }
+}
+
+impl fmt::Display for HugePagePlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pages: Vec<String> = self
+            .0
+            .iter()
+            .map(|p| format!("{} x {} MiB", p.count, p.page_size >> 20))
+            .collect();
+
+        write!(f, "{}", pages.join(", "))
+    }
+}
+
+/// Layout of an enclave's memory once it has been configured.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryLayout {
+    /// Memory regions, in the order they were added to the enclave.
+    pub regions: Vec<MemoryRegionLayout>,
+
+    /// Offset (in bytes) in enclave memory where the enclave image begins.
+    pub image_offset: u64,
+
+    /// Size of the enclave image (in bytes).
+    pub image_size: u64,
+}
+
+/// Layout of a single enclave memory region.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryRegionLayout {
+    /// Offset (in bytes) of the region in enclave memory.
+    pub offset: u64,
+
+    /// Region size (in bytes).
+    pub size: u64,
+
+    /// Size of the huge pages backing the region (in bytes).
+    pub page_size: u64,
+
+    /// Userspace (virtual) address of the region.
+    pub uaddr: u64,
+
+    /// Bytes of the region (relative to its start) holding enclave image contents, if any.
+    pub image: Option<Range<u64>>,
+}
+
+impl MemoryLayout {
+    /// Create an empty layout for an image of image_size bytes placed at image_offset.
+    pub fn new(image_offset: u64, image_size: u64) -> Self {
+        Self {
+            regions: Vec::new(),
+            image_offset,
+            image_size,
         }
     }
+
+    /// Append a memory region to the end of enclave memory, calculating which of its bytes hold
+    /// the enclave image.
+    pub fn push(&mut self, size: u64, page_size: u64, uaddr: u64) {
+        let offset = self.total_size();
+        let end = offset + size;
+
+        let image = self.image_range();
+        let image_start = image.start.clamp(offset, end) - offset;
+        let image_end = image.end.clamp(offset, end) - offset;
+
+        self.regions.push(MemoryRegionLayout {
+            offset,
+            size,
+            page_size,
+            uaddr,
+            image: (image_start < image_end).then_some(image_start..image_end),
+        });
+    }
+
+    /// Total amount of enclave memory (in bytes).
+    pub fn total_size(&self) -> u64 {
+        self.regions.iter().map(|r| r.size).sum()
+    }
+
+    /// Range of enclave memory occupied by the enclave image.
+    pub fn image_range(&self) -> Range<u64> {
+        self.image_offset..self.image_offset.saturating_add(self.image_size)
+    }
+}
+
+impl fmt::Display for MemoryLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} MiB in {} region(s), image at {:#x}..{:#x} ({} bytes)",
+            self.total_size() >> 20,
+            self.regions.len(),
+            self.image_range().start,
+            self.image_range().end,
+            self.image_size
+        )?;
+
+        for (i, r) in self.regions.iter().enumerate() {
+            write!(
+                f,
+                "  region {i}: offset {:#x}, {} MiB of {} MiB pages at uaddr {:#x}",
+                r.offset,
+                r.size >> 20,
+                r.page_size >> 20,
+                r.uaddr
+            )?;
+            if let Some(image) = &r.image {
+                write!(f, ", image bytes {:#x}..{:#x}", image.start, image.end)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
 }
 
 bitflags! {
     /// Configuration flags for starting an enclave.
     #[repr(transparent)]
-    #[derive(Copy, Clone, Default)]
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
     pub struct StartFlags: u64 {
         /// Start enclave in debug mode.
         const DEBUG = 1;
     }
 }
 
-/// Calculate an enclave's poll timeout from its image size and the amount of memory allocated to
-/// it.
+/// Configuration from which an enclave is launched (and relaunched).
+#[derive(Clone, Debug)]
+pub struct EnclaveConfig {
+    /// Path of the enclave image (EIF).
+    pub image: PathBuf,
+
+    /// Amount of memory (in MiB) to allocate to the enclave.
+    pub memory_mib: usize,
+
+    /// Number of vCPUs to add to the enclave, auto-chosen from the NE CPU pool.
+    pub cpu_count: usize,
+
+    /// Specific CPUs to add to the enclave, instead of cpu_count auto-chosen ones.
+    pub cpu_ids: Option<Vec<u32>>,
+
+    /// NUMA node from which to allocate the enclave's memory. It must be that of the enclave's
+    /// CPUs.
+    pub numa_node: Option<u32>,
+
+    /// Huge pages to allocate the enclave's memory with. If None, the largest pages free are
+    /// preferred.
+    pub hugepages: Option<HugePagePlan>,
+
+    /// Scrubbing applied to enclave memory.
+    pub scrub: MemoryScrub,
+
+    /// Flags the enclave is started with.
+    pub flags: StartFlags,
+
+    /// Policy bounding the wait for the enclave's readiness heartbeat. If None, the enclave is
+    /// considered launched as soon as it is started.
+    pub boot_timeout: Option<BootTimeoutPolicy>,
+
+    /// Notifier through which the launch's progress is reported to systemd. Readiness is only
+    /// reported once the enclave's heartbeat is received, so a boot timeout must be set.
+    pub notifier: Option<Notifier>,
+}
+
+impl EnclaveConfig {
+    pub fn new(image: impl Into<PathBuf>, memory_mib: usize, cpu_count: usize) -> Self {
+        Self {
+            image: image.into(),
+            memory_mib,
+            cpu_count,
+            cpu_ids: None,
+            numa_node: None,
+            hugepages: None,
+            scrub: MemoryScrub::default(),
+            flags: StartFlags::default(),
+            boot_timeout: None,
+            notifier: None,
+        }
+    }
+}
+
+/// Calculate an enclave's poll timeout (in milliseconds) from its image size and the amount of
+/// memory allocated to it, using the default [`BootTimeoutPolicy`].
 pub struct PollTimeout(pub i32);
 
 impl TryFrom<(&File, usize)> for PollTimeout {
     type Error = LaunchError;
 
     fn try_from(args: (&File, usize)) -> Result<Self, Self::Error> {
-        let mul = 60 * 1000; // One minute in milliseconds.
         let size = {
             let metadata = args
                 .0
@@ -60,10 +321,13 @@ impl TryFrom<(&File, usize)> for PollTimeout {
             metadata.len()
         };
 
-        let file: i32 = ((1 + (size - 1) / (6 << 30)) as i32).saturating_mul(mul);
-        let alloc: i32 = ((1 + (args.1 - 1) / (100 << 30)) as i32).saturating_mul(mul);
+        let image = BootImage {
+            size,
+            hash: String::new(),
+        };
+        let timeout = BootTimeoutPolicy::default().timeout(&image, args.1);
 
-        Ok(Self(file + alloc))
+        Ok(Self(timeout.as_millis().try_into().unwrap_or(i32::MAX)))
     }
 }
 
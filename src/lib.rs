@@ -2,7 +2,27 @@
 
 //! AWS Nitro Enclave library.
 
+pub mod attestation;
+pub mod console;
+pub mod cpio;
+pub mod eif;
+pub mod forward;
+pub mod heartbeat;
+pub mod inbound;
+pub mod init;
+pub mod kms;
 pub mod launch;
+pub mod nsm;
+pub mod oci;
+pub mod provision;
+pub mod proxy;
+pub mod ratls;
+pub mod rpc;
+pub mod scheduler;
+pub mod supervisor;
+pub mod systemd;
+pub mod transport;
+pub mod tunnel;
 
 mod device;
 
@@ -0,0 +1,114 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{AttestationRequest, Attester, NsmError, Result};
+use crate::attestation::{self, AttestationDoc};
+
+use rcgen::{
+    BasicConstraints, CertificateParams, DistinguishedName, DnType, IsCa, KeyPair,
+    PKCS_ECDSA_P384_SHA384,
+};
+use ring::{
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, ECDSA_P384_SHA384_FIXED_SIGNING},
+};
+use std::{
+    collections::BTreeMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Stand-in for the NSM outside of enclaves, producing attestation documents with configured
+/// PCRs. Its documents are signed by a certificate chaining up to a root generated for each
+/// instance, which verifiers must be configured to trust in place of the AWS root.
+pub struct FakeNsm {
+    pcrs: BTreeMap<usize, Vec<u8>>,
+    root: Vec<u8>,
+    certificate: Vec<u8>,
+    signer: EcdsaKeyPair,
+    timestamp: Option<SystemTime>,
+}
+
+impl FakeNsm {
+    /// Create a fake NSM reporting the given PCRs.
+    pub fn new(pcrs: BTreeMap<usize, Vec<u8>>) -> Result<Self> {
+        let err = |e: rcgen::Error| NsmError::Generate(e.to_string());
+
+        let root_key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).map_err(err)?;
+        let mut params = CertificateParams::new(Vec::new()).map_err(err)?;
+        params.distinguished_name = name("fake.nitro-enclaves");
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let root = params.self_signed(&root_key).map_err(err)?;
+
+        let key = KeyPair::generate_for(&PKCS_ECDSA_P384_SHA384).map_err(err)?;
+        let mut params = CertificateParams::new(Vec::new()).map_err(err)?;
+        params.distinguished_name = name("i-fake-enc0.fake.nitro-enclaves");
+        let certificate = params.signed_by(&key, &root, &root_key).map_err(err)?;
+
+        let signer = EcdsaKeyPair::from_pkcs8(
+            &ECDSA_P384_SHA384_FIXED_SIGNING,
+            &key.serialize_der(),
+            &SystemRandom::new(),
+        )
+        .map_err(|e| NsmError::Generate(e.to_string()))?;
+
+        Ok(Self {
+            pcrs,
+            root: root.der().to_vec(),
+            certificate: certificate.der().to_vec(),
+            signer,
+            timestamp: None,
+        })
+    }
+
+    /// Get the DER-encoded root certificate that documents chain up to.
+    pub fn root_certificate(&self) -> &[u8] {
+        &self.root
+    }
+
+    /// Get the PCRs reported in documents.
+    pub fn pcrs(&self) -> &BTreeMap<usize, Vec<u8>> {
+        &self.pcrs
+    }
+
+    /// Set the time at which documents claim to be produced, instead of the current time.
+    pub fn set_timestamp(&mut self, timestamp: Option<SystemTime>) {
+        self.timestamp = timestamp;
+    }
+}
+
+impl Attester for FakeNsm {
+    fn attest(&self, request: &AttestationRequest) -> Result<Vec<u8>> {
+        let timestamp = self
+            .timestamp
+            .unwrap_or_else(SystemTime::now)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let doc = AttestationDoc {
+            module_id: "i-fake-enc0".to_string(),
+            digest: "SHA384".to_string(),
+            timestamp,
+            pcrs: self.pcrs.clone(),
+            certificate: self.certificate.clone(),
+            cabundle: vec![self.root.clone()],
+            public_key: request.public_key.map(<[u8]>::to_vec),
+            user_data: request.user_data.map(<[u8]>::to_vec),
+            nonce: request.nonce.map(<[u8]>::to_vec),
+        };
+
+        let rng = SystemRandom::new();
+        attestation::encode_sign1(doc.to_payload(), |msg| {
+            self.signer
+                .sign(&rng, msg)
+                .map(|sig| sig.as_ref().to_vec())
+                .map_err(|e| NsmError::Generate(e.to_string()))
+        })
+    }
+}
+
+fn name(common_name: &str) -> DistinguishedName {
+    let mut name = DistinguishedName::new();
+    name.push(DnType::CommonName, common_name);
+
+    name
+}
@@ -0,0 +1,210 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Client of the Nitro Secure Module (NSM), available to enclaves at /dev/nsm. Requests and
+//! responses are CBOR-encoded and exchanged with a single ioctl.
+
+mod fake;
+
+pub use fake::FakeNsm;
+
+use ciborium::Value;
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io,
+    os::fd::AsRawFd,
+};
+
+const NSM_DEVICE: &str = "/dev/nsm";
+
+// Maximum size of an encoded request and response.
+const NSM_REQUEST_MAX_SIZE: usize = 0x1000;
+const NSM_RESPONSE_MAX_SIZE: usize = 0x3000;
+
+// Send a request to the NSM and receive its response.
+const NSM_IOCTL_REQUEST: u64 = nix::request_code_readwrite!(0x0a, 0, size_of::<NsmMessage>()) as _;
+
+type Result<T> = std::result::Result<T, NsmError>;
+
+/// Error that may occur when communicating with the NSM.
+#[derive(Debug)]
+pub enum NsmError {
+    /// Unable to open /dev/nsm.
+    Open(io::Error),
+
+    /// The request could not be encoded or is too large.
+    Encode(String),
+
+    /// The NSM ioctl failed.
+    Ioctl(io::Error),
+
+    /// The response could not be decoded.
+    Decode(String),
+
+    /// The NSM returned an error code.
+    Response(String),
+
+    /// The response was not of the expected form.
+    UnexpectedResponse,
+
+    /// Unable to generate the keys or certificates of a [`FakeNsm`].
+    Generate(String),
+}
+
+impl fmt::Display for NsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Open(e) => format!("unable to open {NSM_DEVICE}: {e}"),
+            Self::Encode(e) => format!("unable to encode NSM request: {e}"),
+            Self::Ioctl(e) => format!("NSM ioctl error: {e}"),
+            Self::Decode(e) => format!("unable to decode NSM response: {e}"),
+            Self::Response(e) => format!("NSM returned error: {e}"),
+            Self::UnexpectedResponse => "unexpected NSM response".to_string(),
+            Self::Generate(e) => format!("unable to generate fake NSM credentials: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+/// Data bound into an attestation document, in addition to the enclave's measurements.
+#[derive(Clone, Debug, Default)]
+pub struct AttestationRequest<'a> {
+    /// Application data (up to 512 bytes).
+    pub user_data: Option<&'a [u8]>,
+
+    /// Nonce provided by the verifier to prove freshness (up to 512 bytes).
+    pub nonce: Option<&'a [u8]>,
+
+    /// Public key of the enclave (up to 1024 bytes).
+    pub public_key: Option<&'a [u8]>,
+}
+
+/// Source of attestation documents: a COSE_Sign1 structure, signed by a certificate chaining up
+/// to the AWS Nitro Enclaves root, whose payload holds the enclave's PCRs and requested data.
+pub trait Attester: Send + Sync {
+    /// Get an attestation document binding the requested data.
+    fn attest(&self, request: &AttestationRequest) -> Result<Vec<u8>>;
+}
+
+/// Request and response buffers exchanged with the NSM driver.
+#[repr(C)]
+struct NsmMessage {
+    request: libc::iovec,
+    response: libc::iovec,
+}
+
+/// Handle to the Nitro Secure Module.
+#[derive(Debug)]
+pub struct Nsm {
+    file: File,
+}
+
+impl Nsm {
+    /// Open /dev/nsm.
+    pub fn open() -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(NSM_DEVICE)
+            .map_err(NsmError::Open)?;
+
+        Ok(Self { file })
+    }
+
+    /// Send a request, returning the body of the response. Requests without arguments are plain
+    /// strings naming the operation, others are single-entry maps from the operation name to its
+    /// arguments.
+    pub fn request(&self, request: &Value) -> Result<Value> {
+        let mut req = Vec::new();
+        ciborium::into_writer(request, &mut req).map_err(|e| NsmError::Encode(e.to_string()))?;
+        if req.len() > NSM_REQUEST_MAX_SIZE {
+            return Err(NsmError::Encode(format!(
+                "request of {} bytes exceeds maximum of {NSM_REQUEST_MAX_SIZE} bytes",
+                req.len()
+            )));
+        }
+
+        let mut resp = vec![0u8; NSM_RESPONSE_MAX_SIZE];
+        let mut msg = NsmMessage {
+            request: libc::iovec {
+                iov_base: req.as_mut_ptr() as *mut _,
+                iov_len: req.len(),
+            },
+            response: libc::iovec {
+                iov_base: resp.as_mut_ptr() as *mut _,
+                iov_len: resp.len(),
+            },
+        };
+
+        let ret = unsafe { libc::ioctl(self.file.as_raw_fd(), NSM_IOCTL_REQUEST as _, &mut msg) };
+        if ret < 0 {
+            return Err(NsmError::Ioctl(io::Error::last_os_error()));
+        }
+        resp.truncate(msg.response.iov_len);
+
+        let resp: Value =
+            ciborium::from_reader(&resp[..]).map_err(|e| NsmError::Decode(e.to_string()))?;
+
+        let name = match request {
+            Value::Text(name) => name.as_str(),
+            Value::Map(entries) => entries
+                .first()
+                .and_then(|(k, _)| k.as_text())
+                .ok_or(NsmError::UnexpectedResponse)?,
+            _ => return Err(NsmError::UnexpectedResponse),
+        };
+
+        // Responses are single-entry maps, keyed either by the operation name or by "Error".
+        let Value::Map(mut entries) = resp else {
+            return Err(NsmError::UnexpectedResponse);
+        };
+        if entries.len() != 1 {
+            return Err(NsmError::UnexpectedResponse);
+        }
+
+        let (key, body) = entries.remove(0);
+        match key.as_text() {
+            Some("Error") => Err(NsmError::Response(
+                body.as_text().unwrap_or("unknown error").to_string(),
+            )),
+            Some(key) if key == name => Ok(body),
+            _ => Err(NsmError::UnexpectedResponse),
+        }
+    }
+
+    /// Get random bytes from the NSM's hardware RNG.
+    pub fn get_random(&self) -> Result<Vec<u8>> {
+        let resp = self.request(&Value::Text("GetRandom".to_string()))?;
+
+        field(&resp, "random")
+            .and_then(Value::as_bytes)
+            .cloned()
+            .ok_or(NsmError::UnexpectedResponse)
+    }
+}
+
+impl Attester for Nsm {
+    fn attest(&self, request: &AttestationRequest) -> Result<Vec<u8>> {
+        let bytes = |b: Option<&[u8]>| b.map_or(Value::Null, |b| Value::Bytes(b.to_vec()));
+        let args = Value::Map(vec![
+            (Value::Text("user_data".into()), bytes(request.user_data)),
+            (Value::Text("nonce".into()), bytes(request.nonce)),
+            (Value::Text("public_key".into()), bytes(request.public_key)),
+        ]);
+        let resp = self.request(&Value::Map(vec![(Value::Text("Attestation".into()), args)]))?;
+
+        field(&resp, "document")
+            .and_then(Value::as_bytes)
+            .cloned()
+            .ok_or(NsmError::UnexpectedResponse)
+    }
+}
+
+/// Get a field of a CBOR map by name.
+fn field<'a>(map: &'a Value, name: &str) -> Option<&'a Value> {
+    map.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_text() == Some(name))
+        .map(|(_, v)| v)
+}
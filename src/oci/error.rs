@@ -0,0 +1,67 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{cpio::CpioError, eif::CmdlineError};
+
+use std::{fmt, io, path::PathBuf};
+
+/// Error that may occur when converting a container image.
+#[derive(Debug)]
+pub enum OciError {
+    /// Unable to read a file of the image.
+    Io(PathBuf, io::Error),
+
+    /// Unable to read the image archive or a layer.
+    Tar(io::Error),
+
+    /// A file of the image is missing.
+    MissingFile(String),
+
+    /// A manifest or configuration is not valid JSON of the expected form.
+    Json(String, serde_json::Error),
+
+    /// A blob's contents do not match its digest.
+    DigestMismatch(String),
+
+    /// A digest uses an unsupported algorithm or is malformed.
+    UnsupportedDigest(String),
+
+    /// A layer is compressed with an unsupported algorithm.
+    UnsupportedCompression,
+
+    /// The image has no manifest, or none for the requested architecture.
+    NoManifest(String),
+
+    /// The image configuration has neither an entrypoint nor a command.
+    NoCommand,
+
+    /// A command argument or environment entry contains a newline, which the command and
+    /// environment files cannot represent.
+    InvalidCommand(String),
+
+    /// Unable to build the root filesystem.
+    Cpio(CpioError),
+
+    /// The kernel command line is invalid.
+    Cmdline(CmdlineError),
+}
+
+impl fmt::Display for OciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Io(p, e) => format!("unable to read {}: {e}", p.display()),
+            Self::Tar(e) => format!("unable to read image archive: {e}"),
+            Self::MissingFile(name) => format!("image has no file {name}"),
+            Self::Json(name, e) => format!("invalid {name}: {e}"),
+            Self::DigestMismatch(digest) => format!("blob {digest} does not match its digest"),
+            Self::UnsupportedDigest(digest) => format!("unsupported digest {digest}"),
+            Self::UnsupportedCompression => "unsupported layer compression".to_string(),
+            Self::NoManifest(arch) => format!("image has no manifest for {arch}"),
+            Self::NoCommand => "image has no entrypoint or command".to_string(),
+            Self::InvalidCommand(s) => format!("invalid command or environment entry \"{s}\""),
+            Self::Cpio(e) => format!("unable to build root filesystem: {e}"),
+            Self::Cmdline(e) => format!("invalid kernel command line: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
@@ -0,0 +1,334 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Conversion of container images into enclave images, without a container runtime. Images are
+//! read from an OCI image layout (a directory, or a tarball of one) or a `docker save` archive.
+//! Their layers are applied in order into a root filesystem, which is archived along with the
+//! command and environment files read by the enclave's init (see [`crate::init`]) to form the
+//! application ramdisk.
+
+mod error;
+mod types;
+
+pub use error::*;
+pub use types::ImageConfig;
+
+use crate::{
+    cpio::{Archive, Entry},
+    eif::{Eif, KernelCmdline},
+    init,
+};
+use types::*;
+
+use flate2::read::GzDecoder;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+type Result<T> = std::result::Result<T, OciError>;
+
+// Prefix of whiteout files, which delete a path of lower layers, and the name of opaque
+// whiteouts, which hide all lower contents of their directory.
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+const IMAGE_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+const MANIFEST_LIST: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+
+/// A container image, as its execution parameters and root filesystem.
+#[derive(Clone, Debug)]
+pub struct Image {
+    pub config: ImageConfig,
+    pub rootfs: Archive,
+}
+
+impl Image {
+    /// Load an image for the host's architecture from an OCI image layout directory, or from a
+    /// tarball of an OCI image layout or a `docker save` archive.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load_for(path, host_architecture())
+    }
+
+    /// Load an image, choosing the manifest for an architecture (in the OCI naming, e.g. amd64)
+    /// when the image has several.
+    pub fn load_for(path: impl AsRef<Path>, architecture: &str) -> Result<Self> {
+        let path = path.as_ref();
+        let source = if path.is_dir() {
+            Source::Dir(path.to_path_buf())
+        } else {
+            let file = fs::File::open(path).map_err(|e| OciError::Io(path.into(), e))?;
+            Source::tar(file)?
+        };
+
+        source.image(architecture)
+    }
+
+    /// Load an image from a tarball of an OCI image layout or a `docker save` archive.
+    pub fn from_tar<R: Read>(reader: R, architecture: &str) -> Result<Self> {
+        Source::tar(reader)?.image(architecture)
+    }
+
+    /// Build the application ramdisk: the root filesystem under [`init::ROOTFS`], with the
+    /// command, environment and working directory files.
+    pub fn ramdisk(&self) -> Result<Archive> {
+        let argv = self.config.argv();
+        if argv.is_empty() {
+            return Err(OciError::NoCommand);
+        }
+
+        let mut ramdisk = Archive::new();
+        ramdisk
+            .add(init::ROOTFS, Entry::directory(0o755))
+            .map_err(OciError::Cpio)?;
+        for (path, entry) in self.rootfs.entries() {
+            ramdisk
+                .add(&format!("{}/{path}", init::ROOTFS), entry.clone())
+                .map_err(OciError::Cpio)?;
+        }
+
+        let mut files = vec![
+            (init::CMD_FILE, lines(&argv)?),
+            (init::ENV_FILE, lines(&self.config.env)?),
+        ];
+        if let Some(dir) = &self.config.working_dir {
+            files.push((init::WORKDIR_FILE, lines(std::slice::from_ref(dir))?));
+        }
+        for (path, contents) in files {
+            ramdisk
+                .add(path, Entry::file(contents.into_bytes(), 0o644))
+                .map_err(OciError::Cpio)?;
+        }
+
+        Ok(ramdisk)
+    }
+
+    /// Build an enclave image running this image, from a kernel, its command line and the
+    /// ramdisk holding the enclave's init.
+    pub fn to_eif(
+        &self,
+        kernel: Vec<u8>,
+        cmdline: &KernelCmdline,
+        init_ramdisk: Vec<u8>,
+    ) -> Result<Eif> {
+        let ramdisk = self.ramdisk()?.to_bytes().map_err(OciError::Cpio)?;
+
+        Eif::new(kernel, cmdline, vec![init_ramdisk, ramdisk]).map_err(OciError::Cmdline)
+    }
+}
+
+/// Files of an image: a directory, or the contents of an archive.
+enum Source {
+    Dir(PathBuf),
+    Tar(HashMap<String, Vec<u8>>),
+}
+
+impl Source {
+    /// Read all files of a tar archive.
+    fn tar<R: Read>(reader: R) -> Result<Self> {
+        let mut files = HashMap::new();
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries().map_err(OciError::Tar)? {
+            let mut entry = entry.map_err(OciError::Tar)?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let path = entry.path().map_err(OciError::Tar)?;
+            let path = path.to_string_lossy();
+            let path = path.trim_start_matches("./").to_string();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(OciError::Tar)?;
+            files.insert(path, data);
+        }
+
+        Ok(Self::Tar(files))
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>> {
+        match self {
+            Self::Dir(dir) => {
+                let path = dir.join(name);
+                fs::read(&path).map_err(|e| match e.kind() {
+                    std::io::ErrorKind::NotFound => OciError::MissingFile(name.to_string()),
+                    _ => OciError::Io(path, e),
+                })
+            }
+            Self::Tar(files) => files
+                .get(name)
+                .cloned()
+                .ok_or_else(|| OciError::MissingFile(name.to_string())),
+        }
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        match self {
+            Self::Dir(dir) => dir.join(name).exists(),
+            Self::Tar(files) => files.contains_key(name),
+        }
+    }
+
+    fn json<T: DeserializeOwned>(&self, name: &str, bytes: &[u8]) -> Result<T> {
+        serde_json::from_slice(bytes).map_err(|e| OciError::Json(name.to_string(), e))
+    }
+
+    /// Read a blob of an OCI image layout, verifying its digest.
+    fn blob(&self, digest: &str) -> Result<Vec<u8>> {
+        let hex = digest
+            .strip_prefix("sha256:")
+            .filter(|hex| hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| OciError::UnsupportedDigest(digest.to_string()))?;
+
+        let blob = self.read(&format!("blobs/sha256/{hex}"))?;
+        let actual: String = Sha256::digest(&blob)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        if actual != hex.to_ascii_lowercase() {
+            return Err(OciError::DigestMismatch(digest.to_string()));
+        }
+
+        Ok(blob)
+    }
+
+    /// Get the image's configuration and layers. OCI image layouts are preferred to docker
+    /// manifests, as recent versions of docker save archives hold both.
+    fn image(&self, architecture: &str) -> Result<Image> {
+        let (config, layers) = if self.exists("index.json") {
+            self.oci_image(architecture)?
+        } else {
+            self.docker_image()?
+        };
+
+        let config: ConfigFile = self.json("image configuration", &config)?;
+        let mut rootfs = Archive::new();
+        for layer in layers {
+            apply_layer(&mut rootfs, &layer)?;
+        }
+
+        Ok(Image {
+            config: config.config.unwrap_or_default(),
+            rootfs,
+        })
+    }
+
+    fn oci_image(&self, architecture: &str) -> Result<(Vec<u8>, Vec<Vec<u8>>)> {
+        let mut index: Index = self.json("index.json", &self.read("index.json")?)?;
+
+        // Descend through nested indexes to the manifest for the architecture.
+        let manifest = loop {
+            let descriptor = select(index.manifests, architecture)?;
+            let blob = self.blob(&descriptor.digest)?;
+            match descriptor.media_type.as_str() {
+                IMAGE_INDEX | MANIFEST_LIST => index = self.json("image index", &blob)?,
+                _ => break self.json::<Manifest>("image manifest", &blob)?,
+            }
+        };
+
+        let config = self.blob(&manifest.config.digest)?;
+        let layers = manifest
+            .layers
+            .iter()
+            .map(|layer| self.blob(&layer.digest))
+            .collect::<Result<_>>()?;
+
+        Ok((config, layers))
+    }
+
+    fn docker_image(&self) -> Result<(Vec<u8>, Vec<Vec<u8>>)> {
+        let manifests: Vec<DockerManifest> =
+            self.json("manifest.json", &self.read("manifest.json")?)?;
+        let manifest = manifests
+            .into_iter()
+            .next()
+            .ok_or_else(|| OciError::NoManifest("any architecture".to_string()))?;
+
+        let config = self.read(&manifest.config)?;
+        let layers = manifest
+            .layers
+            .iter()
+            .map(|layer| self.read(layer))
+            .collect::<Result<_>>()?;
+
+        Ok((config, layers))
+    }
+}
+
+/// Choose the descriptor for an architecture, or the only one of an index without platforms.
+fn select(descriptors: Vec<Descriptor>, architecture: &str) -> Result<Descriptor> {
+    let no_manifest = || OciError::NoManifest(architecture.to_string());
+
+    if descriptors.iter().all(|d| d.platform.is_none()) {
+        return descriptors.into_iter().next().ok_or_else(no_manifest);
+    }
+
+    descriptors
+        .into_iter()
+        .find(|d| {
+            d.platform
+                .as_ref()
+                .is_some_and(|p| p.architecture == architecture)
+        })
+        .ok_or_else(no_manifest)
+}
+
+/// Apply a layer (a tar archive, optionally gzip-compressed) onto a root filesystem. Whiteout
+/// files delete paths of the lower layers, and opaque whiteouts the lower contents of their
+/// directory.
+fn apply_layer(rootfs: &mut Archive, layer: &[u8]) -> Result<()> {
+    let reader: Box<dyn Read + '_> = match layer {
+        [0x1f, 0x8b, ..] => Box::new(GzDecoder::new(layer)),
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => return Err(OciError::UnsupportedCompression),
+        _ => Box::new(layer),
+    };
+
+    // Paths added by this layer, which opaque whiteouts leave in place.
+    let mut added = HashSet::new();
+
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries().map_err(OciError::Tar)? {
+        let entry = entry.map_err(OciError::Tar)?;
+        let path = entry.path().map_err(OciError::Tar)?;
+        let path = path
+            .to_string_lossy()
+            .trim_start_matches("./")
+            .trim_end_matches('/')
+            .to_string();
+        let (dir, name) = path.rsplit_once('/').unwrap_or(("", &path));
+
+        if name == OPAQUE_WHITEOUT {
+            let prefix = format!("{dir}/");
+            rootfs.retain(|p, _| !(p.starts_with(&prefix) || dir.is_empty()) || added.contains(p));
+        } else if let Some(name) = name.strip_prefix(WHITEOUT_PREFIX) {
+            rootfs.remove(&format!("{dir}/{name}"));
+        } else if let Some(converted) = rootfs.tar_entry(&path, entry).map_err(OciError::Cpio)? {
+            rootfs.add(&path, converted).map_err(OciError::Cpio)?;
+            added.insert(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Join entries into the contents of a command or environment file, one entry per line.
+fn lines(entries: &[String]) -> Result<String> {
+    entries
+        .iter()
+        .map(|entry| match entry.contains('\n') {
+            true => Err(OciError::InvalidCommand(entry.clone())),
+            false => Ok(format!("{entry}\n")),
+        })
+        .collect()
+}
+
+/// Get the host's architecture in the OCI naming.
+fn host_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => arch,
+    }
+}
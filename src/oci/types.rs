@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Deserialize;
+
+/// Descriptor of a blob, in an image index or manifest.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Descriptor {
+    #[serde(default)]
+    pub media_type: String,
+    pub digest: String,
+
+    #[serde(default)]
+    pub platform: Option<Platform>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Platform {
+    pub architecture: String,
+}
+
+/// Image index (index.json of an OCI layout), or manifest list.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Index {
+    pub manifests: Vec<Descriptor>,
+}
+
+/// Image manifest.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Manifest {
+    pub config: Descriptor,
+    pub layers: Vec<Descriptor>,
+}
+
+/// Entry of the manifest.json of a docker save archive.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct DockerManifest {
+    pub config: String,
+    pub layers: Vec<String>,
+}
+
+/// Image configuration, of which only the execution parameters are used.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ConfigFile {
+    #[serde(default)]
+    pub config: Option<ImageConfig>,
+}
+
+/// Execution parameters of an image.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImageConfig {
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub entrypoint: Vec<String>,
+
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub cmd: Vec<String>,
+
+    /// Environment variables, as KEY=VALUE entries.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub env: Vec<String>,
+
+    #[serde(default)]
+    pub working_dir: Option<String>,
+}
+
+impl ImageConfig {
+    /// Get the command run by the image: the entrypoint followed by the command.
+    pub fn argv(&self) -> Vec<String> {
+        [&self.entrypoint[..], &self.cmd[..]].concat()
+    }
+}
+
+/// Docker writes missing lists as null.
+fn null_as_default<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+}
@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{attestation::AttestationError, heartbeat::HeartbeatError, nsm::NsmError};
+
+use std::{fmt, io};
+
+/// Error that may occur while provisioning secrets to an enclave.
+#[derive(Debug)]
+pub enum ProvisionError {
+    /// Unable to listen for or connect to the other side.
+    Connect(io::Error),
+
+    /// Error reading or writing a message.
+    Io(io::Error),
+
+    /// No message was received before the timeout elapsed.
+    Timeout,
+
+    /// A message exceeds the maximum message size.
+    MessageTooLarge(usize),
+
+    /// The enclave was unable to get an attestation document.
+    Attest(NsmError),
+
+    /// The enclave's attestation document is not trusted.
+    Attestation(AttestationError),
+
+    /// The enclave's attestation document does not include its public key.
+    MissingPublicKey,
+
+    /// Unable to generate the enclave's key or encrypt the secrets.
+    Encrypt,
+
+    /// Unable to decrypt the secrets.
+    Decrypt,
+
+    /// A message could not be decoded.
+    Malformed(String),
+
+    /// The enclave did not acknowledge receiving the secrets.
+    Rejected,
+
+    /// The enclave did not signal readiness.
+    Heartbeat(HeartbeatError),
+}
+
+impl fmt::Display for ProvisionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Connect(e) => format!("unable to open provisioning connection: {e}"),
+            Self::Io(e) => format!("provisioning connection error: {e}"),
+            Self::Timeout => "provisioning timed out".to_string(),
+            Self::MessageTooLarge(size) => {
+                format!("provisioning message of {size} bytes is too large")
+            }
+            Self::Attest(e) => format!("unable to attest enclave: {e}"),
+            Self::Attestation(e) => format!("enclave attestation rejected: {e}"),
+            Self::MissingPublicKey => "attestation document has no public key".to_string(),
+            Self::Encrypt => "unable to encrypt secrets".to_string(),
+            Self::Decrypt => "unable to decrypt secrets".to_string(),
+            Self::Malformed(e) => format!("malformed provisioning message: {e}"),
+            Self::Rejected => "enclave did not acknowledge secrets".to_string(),
+            Self::Heartbeat(e) => e.to_string(),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
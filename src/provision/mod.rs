@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Delivery of secrets to an enclave once it has proven, by attestation, that it runs the
+//! expected image. The exchange runs over a single connection from the parent instance to the
+//! enclave's [`PROVISION_VSOCK_PORT`], each message prefixed by its length (u32, big-endian):
+//!
+//! 1. The parent sends a random nonce.
+//! 2. The enclave generates an ephemeral RSA key and replies with an attestation document
+//!    binding its public key and the nonce.
+//! 3. The parent verifies the document and replies with the nonce and secrets, encrypted to the
+//!    enclave's key in a CMS envelope (RSAES-OAEP and AES-256-CBC, as used by KMS).
+//! 4. The enclave decrypts the secrets and acknowledges them with a zero byte.
+
+mod error;
+
+pub use error::*;
+
+use crate::{
+    attestation::{AttestationDoc, VerifyPolicy},
+    eif::Eif,
+    heartbeat::ReadyListener,
+    kms::envelope,
+    nsm::{AttestationRequest, Attester},
+    transport::{Addr, Stream, Transport, VMADDR_CID_ANY},
+};
+
+use ciborium::Value;
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use ring::rand::{SecureRandom, SystemRandom};
+use rsa::{pkcs8::EncodePublicKey, RsaPrivateKey};
+use std::{
+    collections::BTreeMap,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+type Result<T> = std::result::Result<T, ProvisionError>;
+
+/// Port on which enclaves receive secrets.
+pub const PROVISION_VSOCK_PORT: u32 = 9001;
+
+/// Secrets delivered to an enclave, by name.
+pub type Secrets = BTreeMap<String, Vec<u8>>;
+
+// Maximum size of a message.
+const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+// Size of the parent's nonce.
+const NONCE_SIZE: usize = 32;
+
+// Size of the enclave's ephemeral RSA key, in bits.
+const KEY_BITS: usize = 2048;
+
+// Delay between attempts to connect to an enclave that is not yet listening.
+const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Parent-side provisioner, delivering secrets to enclaves whose attestation satisfies a policy.
+#[derive(Clone, Debug)]
+pub struct Provisioner {
+    policy: VerifyPolicy,
+}
+
+impl Provisioner {
+    pub fn new(policy: VerifyPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Deliver secrets only to enclaves running an image, identified by its PCRs, with attestation
+    /// documents chaining up to root.
+    pub fn for_eif(eif: &Eif, root: Vec<u8>) -> Self {
+        let mut policy = VerifyPolicy::new(root);
+        policy.pcrs = eif.pcrs();
+
+        Self::new(policy)
+    }
+
+    /// Get the policy that enclaves must satisfy.
+    pub fn policy(&self) -> &VerifyPolicy {
+        &self.policy
+    }
+
+    /// Wait up to timeout for the enclave with the given CID to signal readiness, then deliver
+    /// secrets to it within the remaining time. This follows [`Launcher::start`] in the launch
+    /// sequence, with the ready listener bound before the enclave was started.
+    ///
+    /// [`Launcher::start`]: crate::launch::Launcher::start
+    pub fn provision_after_ready<T: Transport>(
+        &self,
+        ready: &ReadyListener<T>,
+        timeout: Duration,
+        cid: u32,
+        secrets: &Secrets,
+    ) -> Result<AttestationDoc> {
+        let deadline = Instant::now() + timeout;
+        ready
+            .wait(timeout, cid)
+            .map_err(ProvisionError::Heartbeat)?;
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        self.provision_enclave(ready.transport(), cid, secrets, remaining)
+    }
+
+    /// Connect to the enclave with the given CID, retrying until it listens for secrets, and
+    /// deliver secrets to it, all within timeout.
+    pub fn provision_enclave<T: Transport>(
+        &self,
+        transport: &T,
+        cid: u32,
+        secrets: &Secrets,
+        timeout: Duration,
+    ) -> Result<AttestationDoc> {
+        let deadline = Instant::now() + timeout;
+
+        let mut stream = loop {
+            match transport.connect(Addr::new(cid, PROVISION_VSOCK_PORT)) {
+                Ok(stream) => break stream,
+                Err(e) if Instant::now() + CONNECT_RETRY_DELAY > deadline => {
+                    return Err(ProvisionError::Connect(e))
+                }
+                Err(_) => thread::sleep(CONNECT_RETRY_DELAY),
+            }
+        };
+
+        self.provision(&mut stream, secrets, Some(deadline))
+    }
+
+    /// Deliver secrets over an established connection, returning the enclave's verified
+    /// attestation document.
+    pub fn provision<S: Stream>(
+        &self,
+        stream: &mut S,
+        secrets: &Secrets,
+        deadline: Option<Instant>,
+    ) -> Result<AttestationDoc> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| ProvisionError::Encrypt)?;
+        write_message(stream, &nonce)?;
+
+        let document = read_message(stream, deadline)?;
+        let mut policy = self.policy.clone();
+        policy.nonce = Some(nonce.to_vec());
+        let doc = AttestationDoc::verify(&document, &policy, SystemTime::now())
+            .map_err(ProvisionError::Attestation)?;
+        let public_key = doc
+            .public_key
+            .as_deref()
+            .ok_or(ProvisionError::MissingPublicKey)?;
+
+        // The nonce is sealed along with the secrets, binding them to this exchange.
+        let plaintext = [&nonce[..], &encode(secrets)].concat();
+        let sealed = envelope::seal(&plaintext, public_key).map_err(|_| ProvisionError::Encrypt)?;
+        write_message(stream, &sealed)?;
+
+        match read_message(stream, deadline)?[..] {
+            [0] => Ok(doc),
+            _ => Err(ProvisionError::Rejected),
+        }
+    }
+}
+
+/// Enclave-side receiver of secrets. It should be bound before the enclave signals readiness, so
+/// that the parent can connect as soon as it is ready.
+pub struct Receiver<T: Transport> {
+    transport: T,
+    listener: T::Listener,
+}
+
+impl<T: Transport> Receiver<T> {
+    /// Listen for the parent on [`PROVISION_VSOCK_PORT`].
+    pub fn bind(transport: T) -> Result<Self> {
+        let listener = transport
+            .bind(Addr::new(VMADDR_CID_ANY, PROVISION_VSOCK_PORT))
+            .map_err(ProvisionError::Connect)?;
+
+        Ok(Self {
+            transport,
+            listener,
+        })
+    }
+
+    /// Accept the parent's connection and receive secrets, attesting with attester. Once
+    /// connected, the parent has up to timeout to complete the exchange.
+    pub fn receive<A: Attester + ?Sized>(
+        &self,
+        attester: &A,
+        timeout: Duration,
+    ) -> Result<Secrets> {
+        let (mut stream, _) = self
+            .transport
+            .accept(&self.listener)
+            .map_err(ProvisionError::Connect)?;
+
+        receive(&mut stream, attester, Some(Instant::now() + timeout))
+    }
+}
+
+/// Receive secrets over an established connection, attesting with attester.
+pub fn receive<S: Stream, A: Attester + ?Sized>(
+    stream: &mut S,
+    attester: &A,
+    deadline: Option<Instant>,
+) -> Result<Secrets> {
+    let nonce = read_message(stream, deadline)?;
+
+    let key = RsaPrivateKey::new(&mut rsa::rand_core::OsRng, KEY_BITS)
+        .map_err(|_| ProvisionError::Encrypt)?;
+    let public_key = key
+        .to_public_key()
+        .to_public_key_der()
+        .map_err(|_| ProvisionError::Encrypt)?;
+    let document = attester
+        .attest(&AttestationRequest {
+            nonce: Some(&nonce),
+            public_key: Some(public_key.as_bytes()),
+            ..Default::default()
+        })
+        .map_err(ProvisionError::Attest)?;
+    write_message(stream, &document)?;
+
+    let message = read_message(stream, deadline)?;
+    let plaintext = envelope::open(&message, &key).map_err(|_| ProvisionError::Decrypt)?;
+    let secrets = match plaintext.strip_prefix(&nonce[..]) {
+        Some(secrets) => decode(secrets)?,
+        None => return Err(ProvisionError::Malformed("nonce mismatch".to_string())),
+    };
+
+    write_message(stream, &[0])?;
+
+    Ok(secrets)
+}
+
+fn encode(secrets: &Secrets) -> Vec<u8> {
+    let map = Value::Map(
+        secrets
+            .iter()
+            .map(|(k, v)| (Value::Text(k.clone()), Value::Bytes(v.clone())))
+            .collect(),
+    );
+
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&map, &mut bytes).unwrap();
+
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Result<Secrets> {
+    let malformed = |e: String| ProvisionError::Malformed(e);
+
+    let value: Value = ciborium::from_reader(bytes).map_err(|e| malformed(e.to_string()))?;
+    value
+        .into_map()
+        .map_err(|_| malformed("secrets are not a map".to_string()))?
+        .into_iter()
+        .map(|(k, v)| match (k, v) {
+            (Value::Text(k), Value::Bytes(v)) => Ok((k, v)),
+            _ => Err(malformed("invalid secret".to_string())),
+        })
+        .collect()
+}
+
+fn write_message<S: Stream>(stream: &mut S, message: &[u8]) -> Result<()> {
+    let len = (message.len() as u32).to_be_bytes();
+
+    stream
+        .write_all(&[&len[..], message].concat())
+        .map_err(ProvisionError::Io)
+}
+
+/// Read a message, waiting for it to arrive until the deadline, if any.
+fn read_message<S: Stream>(stream: &mut S, deadline: Option<Instant>) -> Result<Vec<u8>> {
+    if let Some(deadline) = deadline {
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let timeout = remaining.as_millis().min(i32::MAX as u128) as i32;
+
+            let mut fds = [PollFd::new(stream.as_raw_fd(), PollFlags::POLLIN)];
+            match poll(&mut fds, timeout) {
+                Ok(0) => return Err(ProvisionError::Timeout),
+                Ok(_) => break,
+                Err(Errno::EINTR) => continue,
+                Err(e) => return Err(ProvisionError::Io(e.into())),
+            }
+        }
+    }
+
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).map_err(ProvisionError::Io)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_SIZE {
+        return Err(ProvisionError::MessageTooLarge(len));
+    }
+
+    let mut message = vec![0u8; len];
+    stream
+        .read_exact(&mut message)
+        .map_err(ProvisionError::Io)?;
+
+    Ok(message)
+}
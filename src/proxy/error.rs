@@ -0,0 +1,48 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt, io};
+
+/// Error that may occur when configuring or running a proxy.
+#[derive(Debug)]
+pub enum ProxyError {
+    /// Unable to read the allowlist configuration file.
+    ConfigRead(io::Error),
+
+    /// The allowlist configuration is not valid YAML, or is missing required fields.
+    ConfigParse(String),
+
+    /// Unable to resolve a host name to an address.
+    Resolve(String, io::Error),
+
+    /// The remote address is not in the allowlist.
+    NotAllowed(String, u16),
+
+    /// No address of the requested IP version was found for the remote host.
+    NoAddress(String),
+
+    /// Unable to connect to the remote host.
+    Connect(String, io::Error),
+
+    /// Error while waiting for or accepting connections.
+    Accept(io::Error),
+}
+
+impl fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::ConfigRead(e) => format!("unable to read allowlist configuration: {e}"),
+            Self::ConfigParse(e) => format!("invalid allowlist configuration: {e}"),
+            Self::Resolve(host, e) => format!("unable to resolve {host}: {e}"),
+            Self::NotAllowed(host, port) => {
+                format!("remote address {host}:{port} is not in the allowlist")
+            }
+            Self::NoAddress(host) => {
+                format!("no address of the requested IP version found for {host}")
+            }
+            Self::Connect(host, e) => format!("unable to connect to {host}: {e}"),
+            Self::Accept(e) => format!("unable to accept connection: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
@@ -0,0 +1,69 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Host-side proxy forwarding connections from enclaves over vsock to TCP hosts, equivalent to
+//! the AWS vsock-proxy.
+
+mod error;
+mod types;
+
+pub use error::*;
+pub use types::*;
+
+use crate::{
+    forward::{self, ShutdownHandle},
+    transport::Listener,
+};
+use std::{io, net::TcpStream};
+
+type Result<T> = std::result::Result<T, ProxyError>;
+
+/// Forwards connections accepted on a listener to an allowed remote TCP host.
+pub struct Proxy {
+    config: ProxyConfig,
+    shutdown: ShutdownHandle,
+}
+
+impl Proxy {
+    /// Create a proxy, ensuring that the configured remote host is in the allowlist.
+    pub fn new(config: ProxyConfig, allowlist: &Allowlist) -> Result<Self> {
+        if !allowlist.allows(&config.remote_host, config.remote_port, config.ip_version)? {
+            return Err(ProxyError::NotAllowed(
+                config.remote_host,
+                config.remote_port,
+            ));
+        }
+
+        Ok(Self {
+            config,
+            shutdown: ShutdownHandle::default(),
+        })
+    }
+
+    /// Get a handle that can be used to shut down the proxy.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Accept and forward connections until the proxy is shut down. Connections that cannot be
+    /// forwarded to the remote host are closed.
+    pub fn run<L: Listener>(&self, listener: &L) -> Result<()> {
+        forward::run(
+            listener,
+            &self.shutdown,
+            self.config.max_connections,
+            |_| self.connect().map_err(|e| io::Error::other(e.to_string())),
+        )
+        .map_err(ProxyError::Accept)
+    }
+
+    /// Connect to the remote host, trying each of its addresses in turn.
+    fn connect(&self) -> Result<TcpStream> {
+        let host = &self.config.remote_host;
+        let addrs = resolve(host, self.config.remote_port, self.config.ip_version)?;
+        if addrs.is_empty() {
+            return Err(ProxyError::NoAddress(host.clone()));
+        }
+
+        TcpStream::connect(&addrs[..]).map_err(|e| ProxyError::Connect(host.clone(), e))
+    }
+}
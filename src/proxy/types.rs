@@ -0,0 +1,178 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::error::*;
+
+use std::{
+    fs,
+    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    path::Path,
+};
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// IP version used to connect to a remote host.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum IpVersion {
+    /// Use the first address resolved for the host.
+    #[default]
+    Any,
+
+    /// Only use IPv4 addresses.
+    V4,
+
+    /// Only use IPv6 addresses.
+    V6,
+}
+
+impl IpVersion {
+    /// Check whether an address is of this IP version.
+    pub fn matches(&self, addr: &IpAddr) -> bool {
+        match self {
+            Self::Any => true,
+            Self::V4 => addr.is_ipv4(),
+            Self::V6 => addr.is_ipv6(),
+        }
+    }
+}
+
+/// A remote address that a proxy is allowed to forward connections to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllowlistEntry {
+    /// Host name or IP address.
+    pub address: String,
+
+    /// TCP port.
+    pub port: u16,
+}
+
+/// Remote addresses that a proxy is allowed to forward connections to, in the vsock-proxy
+/// configuration format:
+///
+/// ```yaml
+/// allowlist:
+/// - {address: kms.us-east-1.amazonaws.com, port: 443}
+/// - {address: 10.0.0.1, port: 8080}
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Allowlist(pub Vec<AllowlistEntry>);
+
+impl Allowlist {
+    /// Load an allowlist from a YAML configuration file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ProxyError> {
+        let yaml = fs::read_to_string(path).map_err(ProxyError::ConfigRead)?;
+
+        Self::from_yaml(&yaml)
+    }
+
+    /// Parse an allowlist from a YAML configuration.
+    pub fn from_yaml(yaml: &str) -> Result<Self, ProxyError> {
+        let docs =
+            YamlLoader::load_from_str(yaml).map_err(|e| ProxyError::ConfigParse(e.to_string()))?;
+        let Some(doc) = docs.first() else {
+            return Err(ProxyError::ConfigParse("empty configuration".to_string()));
+        };
+
+        let entries = match &doc["allowlist"] {
+            Yaml::Array(entries) => entries,
+            Yaml::BadValue | Yaml::Null => return Ok(Self::default()),
+            _ => {
+                return Err(ProxyError::ConfigParse(
+                    "allowlist is not a list".to_string(),
+                ))
+            }
+        };
+
+        let mut allowlist = Vec::new();
+        for entry in entries {
+            let address = match &entry["address"] {
+                Yaml::String(s) => s.clone(),
+                _ => {
+                    return Err(ProxyError::ConfigParse(
+                        "allowlist entry missing address".to_string(),
+                    ))
+                }
+            };
+
+            let port = match &entry["port"] {
+                Yaml::Integer(p) => u16::try_from(*p).ok(),
+                Yaml::String(s) => s.parse().ok(),
+                _ => None,
+            };
+            let Some(port) = port else {
+                return Err(ProxyError::ConfigParse(format!(
+                    "allowlist entry for {address} missing valid port"
+                )));
+            };
+
+            allowlist.push(AllowlistEntry { address, port });
+        }
+
+        Ok(Self(allowlist))
+    }
+
+    /// Check whether the remote host and port are allowed. The remote is allowed if an entry
+    /// names the same host and port, or if any of the remote's addresses (of the given IP
+    /// version) match an address resolved from an entry with the same port.
+    pub fn allows(&self, host: &str, port: u16, version: IpVersion) -> Result<bool, ProxyError> {
+        let entries: Vec<&AllowlistEntry> = self.0.iter().filter(|e| e.port == port).collect();
+
+        if entries.iter().any(|e| e.address.eq_ignore_ascii_case(host)) {
+            return Ok(true);
+        }
+
+        let remote = resolve(host, port, version)?;
+        for entry in entries {
+            // Entries that fail to resolve cannot match, but should not prevent checking the
+            // remaining entries.
+            let Ok(allowed) = resolve(&entry.address, entry.port, version) else {
+                continue;
+            };
+
+            if allowed.iter().any(|a| remote.contains(a)) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+/// Configuration of a vsock to TCP proxy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProxyConfig {
+    /// Remote host name or IP address to forward connections to.
+    pub remote_host: String,
+
+    /// Remote TCP port to forward connections to.
+    pub remote_port: u16,
+
+    /// IP version used to connect to the remote host.
+    pub ip_version: IpVersion,
+
+    /// Maximum number of connections forwarded at once. Connections accepted beyond the limit
+    /// are closed immediately.
+    pub max_connections: Option<usize>,
+}
+
+impl ProxyConfig {
+    pub fn new(remote_host: impl Into<String>, remote_port: u16) -> Self {
+        Self {
+            remote_host: remote_host.into(),
+            remote_port,
+            ip_version: IpVersion::default(),
+            max_connections: None,
+        }
+    }
+}
+
+/// Resolve the addresses of a host of the given IP version.
+pub(super) fn resolve(
+    host: &str,
+    port: u16,
+    version: IpVersion,
+) -> Result<Vec<SocketAddr>, ProxyError> {
+    let addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| ProxyError::Resolve(host.to_string(), e))?;
+
+    Ok(addrs.filter(|a| version.matches(&a.ip())).collect())
+}
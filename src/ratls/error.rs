@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{attestation::AttestationError, nsm::NsmError};
+
+use std::fmt;
+
+/// Error that may occur when setting up or verifying attested TLS.
+#[derive(Debug)]
+pub enum RaTlsError {
+    /// Unable to get an attestation document for the certificate's key.
+    Attest(NsmError),
+
+    /// Unable to generate the certificate.
+    Certificate(String),
+
+    /// Unable to build the TLS configuration.
+    Tls(rustls::Error),
+
+    /// The peer's certificate does not carry an attestation document.
+    MissingAttestation,
+
+    /// The peer's attestation document is not trusted.
+    Attestation(AttestationError),
+
+    /// The peer's attestation document does not bind its certificate's public key.
+    KeyMismatch,
+}
+
+impl fmt::Display for RaTlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Attest(e) => format!("unable to attest certificate key: {e}"),
+            Self::Certificate(e) => format!("unable to generate certificate: {e}"),
+            Self::Tls(e) => format!("TLS configuration error: {e}"),
+            Self::MissingAttestation => "certificate has no attestation document".to_string(),
+            Self::Attestation(e) => e.to_string(),
+            Self::KeyMismatch => {
+                "attestation document does not bind the certificate's public key".to_string()
+            }
+        };
+
+        write!(f, "{}", msg)
+    }
+}
@@ -0,0 +1,205 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Attested TLS (RA-TLS). Enclave servers present a self-signed certificate carrying an
+//! attestation document that binds the certificate's public key, and clients trust the server by
+//! verifying that document during the handshake instead of relying on a CA.
+
+mod error;
+
+pub use error::*;
+
+use crate::{
+    attestation::{AttestationDoc, VerifyPolicy},
+    nsm::{AttestationRequest, Attester},
+};
+
+use rcgen::{CertificateParams, CustomExtension, KeyPair, PKCS_ECDSA_P256_SHA256};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{self, CryptoProvider},
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, ServerConfig, SignatureScheme,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use x509_parser::{certificate::X509Certificate, prelude::FromDer};
+
+type Result<T> = std::result::Result<T, RaTlsError>;
+
+/// OID of the certificate extension carrying the attestation document.
+pub const ATTESTATION_EXTENSION_OID: &[u64] = &[1, 3, 6, 1, 4, 1, 4128, 2100, 1];
+
+/// Attestation document field binding the certificate's public key (its DER-encoded
+/// SubjectPublicKeyInfo).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum KeyBinding {
+    /// The document's public_key field.
+    #[default]
+    PublicKey,
+
+    /// The document's user_data field.
+    UserData,
+}
+
+/// Generate a key and a self-signed certificate carrying an attestation document that binds its
+/// public key. As verifiers bound the age of documents, servers must regenerate their
+/// certificate before it exceeds the maximum age configured by their clients.
+pub fn generate_certificate<A: Attester + ?Sized>(
+    attester: &A,
+    binding: KeyBinding,
+) -> Result<(CertificateDer<'static>, PrivateKeyDer<'static>)> {
+    let err = |e: rcgen::Error| RaTlsError::Certificate(e.to_string());
+
+    let key = KeyPair::generate_for(&PKCS_ECDSA_P256_SHA256).map_err(err)?;
+    let spki = key.public_key_der();
+
+    let mut request = AttestationRequest::default();
+    match binding {
+        KeyBinding::PublicKey => request.public_key = Some(&spki),
+        KeyBinding::UserData => request.user_data = Some(&spki),
+    }
+    let document = attester.attest(&request).map_err(RaTlsError::Attest)?;
+
+    let mut params = CertificateParams::new(vec!["enclave".to_string()]).map_err(err)?;
+    params
+        .custom_extensions
+        .push(CustomExtension::from_oid_content(
+            ATTESTATION_EXTENSION_OID,
+            document,
+        ));
+    let cert = params.self_signed(&key).map_err(err)?;
+
+    let key = PrivatePkcs8KeyDer::from(key.serialize_der());
+
+    Ok((cert.der().clone(), key.into()))
+}
+
+/// Build a TLS 1.3 server configuration presenting an attested certificate.
+pub fn server_config<A: Attester + ?Sized>(
+    attester: &A,
+    binding: KeyBinding,
+) -> Result<ServerConfig> {
+    let (cert, key) = generate_certificate(attester, binding)?;
+
+    ServerConfig::builder_with_provider(provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(RaTlsError::Tls)?
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], key)
+        .map_err(RaTlsError::Tls)
+}
+
+/// Build a TLS 1.3 client configuration trusting servers whose attestation satisfies a policy.
+pub fn client_config(policy: VerifyPolicy) -> Result<ClientConfig> {
+    let verifier = AttestedCertVerifier::new(policy);
+
+    Ok(ClientConfig::builder_with_provider(provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(RaTlsError::Tls)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
+
+/// Verifier of server certificates carrying attestation documents. The server name is not
+/// checked, as trust derives from the enclave's measurements rather than its identity.
+#[derive(Debug)]
+pub struct AttestedCertVerifier {
+    policy: VerifyPolicy,
+    provider: Arc<CryptoProvider>,
+}
+
+impl AttestedCertVerifier {
+    /// Create a verifier enforcing a policy.
+    pub fn new(policy: VerifyPolicy) -> Self {
+        Self {
+            policy,
+            provider: provider(),
+        }
+    }
+
+    /// Verify the attestation document carried by a certificate, returning its payload.
+    pub fn verify(&self, cert: &[u8], now: SystemTime) -> Result<AttestationDoc> {
+        let (_, cert) =
+            X509Certificate::from_der(cert).map_err(|e| RaTlsError::Certificate(e.to_string()))?;
+
+        let oid = ATTESTATION_EXTENSION_OID
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(".");
+        let document = cert
+            .extensions()
+            .iter()
+            .find(|ext| ext.oid.to_id_string() == oid)
+            .ok_or(RaTlsError::MissingAttestation)?
+            .value;
+
+        let doc =
+            AttestationDoc::verify(document, &self.policy, now).map_err(RaTlsError::Attestation)?;
+
+        let spki = cert.public_key().raw;
+        if doc.public_key.as_deref() != Some(spki) && doc.user_data.as_deref() != Some(spki) {
+            return Err(RaTlsError::KeyMismatch);
+        }
+
+        Ok(doc)
+    }
+}
+
+impl ServerCertVerifier for AttestedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let now = UNIX_EPOCH + Duration::from_secs(now.as_secs());
+        self.verify(end_entity, now)
+            .map_err(|e| rustls::Error::General(e.to_string()))?;
+
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(crypto::ring::default_provider())
+}
@@ -0,0 +1,170 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{codec::Codec, error::RpcError, frame::Frame, Result};
+use crate::transport::{Addr, Stream, Transport};
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    net::Shutdown,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Calls awaiting a response, each with the channel its response is sent on.
+#[derive(Default)]
+struct Pending {
+    calls: HashMap<u64, mpsc::Sender<Frame>>,
+    closed: bool,
+}
+
+/// Client making calls over a single connection. Calls may be made concurrently from multiple
+/// threads, with responses matched to calls by request ID as they arrive.
+pub struct Client<S: Stream, C: Codec> {
+    writer: Mutex<S>,
+    pending: Arc<Mutex<Pending>>,
+    next_id: AtomicU64,
+    codec: C,
+    timeout: Option<Duration>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl<S: Stream, C: Codec> Client<S, C> {
+    /// Connect to a server listening on a port of the VM with the given CID, such as that of an
+    /// enclave returned by [`Launcher::start`](crate::launch::Launcher::start).
+    pub fn connect<T>(transport: &T, cid: u32, port: u32, codec: C) -> Result<Self>
+    where
+        T: Transport<Stream = S>,
+    {
+        let stream = transport
+            .connect(Addr::new(cid, port))
+            .map_err(RpcError::Connect)?;
+
+        Self::new(stream, codec)
+    }
+
+    /// Make calls over an established connection.
+    pub fn new(stream: S, codec: C) -> Result<Self> {
+        let mut reader = stream.try_clone().map_err(RpcError::Io)?;
+        let pending = Arc::new(Mutex::new(Pending::default()));
+
+        // Dispatch responses to their calls until the connection is closed, then fail all calls
+        // still awaiting a response.
+        let responses = pending.clone();
+        let reader = thread::spawn(move || {
+            while let Ok(Some(frame)) = Frame::read_from(&mut reader) {
+                if let Some(tx) = responses.lock().unwrap().calls.remove(&frame.id()) {
+                    let _ = tx.send(frame);
+                }
+            }
+
+            let mut pending = responses.lock().unwrap();
+            pending.closed = true;
+            pending.calls.clear();
+        });
+
+        Ok(Self {
+            writer: Mutex::new(stream),
+            pending,
+            next_id: AtomicU64::new(0),
+            codec,
+            timeout: None,
+            reader: Some(reader),
+        })
+    }
+
+    /// Set the time that calls made with [`Client::call`] wait for a response. By default, calls
+    /// wait indefinitely.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Call a method, waiting for its response up to the client's timeout.
+    pub fn call<Req, Resp>(&self, method: &str, request: &Req) -> Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        self.call_inner(method, request, self.timeout)
+    }
+
+    /// Call a method, waiting for its response up to timeout. A response arriving after the
+    /// timeout is discarded.
+    pub fn call_timeout<Req, Resp>(
+        &self,
+        method: &str,
+        request: &Req,
+        timeout: Duration,
+    ) -> Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        self.call_inner(method, request, Some(timeout))
+    }
+
+    fn call_inner<Req, Resp>(
+        &self,
+        method: &str,
+        request: &Req,
+        timeout: Option<Duration>,
+    ) -> Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let frame = Frame::Request {
+            id,
+            method: method.to_string(),
+            body: self.codec.encode(request).map_err(RpcError::Encode)?,
+        };
+
+        let (tx, rx) = mpsc::channel();
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if pending.closed {
+                return Err(RpcError::Disconnected);
+            }
+            pending.calls.insert(id, tx);
+        }
+
+        let result = frame.write_to(&mut *self.writer.lock().unwrap());
+        let response = result.and_then(|()| match timeout {
+            Some(timeout) => rx.recv_timeout(timeout).map_err(|e| match e {
+                RecvTimeoutError::Timeout => RpcError::Timeout,
+                RecvTimeoutError::Disconnected => RpcError::Disconnected,
+            }),
+            None => rx.recv().map_err(|_| RpcError::Disconnected),
+        });
+
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                self.pending.lock().unwrap().calls.remove(&id);
+                return Err(e);
+            }
+        };
+
+        match response {
+            Frame::Response { body, .. } => self.codec.decode(&body).map_err(RpcError::Decode),
+            Frame::Error { message, .. } => Err(RpcError::Remote(message)),
+            Frame::Request { .. } => Err(RpcError::InvalidFrame("unexpected request")),
+        }
+    }
+}
+
+impl<S: Stream, C: Codec> Drop for Client<S, C> {
+    fn drop(&mut self) {
+        // Closing the connection stops the response reader.
+        let _ = self.writer.lock().unwrap().shutdown(Shutdown::Both);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
@@ -0,0 +1,43 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Serialization of request and response bodies. Clients and servers must use the same codec.
+pub trait Codec: Send + Sync {
+    /// Serialize a value.
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String>;
+
+    /// Deserialize a value.
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String>;
+}
+
+/// CBOR serialization, compact and the format used by the NSM.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Cbor;
+
+impl Codec for Cbor {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).map_err(|e| e.to_string())?;
+
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        ciborium::from_reader(bytes).map_err(|e| e.to_string())
+    }
+}
+
+/// JSON serialization, convenient for debugging.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Json;
+
+impl Codec for Json {
+    fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(value).map_err(|e| e.to_string())
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, String> {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,52 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt, io};
+
+/// Error that may occur when making or serving remote procedure calls.
+#[derive(Debug)]
+pub enum RpcError {
+    /// Unable to connect to the server.
+    Connect(io::Error),
+
+    /// Error reading or writing frames on a connection.
+    Io(io::Error),
+
+    /// Unable to serialize a request or response.
+    Encode(String),
+
+    /// Unable to deserialize a request or response.
+    Decode(String),
+
+    /// A frame exceeds the maximum frame size.
+    FrameTooLarge(usize),
+
+    /// A malformed frame was received.
+    InvalidFrame(&'static str),
+
+    /// No response was received before the call's deadline.
+    Timeout,
+
+    /// The connection was closed before a response was received.
+    Disconnected,
+
+    /// The server failed to handle the request.
+    Remote(String),
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Connect(e) => format!("unable to connect to RPC server: {e}"),
+            Self::Io(e) => format!("RPC connection error: {e}"),
+            Self::Encode(e) => format!("unable to serialize RPC message: {e}"),
+            Self::Decode(e) => format!("unable to deserialize RPC message: {e}"),
+            Self::FrameTooLarge(size) => format!("RPC frame of {size} bytes is too large"),
+            Self::InvalidFrame(e) => format!("invalid RPC frame: {e}"),
+            Self::Timeout => "RPC call timed out".to_string(),
+            Self::Disconnected => "RPC connection closed".to_string(),
+            Self::Remote(e) => format!("RPC call failed: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
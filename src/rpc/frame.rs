@@ -0,0 +1,128 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{error::RpcError, Result};
+
+use std::io::{self, Read, Write};
+
+/// Maximum size of a frame, excluding its length prefix.
+pub const MAX_FRAME_SIZE: usize = 16 << 20;
+
+// Frame kinds.
+const KIND_REQUEST: u8 = 0;
+const KIND_RESPONSE: u8 = 1;
+const KIND_ERROR: u8 = 2;
+
+// Size of the request ID and kind following the length prefix.
+const HEADER_SIZE: usize = 9;
+
+/// A message exchanged between client and server. On the wire, each frame is prefixed by its
+/// length (u32, big-endian), followed by the request ID (u64, big-endian) and kind (u8). Requests
+/// then carry the method name's length (u16, big-endian) and the method name, followed by the
+/// body. Responses carry the body and errors a UTF-8 message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Frame {
+    /// Call of a method.
+    Request {
+        id: u64,
+        method: String,
+        body: Vec<u8>,
+    },
+
+    /// Successful result of a call.
+    Response { id: u64, body: Vec<u8> },
+
+    /// Failed result of a call.
+    Error { id: u64, message: String },
+}
+
+impl Frame {
+    /// Get the ID of the request that the frame belongs to.
+    pub fn id(&self) -> u64 {
+        match self {
+            Self::Request { id, .. } | Self::Response { id, .. } | Self::Error { id, .. } => *id,
+        }
+    }
+
+    /// Write the frame to a stream.
+    pub fn write_to(&self, w: &mut impl Write) -> Result<()> {
+        let mut buf = vec![0u8; 4];
+        buf.extend_from_slice(&self.id().to_be_bytes());
+
+        match self {
+            Self::Request { method, body, .. } => {
+                let len = u16::try_from(method.len())
+                    .map_err(|_| RpcError::Encode("method name too long".to_string()))?;
+                buf.push(KIND_REQUEST);
+                buf.extend_from_slice(&len.to_be_bytes());
+                buf.extend_from_slice(method.as_bytes());
+                buf.extend_from_slice(body);
+            }
+            Self::Response { body, .. } => {
+                buf.push(KIND_RESPONSE);
+                buf.extend_from_slice(body);
+            }
+            Self::Error { message, .. } => {
+                buf.push(KIND_ERROR);
+                buf.extend_from_slice(message.as_bytes());
+            }
+        }
+
+        let size = buf.len() - 4;
+        if size > MAX_FRAME_SIZE {
+            return Err(RpcError::FrameTooLarge(size));
+        }
+        buf[..4].copy_from_slice(&(size as u32).to_be_bytes());
+
+        w.write_all(&buf).map_err(RpcError::Io)
+    }
+
+    /// Read a frame from a stream, returning None if the stream was closed between frames.
+    pub fn read_from(r: &mut impl Read) -> Result<Option<Self>> {
+        let mut len = [0u8; 4];
+        match r.read_exact(&mut len) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(RpcError::Io(e)),
+        }
+
+        let size = u32::from_be_bytes(len) as usize;
+        if size > MAX_FRAME_SIZE {
+            return Err(RpcError::FrameTooLarge(size));
+        }
+        if size < HEADER_SIZE {
+            return Err(RpcError::InvalidFrame("truncated header"));
+        }
+
+        let mut buf = vec![0u8; size];
+        r.read_exact(&mut buf).map_err(RpcError::Io)?;
+
+        let id = u64::from_be_bytes(buf[..8].try_into().unwrap());
+        let kind = buf[8];
+        let mut rest = buf.split_off(HEADER_SIZE);
+
+        let frame = match kind {
+            KIND_REQUEST => {
+                if rest.len() < 2 {
+                    return Err(RpcError::InvalidFrame("truncated method name"));
+                }
+                let len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+                if rest.len() < 2 + len {
+                    return Err(RpcError::InvalidFrame("truncated method name"));
+                }
+                let body = rest.split_off(2 + len);
+                let method = String::from_utf8(rest.split_off(2))
+                    .map_err(|_| RpcError::InvalidFrame("method name is not UTF-8"))?;
+
+                Self::Request { id, method, body }
+            }
+            KIND_RESPONSE => Self::Response { id, body: rest },
+            KIND_ERROR => Self::Error {
+                id,
+                message: String::from_utf8_lossy(&rest).into_owned(),
+            },
+            _ => return Err(RpcError::InvalidFrame("unknown frame kind")),
+        };
+
+        Ok(Some(frame))
+    }
+}
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Remote procedure calls between the parent instance and enclaves. Calls are carried in
+//! length-prefixed frames tagged with request IDs, so that concurrent calls can share a single
+//! connection, with request and response bodies serialized by a pluggable [`Codec`].
+
+mod client;
+mod codec;
+mod error;
+mod frame;
+mod server;
+
+pub use client::*;
+pub use codec::*;
+pub use error::*;
+pub use frame::{Frame, MAX_FRAME_SIZE};
+pub use server::*;
+
+type Result<T> = std::result::Result<T, RpcError>;
@@ -0,0 +1,138 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{codec::Codec, error::RpcError, frame::Frame, Result};
+use crate::{
+    forward::{ShutdownHandle, SHUTDOWN_POLL_MS},
+    transport::{Listener, Stream},
+};
+
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, fmt, io, sync::Mutex, thread};
+
+/// Handler of a method, given the encoded request and returning the encoded response.
+type Handler<C> = Box<dyn Fn(&C, &[u8]) -> std::result::Result<Vec<u8>, String> + Send + Sync>;
+
+/// Server dispatching calls to handlers registered by method name. Each call is handled on its
+/// own thread, so that a slow call does not hold up others made over the same connection.
+pub struct Server<C: Codec> {
+    codec: C,
+    handlers: HashMap<String, Handler<C>>,
+    shutdown: ShutdownHandle,
+}
+
+impl<C: Codec> Server<C> {
+    /// Create a server without any handlers.
+    pub fn new(codec: C) -> Self {
+        Self {
+            codec,
+            handlers: HashMap::new(),
+            shutdown: ShutdownHandle::default(),
+        }
+    }
+
+    /// Register the handler of a method, replacing any previous handler. Errors returned by the
+    /// handler are reported to the caller as [`RpcError::Remote`].
+    pub fn register<Req, Resp, E, F>(&mut self, method: &str, handler: F) -> &mut Self
+    where
+        Req: DeserializeOwned,
+        Resp: Serialize,
+        E: fmt::Display,
+        F: Fn(Req) -> std::result::Result<Resp, E> + Send + Sync + 'static,
+    {
+        let handler = move |codec: &C, body: &[u8]| {
+            let request = codec
+                .decode(body)
+                .map_err(|e| format!("invalid request: {e}"))?;
+            let response = handler(request).map_err(|e| e.to_string())?;
+
+            codec.encode(&response)
+        };
+        self.handlers.insert(method.to_string(), Box::new(handler));
+
+        self
+    }
+
+    /// Get a handle that can be used to shut down the server.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Accept connections and serve calls made over them until the server is shut down. Each
+    /// connection is served on its own thread.
+    pub fn run<L: Listener>(&self, listener: &L) -> Result<()> {
+        thread::scope(|s| loop {
+            if !wait_readable(listener, &self.shutdown)? {
+                return Ok(());
+            }
+
+            match listener.accept() {
+                Ok(stream) => {
+                    s.spawn(move || self.serve(stream));
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(RpcError::Io(e)),
+            }
+        })
+    }
+
+    /// Serve calls made over a connection until it is closed or the server is shut down.
+    pub fn serve<S: Stream>(&self, stream: S) -> Result<()> {
+        let writer = Mutex::new(stream.try_clone().map_err(RpcError::Io)?);
+        let mut reader = stream;
+
+        thread::scope(|s| {
+            while wait_readable(&reader, &self.shutdown)? {
+                let Some(frame) = Frame::read_from(&mut reader)? else {
+                    break;
+                };
+
+                let Frame::Request { id, method, body } = frame else {
+                    return Err(RpcError::InvalidFrame("expected request"));
+                };
+
+                let writer = &writer;
+                s.spawn(move || {
+                    let response = match self.dispatch(&method, &body) {
+                        Ok(body) => Frame::Response { id, body },
+                        Err(message) => Frame::Error { id, message },
+                    };
+
+                    // A failed write closes the connection, which the reader will notice.
+                    let _ = response.write_to(&mut *writer.lock().unwrap());
+                });
+            }
+
+            Ok(())
+        })
+    }
+
+    fn dispatch(&self, method: &str, body: &[u8]) -> std::result::Result<Vec<u8>, String> {
+        let handler = self
+            .handlers
+            .get(method)
+            .ok_or_else(|| format!("unknown method \"{method}\""))?;
+
+        handler(&self.codec, body)
+    }
+}
+
+/// Wait until a listener or stream is readable, returning false if the server was shut down in
+/// the meantime.
+fn wait_readable(fd: &impl std::os::fd::AsRawFd, shutdown: &ShutdownHandle) -> Result<bool> {
+    loop {
+        if shutdown.is_shutdown() {
+            return Ok(false);
+        }
+
+        let mut fds = [PollFd::new(fd.as_raw_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, SHUTDOWN_POLL_MS) {
+            Ok(0) | Err(Errno::EINTR) => continue,
+            Ok(_) => return Ok(true),
+            Err(e) => return Err(RpcError::Io(e.into())),
+        }
+    }
+}
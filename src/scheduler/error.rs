@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::types::CapacityReport;
+
+use std::{fmt, io, path::PathBuf};
+
+/// Error that may occur when admitting an enclave to the host's resources.
+#[derive(Debug)]
+pub enum SchedulerError {
+    /// Unable to read the host's topology from sysfs.
+    Sysfs(PathBuf, io::Error),
+
+    /// A sysfs file holds an unexpected value.
+    InvalidSysfs(PathBuf),
+
+    /// The number of vCPUs requested is not a multiple of the number of threads per core, so
+    /// full cores cannot be used.
+    NotFullCores {
+        cpus: usize,
+        threads_per_core: usize,
+    },
+
+    /// A requested CPU is not in the NE CPU pool, or is reserved by another enclave.
+    CpuUnavailable(u32),
+
+    /// The requested CPUs belong to different NUMA nodes.
+    MixedNumaNodes,
+
+    /// No NUMA node has enough free cores and memory for the enclave.
+    InsufficientCapacity(CapacityReport),
+}
+
+impl fmt::Display for SchedulerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Sysfs(p, e) => format!("unable to read {}: {e}", p.display()),
+            Self::InvalidSysfs(p) => format!("unexpected contents of {}", p.display()),
+            Self::NotFullCores {
+                cpus,
+                threads_per_core,
+            } => format!(
+                "{cpus} vCPU(s) do not make up full cores of {threads_per_core} thread(s) each"
+            ),
+            Self::CpuUnavailable(cpu) => {
+                format!("CPU {cpu} is not in the NE CPU pool or is already reserved")
+            }
+            Self::MixedNumaNodes => "the requested CPUs span several NUMA nodes".to_string(),
+            Self::InsufficientCapacity(report) => format!("insufficient capacity: {report}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
@@ -0,0 +1,349 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Partitioning of a parent instance's enclave resources between several enclaves. A
+//! [`ResourceManager`] knows the cores of the NE CPU pool and the free huge pages of each NUMA
+//! node, and admits enclave configurations against the resources not yet reserved: full cores and
+//! memory from a single NUMA node, as the driver requires.
+
+mod error;
+mod types;
+
+pub use error::*;
+pub use types::*;
+
+use crate::launch::{hugepage, EnclaveConfig, HugePageCount, HugePagePlan};
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+type Result<T> = std::result::Result<T, SchedulerError>;
+
+const SYSFS_DIR: &str = "/sys";
+
+// CPUs of the NE CPU pool, relative to the sysfs directory.
+const NE_CPUS_PATH: &str = "module/nitro_enclaves/parameters/ne_cpus";
+
+// CPU and NUMA node directories, relative to the sysfs directory.
+const CPU_DIR: &str = "devices/system/cpu";
+const NODE_DIR: &str = "devices/system/node";
+
+// Huge pages of hosts without NUMA support, relative to the sysfs directory.
+const HUGEPAGES_DIR: &str = "kernel/mm/hugepages";
+
+impl Topology {
+    /// Read the host's topology.
+    pub fn detect() -> Result<Self> {
+        Self::from_sysfs(SYSFS_DIR)
+    }
+
+    /// Read a host's topology from a sysfs directory.
+    pub fn from_sysfs(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref();
+        let pool = read_cpu_list(&root.join(NE_CPUS_PATH))?;
+
+        // Hosts without NUMA support have all CPUs and memory on node 0.
+        let node_dir = root.join(NODE_DIR);
+        let mut nodes = BTreeMap::new();
+        if node_dir.is_dir() {
+            for entry in fs::read_dir(&node_dir).map_err(|e| SchedulerError::Sysfs(node_dir, e))? {
+                let entry = entry.map_err(|e| SchedulerError::Sysfs(root.join(NODE_DIR), e))?;
+                let Some(node) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|n| n.strip_prefix("node"))
+                    .and_then(|n| n.parse::<u32>().ok())
+                else {
+                    continue;
+                };
+
+                nodes.insert(node, entry.path());
+            }
+        }
+
+        let node_of = |cpu: u32| -> Result<u32> {
+            for (node, dir) in &nodes {
+                if read_cpu_list(&dir.join("cpulist"))?.contains(&cpu) {
+                    return Ok(*node);
+                }
+            }
+
+            Ok(0)
+        };
+
+        // Group the pool's CPUs by core. The driver only accepts pools made of full cores.
+        let mut cores = Vec::new();
+        let mut seen: BTreeSet<u32> = BTreeSet::new();
+        for cpu in &pool {
+            if seen.contains(cpu) {
+                continue;
+            }
+
+            let path = root
+                .join(CPU_DIR)
+                .join(format!("cpu{cpu}/topology/thread_siblings_list"));
+            let siblings = match path.exists() {
+                true => read_cpu_list(&path)?,
+                false => BTreeSet::from([*cpu]),
+            };
+            seen.extend(&siblings);
+
+            if siblings.is_subset(&pool) {
+                cores.push(Core {
+                    cpus: siblings.into_iter().collect(),
+                    node: node_of(*cpu)?,
+                });
+            }
+        }
+
+        let memory = match nodes.is_empty() {
+            true => vec![(0, read_hugepages(&root.join(HUGEPAGES_DIR))?)],
+            false => nodes
+                .iter()
+                .map(|(node, dir)| Ok((*node, read_hugepages(&dir.join("hugepages"))?)))
+                .collect::<Result<_>>()?,
+        };
+
+        Ok(Self { cores, memory })
+    }
+}
+
+/// Admits enclaves against the resources of a parent instance, reserving CPUs and memory for
+/// each. Clones share the same reservations.
+#[derive(Clone, Debug)]
+pub struct ResourceManager(Arc<Mutex<State>>);
+
+#[derive(Debug)]
+struct State {
+    topology: Topology,
+    reserved: BTreeSet<u32>,
+    memory: BTreeMap<u32, Vec<HugePageCount>>,
+}
+
+impl ResourceManager {
+    /// Manage the resources of a topology, none of which are reserved.
+    pub fn new(topology: Topology) -> Self {
+        let memory = topology.memory.iter().cloned().collect();
+
+        Self(Arc::new(Mutex::new(State {
+            topology,
+            reserved: BTreeSet::new(),
+            memory,
+        })))
+    }
+
+    /// Manage the resources of the host.
+    pub fn detect() -> Result<Self> {
+        Ok(Self::new(Topology::detect()?))
+    }
+
+    /// Get the resources of each NUMA node that are not reserved.
+    pub fn capacity(&self) -> Vec<NodeCapacity> {
+        self.state().capacity()
+    }
+
+    /// Reserve the resources of an enclave: its memory, and either the CPUs it lists or cpu_count
+    /// CPUs made up of full cores, all from a single NUMA node. Apply the reservation to the
+    /// configuration with [`Reservation::apply`] before launching the enclave.
+    pub fn admit(&self, config: &EnclaveConfig) -> Result<Reservation> {
+        let mut state = self.state();
+        let capacity = state.capacity();
+        let report = || {
+            SchedulerError::InsufficientCapacity(CapacityReport {
+                cpus: config.cpu_ids.as_ref().map_or(config.cpu_count, Vec::len),
+                memory_mib: config.memory_mib,
+                nodes: capacity.clone(),
+            })
+        };
+        let plan = |node: &NodeCapacity| HugePagePlan::new(config.memory_mib << 20, &node.memory);
+
+        let (cpus, node, memory) = match &config.cpu_ids {
+            Some(ids) => {
+                let cpus: BTreeSet<u32> = ids.iter().copied().collect();
+                let mut nodes = BTreeSet::new();
+                for cpu in &cpus {
+                    let core = state
+                        .core_of(*cpu)
+                        .filter(|_| !state.reserved.contains(cpu))
+                        .ok_or(SchedulerError::CpuUnavailable(*cpu))?;
+
+                    if !core.cpus.iter().all(|c| cpus.contains(c)) {
+                        return Err(SchedulerError::NotFullCores {
+                            cpus: cpus.len(),
+                            threads_per_core: core.cpus.len(),
+                        });
+                    }
+                    nodes.insert(core.node);
+                }
+
+                let node = match nodes.len() {
+                    0 | 1 => nodes.first().copied().unwrap_or_default(),
+                    _ => return Err(SchedulerError::MixedNumaNodes),
+                };
+                let memory = capacity
+                    .iter()
+                    .find(|c| c.node == node)
+                    .and_then(plan)
+                    .ok_or_else(report)?;
+
+                (cpus.into_iter().collect(), node, memory)
+            }
+            None => {
+                let threads = state.threads_per_core();
+                if !config.cpu_count.is_multiple_of(threads) {
+                    return Err(SchedulerError::NotFullCores {
+                        cpus: config.cpu_count,
+                        threads_per_core: threads,
+                    });
+                }
+
+                let needed = config.cpu_count / threads;
+                let (node, memory) = capacity
+                    .iter()
+                    .filter(|c| c.cores >= needed)
+                    .find_map(|c| Some((c, plan(c)?)))
+                    .ok_or_else(report)?;
+
+                let cpus = node.cpus[..config.cpu_count].to_vec();
+
+                (cpus, node.node, memory)
+            }
+        };
+
+        state.reserve(&cpus, node, &memory);
+
+        Ok(Reservation {
+            manager: self.0.clone(),
+            cpus,
+            node,
+            memory,
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // The state is always left consistent, so it remains usable if a holder of the lock
+        // panicked.
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    fn capacity(&self) -> Vec<NodeCapacity> {
+        self.topology
+            .nodes()
+            .into_iter()
+            .map(|node| {
+                let free: Vec<&Core> = self
+                    .topology
+                    .cores
+                    .iter()
+                    .filter(|c| c.node == node && c.cpus.iter().all(|c| !self.reserved.contains(c)))
+                    .collect();
+
+                NodeCapacity {
+                    node,
+                    cpus: free.iter().flat_map(|c| c.cpus.iter().copied()).collect(),
+                    cores: free.len(),
+                    memory: self
+                        .memory
+                        .get(&node)
+                        .cloned()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|p| p.count > 0)
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    fn core_of(&self, cpu: u32) -> Option<&Core> {
+        self.topology.cores.iter().find(|c| c.cpus.contains(&cpu))
+    }
+
+    fn threads_per_core(&self) -> usize {
+        self.topology.cores.first().map_or(1, |c| c.cpus.len())
+    }
+
+    fn reserve(&mut self, cpus: &[u32], node: u32, memory: &HugePagePlan) {
+        self.reserved.extend(cpus);
+        self.adjust(node, memory, |free, count| free - count);
+    }
+
+    fn release(&mut self, cpus: &[u32], node: u32, memory: &HugePagePlan) {
+        cpus.iter().for_each(|cpu| {
+            self.reserved.remove(cpu);
+        });
+        self.adjust(node, memory, |free, count| free + count);
+    }
+
+    fn adjust(&mut self, node: u32, memory: &HugePagePlan, op: fn(usize, usize) -> usize) {
+        let free = self.memory.entry(node).or_default();
+        for pages in &memory.0 {
+            if let Some(f) = free.iter_mut().find(|f| f.page_size == pages.page_size) {
+                f.count = op(f.count, pages.count);
+            }
+        }
+    }
+}
+
+/// Resources reserved for an enclave, released when dropped. Hand the reservation to the
+/// enclave's [`LaunchTransaction`](crate::launch::LaunchTransaction) to release it when the launch
+/// fails or the enclave terminates.
+#[derive(Debug)]
+pub struct Reservation {
+    manager: Arc<Mutex<State>>,
+    cpus: Vec<u32>,
+    node: u32,
+    memory: HugePagePlan,
+}
+
+impl Reservation {
+    /// Get the CPUs reserved for the enclave.
+    pub fn cpu_ids(&self) -> &[u32] {
+        &self.cpus
+    }
+
+    /// Get the NUMA node of the enclave's CPUs and memory.
+    pub fn numa_node(&self) -> u32 {
+        self.node
+    }
+
+    /// Get the huge pages reserved for the enclave's memory.
+    pub fn memory(&self) -> &HugePagePlan {
+        &self.memory
+    }
+
+    /// Set an enclave configuration's CPUs, NUMA node and huge pages to those reserved.
+    pub fn apply(&self, config: &mut EnclaveConfig) {
+        config.cpu_ids = Some(self.cpus.clone());
+        config.numa_node = Some(self.node);
+        config.hugepages = Some(self.memory.clone());
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.manager
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .release(&self.cpus, self.node, &self.memory);
+    }
+}
+
+fn read_cpu_list(path: &Path) -> Result<BTreeSet<u32>> {
+    let list = fs::read_to_string(path).map_err(|e| SchedulerError::Sysfs(path.into(), e))?;
+
+    parse_cpu_list(&list).ok_or_else(|| SchedulerError::InvalidSysfs(path.into()))
+}
+
+fn read_hugepages(dir: &Path) -> Result<Vec<HugePageCount>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    hugepage::available_in(dir).map_err(|e| SchedulerError::Sysfs(PathBuf::from(dir), e))
+}
@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::launch::{HugePageCount, HugePagePlan};
+
+use std::{collections::BTreeSet, fmt};
+
+/// A physical core of the NE CPU pool, with its hardware threads.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Core {
+    /// CPU ids of the core's threads.
+    pub cpus: Vec<u32>,
+
+    /// NUMA node of the core.
+    pub node: u32,
+}
+
+/// Resources available to enclaves on the host: the cores of the NE CPU pool, and the free huge
+/// pages of each NUMA node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Topology {
+    /// Cores of the NE CPU pool.
+    pub cores: Vec<Core>,
+
+    /// Free huge pages of each NUMA node, ordered by node.
+    pub memory: Vec<(u32, Vec<HugePageCount>)>,
+}
+
+impl Topology {
+    /// Get the NUMA nodes holding cores or memory.
+    pub fn nodes(&self) -> BTreeSet<u32> {
+        self.cores
+            .iter()
+            .map(|c| c.node)
+            .chain(self.memory.iter().map(|(node, _)| *node))
+            .collect()
+    }
+}
+
+/// Resources of a NUMA node that are not reserved by an enclave.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeCapacity {
+    /// NUMA node.
+    pub node: u32,
+
+    /// CPUs of the node's free cores.
+    pub cpus: Vec<u32>,
+
+    /// Number of free cores.
+    pub cores: usize,
+
+    /// Free huge pages.
+    pub memory: Vec<HugePageCount>,
+}
+
+impl NodeCapacity {
+    /// Amount of free memory (in MiB).
+    pub fn memory_mib(&self) -> usize {
+        self.memory
+            .iter()
+            .map(|p| p.count * p.page_size)
+            .sum::<usize>()
+            >> 20
+    }
+}
+
+/// Resources requested by an enclave, and those remaining on each NUMA node, reported when the
+/// enclave cannot be admitted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CapacityReport {
+    /// Number of vCPUs requested.
+    pub cpus: usize,
+
+    /// Amount of memory (in MiB) requested.
+    pub memory_mib: usize,
+
+    /// Remaining resources of each NUMA node.
+    pub nodes: Vec<NodeCapacity>,
+}
+
+impl fmt::Display for CapacityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "requested {} vCPU(s) and {} MiB on a single NUMA node; available:",
+            self.cpus, self.memory_mib
+        )?;
+
+        for n in &self.nodes {
+            write!(
+                f,
+                " node {}: {} core(s) (CPUs {}), {} MiB",
+                n.node,
+                n.cores,
+                format_cpu_list(&n.cpus),
+                n.memory_mib()
+            )?;
+            if !n.memory.is_empty() {
+                write!(f, " ({})", HugePagePlan(n.memory.clone()))?;
+            }
+            write!(f, ";")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a CPU list, such as "0-3,8,10-11".
+pub fn parse_cpu_list(list: &str) -> Option<BTreeSet<u32>> {
+    let mut cpus = BTreeSet::new();
+
+    for range in list.trim().split(',').filter(|r| !r.is_empty()) {
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        let (start, end): (u32, u32) = (start.parse().ok()?, end.parse().ok()?);
+        if start > end {
+            return None;
+        }
+        cpus.extend(start..=end);
+    }
+
+    Some(cpus)
+}
+
+/// Format CPU ids as a CPU list, such as "0-3,8,10-11".
+pub fn format_cpu_list(cpus: &[u32]) -> String {
+    let cpus: BTreeSet<u32> = cpus.iter().copied().collect();
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+
+    for cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == cpu => *end = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+
+    let ranges: Vec<String> = ranges
+        .into_iter()
+        .map(|(start, end)| match start == end {
+            true => start.to_string(),
+            false => format!("{start}-{end}"),
+        })
+        .collect();
+
+    ranges.join(",")
+}
@@ -0,0 +1,33 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::types::ExitReason;
+use crate::launch::LaunchError;
+
+use std::fmt;
+
+/// Error ending the supervision of an enclave.
+#[derive(Debug)]
+pub enum SupervisorError {
+    /// The enclave failed and the restart policy does not restart it.
+    Failed(ExitReason),
+
+    /// The enclave failed after the maximum number of consecutive restarts.
+    RetriesExhausted { retries: u32, reason: ExitReason },
+
+    /// Unable to terminate the enclave when shutting down.
+    Terminate(LaunchError),
+}
+
+impl fmt::Display for SupervisorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Failed(reason) => format!("enclave failed: {reason}"),
+            Self::RetriesExhausted { retries, reason } => {
+                format!("enclave failed after {retries} restart(s): {reason}")
+            }
+            Self::Terminate(e) => format!("unable to terminate enclave: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
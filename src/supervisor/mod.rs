@@ -0,0 +1,347 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Supervision of long-lived enclaves. A [`Supervisor`] launches an enclave, watches for it to
+//! exit (reported by its VM file descriptor) or stop answering liveness probes, and relaunches it
+//! according to a [`RestartPolicy`], reusing its CID where possible. Lifecycle [`Event`]s are sent
+//! to subscribers, and optionally reported to systemd.
+
+mod error;
+mod types;
+
+pub use error::*;
+pub use types::*;
+
+use crate::{
+    forward::{ShutdownHandle, SHUTDOWN_POLL_MS},
+    heartbeat,
+    inbound::EnclaveCid,
+    launch::{CidPolicy, IoctlError, LaunchError, Launcher},
+    systemd::{Notifier, State},
+    transport::Transport,
+};
+
+use nix::{
+    errno::Errno,
+    poll::{poll, PollFd, PollFlags},
+};
+use std::{
+    os::fd::RawFd,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+type Result<T> = std::result::Result<T, SupervisorError>;
+
+/// A running enclave, as launched for a supervisor.
+pub trait Enclave {
+    /// Get the enclave's CID.
+    fn cid(&self) -> u64;
+
+    /// Get the enclave VM's file descriptor, which reports POLLHUP once the enclave exits.
+    fn vm_fd(&self) -> RawFd;
+
+    /// Terminate the enclave and release its resources.
+    fn terminate(self) -> std::result::Result<(), LaunchError>;
+}
+
+impl Enclave for Launcher {
+    fn cid(&self) -> u64 {
+        Launcher::cid(self).unwrap_or_default()
+    }
+
+    fn vm_fd(&self) -> RawFd {
+        Launcher::vm_fd(self)
+    }
+
+    fn terminate(mut self) -> std::result::Result<(), LaunchError> {
+        // The supervisor reports stopping to systemd itself, and terminating an enclave to
+        // relaunch it does not stop the service.
+        self.take_notifier();
+        Launcher::terminate(self)
+    }
+}
+
+/// Keeps an enclave running, relaunching it when it ends.
+pub struct Supervisor<T: Transport> {
+    transport: T,
+    policy: RestartPolicy,
+    liveness: Option<LivenessCheck>,
+    notifier: Option<Notifier>,
+    enclave_cid: Option<EnclaveCid>,
+    subscribers: Mutex<Vec<Sender<Event>>>,
+    shutdown: ShutdownHandle,
+}
+
+impl<T: Transport> Supervisor<T> {
+    /// Create a supervisor restarting enclaves according to the policy. The transport is used to
+    /// probe the liveness of the enclave's application, if enabled.
+    pub fn new(transport: T, policy: RestartPolicy) -> Self {
+        Self {
+            transport,
+            policy,
+            liveness: None,
+            notifier: None,
+            enclave_cid: None,
+            subscribers: Mutex::new(Vec::new()),
+            shutdown: ShutdownHandle::default(),
+        }
+    }
+
+    /// Probe the liveness of the enclave's application (see [`heartbeat::probe`]), terminating
+    /// the enclave once it misses too many probes.
+    pub fn with_liveness(mut self, check: LivenessCheck) -> Self {
+        self.liveness = Some(check);
+        self
+    }
+
+    /// Report the enclave's lifecycle to systemd as the service's status, and `STOPPING=1` when
+    /// terminating it. If the notifier has a watchdog, it is pinged while the enclave runs and
+    /// answers its liveness probes (if enabled), but not while it is being relaunched.
+    ///
+    /// Readiness is reported by the launch, once the enclave's heartbeat is received: set the
+    /// notifier on the [`EnclaveConfig`](crate::launch::EnclaveConfig) too.
+    pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Keep the CID that inbound forwarders connect to up to date: set when the enclave is
+    /// (re)started, and cleared once it ends.
+    pub fn with_enclave_cid(mut self, cid: EnclaveCid) -> Self {
+        self.enclave_cid = Some(cid);
+        self
+    }
+
+    /// Receive the lifecycle events of the supervised enclave.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+
+        rx
+    }
+
+    /// Get a handle that can be used to terminate the enclave and stop the supervisor.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Launch an enclave and keep it running until the supervisor is shut down, or the enclave
+    /// ends and the restart policy does not restart it.
+    ///
+    /// The enclave is launched with launch, which is given the CID policy to start the enclave
+    /// with: cid for the first launch, and the enclave's previous CID for relaunches (falling
+    /// back to cid if it is no longer available). Typically, launch calls [`Launcher::launch`]
+    /// with the enclave's configuration.
+    pub fn run<E, F>(&self, cid: CidPolicy, mut launch: F) -> Result<()>
+    where
+        E: Enclave,
+        F: FnMut(CidPolicy) -> std::result::Result<E, LaunchError>,
+    {
+        let mut attempt = 0;
+        let mut restarts = 0;
+        let mut previous = None;
+
+        loop {
+            if self.shutdown.is_shutdown() {
+                self.emit(Event::Stopped);
+                return Ok(());
+            }
+
+            self.emit(Event::Starting { attempt });
+            attempt += 1;
+
+            let started = Instant::now();
+            let (cid, reason) = match self.launch(&mut launch, &cid, previous) {
+                Ok(enclave) => {
+                    let cid = enclave.cid();
+                    previous = Some(cid);
+                    self.emit(Event::Started { cid });
+
+                    let Some(reason) = self.monitor(&enclave) else {
+                        self.emit(Event::Stopping { cid });
+                        enclave.terminate().map_err(SupervisorError::Terminate)?;
+                        self.emit(Event::Stopped);
+                        return Ok(());
+                    };
+
+                    // The enclave is relaunched regardless of whether its resources could be
+                    // released.
+                    let _ = enclave.terminate();
+                    (Some(cid), reason)
+                }
+                Err(e) => (None, ExitReason::LaunchFailed(Arc::new(e))),
+            };
+            self.emit(Event::Exited {
+                cid,
+                reason: reason.clone(),
+            });
+
+            if started.elapsed() >= self.policy.reset_after {
+                restarts = 0;
+            }
+
+            if !self.policy.should_restart(&reason) {
+                self.emit(Event::GaveUp {
+                    reason: reason.clone(),
+                });
+
+                return match reason.is_failure() {
+                    true => Err(SupervisorError::Failed(reason)),
+                    false => Ok(()),
+                };
+            }
+
+            if self.policy.max_retries.is_some_and(|max| restarts >= max) {
+                self.emit(Event::GaveUp {
+                    reason: reason.clone(),
+                });
+
+                return Err(SupervisorError::RetriesExhausted {
+                    retries: restarts,
+                    reason,
+                });
+            }
+
+            let delay = self.policy.backoff.delay(restarts);
+            restarts += 1;
+            self.emit(Event::Restarting {
+                restart: restarts,
+                delay,
+            });
+            self.sleep(delay);
+        }
+    }
+
+    /// Launch the enclave, reusing its previous CID (if any) so that peers can keep reaching it.
+    fn launch<E, F>(
+        &self,
+        launch: &mut F,
+        cid: &CidPolicy,
+        previous: Option<u64>,
+    ) -> std::result::Result<E, LaunchError>
+    where
+        F: FnMut(CidPolicy) -> std::result::Result<E, LaunchError>,
+    {
+        if let Some(previous) = previous {
+            match launch(CidPolicy::Strict(previous)) {
+                Err(e)
+                    if matches!(
+                        e.cause(),
+                        LaunchError::CidInUse(_)
+                            | LaunchError::Ioctl(IoctlError::InvalidEnclaveCid)
+                    ) => {}
+                ret => return ret,
+            }
+        }
+
+        launch(cid.clone())
+    }
+
+    /// Wait for the enclave to exit or stop responding. Returns None if the supervisor was shut
+    /// down in the meantime.
+    fn monitor<E: Enclave>(&self, enclave: &E) -> Option<ExitReason> {
+        let mut next_probe = self
+            .liveness
+            .as_ref()
+            .map(|check| Instant::now() + check.grace_period);
+        let mut misses = 0;
+        let mut last_ping: Option<Instant> = None;
+
+        loop {
+            if self.shutdown.is_shutdown() {
+                return None;
+            }
+
+            // POLLHUP (and POLLERR) are reported without being requested.
+            let mut fds = [PollFd::new(enclave.vm_fd(), PollFlags::empty())];
+            match poll(&mut fds, SHUTDOWN_POLL_MS) {
+                Ok(0) | Err(Errno::EINTR) => (),
+                _ => return Some(ExitReason::Exited),
+            }
+
+            // Ping the watchdog twice per timeout, as long as the enclave answers its probes.
+            let watchdog = self.notifier.as_ref().and_then(|n| n.watchdog());
+            if let Some(timeout) = watchdog {
+                if misses == 0 && last_ping.is_none_or(|at| at.elapsed() >= timeout / 2) {
+                    self.notify(&[State::Watchdog]);
+                    last_ping = Some(Instant::now());
+                }
+            }
+
+            let (Some(check), Some(at)) = (&self.liveness, next_probe) else {
+                continue;
+            };
+            if Instant::now() < at {
+                continue;
+            }
+
+            match heartbeat::probe(&self.transport, enclave.cid() as u32, check.timeout) {
+                Ok(()) => misses = 0,
+                Err(_) => misses += 1,
+            }
+            if misses > 0 && misses >= check.misses {
+                return Some(ExitReason::Unresponsive { misses });
+            }
+
+            next_probe = Some(Instant::now() + check.interval);
+        }
+    }
+
+    /// Sleep for a delay, returning early if the supervisor is shut down.
+    fn sleep(&self, delay: Duration) {
+        let deadline = Instant::now() + delay;
+
+        while !self.shutdown.is_shutdown() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+
+            thread::sleep(remaining.min(Duration::from_millis(SHUTDOWN_POLL_MS as u64)));
+        }
+    }
+
+    /// Send an event to all subscribers, dropping those that stopped receiving, and report it to
+    /// systemd.
+    fn emit(&self, event: Event) {
+        if let Some(enclave_cid) = &self.enclave_cid {
+            match &event {
+                Event::Started { cid } => enclave_cid.set(*cid),
+                Event::Exited { .. } | Event::Stopped => enclave_cid.clear(),
+                _ => (),
+            }
+        }
+
+        let status = match &event {
+            Event::Starting { attempt } => format!("Launching enclave (attempt {})", attempt + 1),
+            Event::Started { cid } => format!("Enclave running (CID {cid})"),
+            Event::Exited { reason, .. } => format!("Enclave ended: {reason}"),
+            Event::Restarting { restart, delay } => {
+                format!("Restarting enclave in {delay:?} (restart {restart})")
+            }
+            Event::GaveUp { reason } => format!("Enclave not restarted: {reason}"),
+            Event::Stopping { cid } => format!("Stopping enclave (CID {cid})"),
+            Event::Stopped => "Enclave stopped".to_string(),
+        };
+        match &event {
+            Event::Stopping { .. } => self.notify(&[State::Stopping, State::Status(status)]),
+            _ => self.notify(&[State::Status(status)]),
+        }
+
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|s| s.send(event.clone()).is_ok());
+    }
+
+    /// Notify systemd, if enabled. Notifications are best-effort and do not stop supervision.
+    fn notify(&self, states: &[State]) {
+        if let Some(notifier) = &self.notifier {
+            let _ = notifier.notify(states);
+        }
+    }
+}
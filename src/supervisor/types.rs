@@ -0,0 +1,196 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::launch::LaunchError;
+
+use std::{fmt, sync::Arc, time::Duration};
+
+/// When to restart an enclave once it has ended.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Restart {
+    /// Never restart the enclave.
+    Never,
+
+    /// Restart the enclave however it ended.
+    #[default]
+    Always,
+
+    /// Restart the enclave if it failed to launch or stopped responding, but not if it exited by
+    /// itself.
+    OnFailure,
+}
+
+/// Exponential backoff between restarts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backoff {
+    /// Delay before the first restart.
+    pub initial: Duration,
+
+    /// Upper bound of the delay.
+    pub max: Duration,
+
+    /// Factor by which the delay grows with each consecutive restart.
+    pub multiplier: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(60),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl Backoff {
+    /// Get the delay before a restart, given the number of consecutive restarts preceding it.
+    pub fn delay(&self, restarts: u32) -> Duration {
+        let delay = self.initial.as_secs_f64() * self.multiplier.powi(restarts as i32);
+
+        Duration::try_from_secs_f64(delay)
+            .unwrap_or(self.max)
+            .min(self.max)
+    }
+}
+
+/// Policy for restarting a supervised enclave.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestartPolicy {
+    /// When to restart the enclave.
+    pub restart: Restart,
+
+    /// Maximum number of consecutive restarts, after which the supervisor gives up. If None, the
+    /// enclave is restarted indefinitely.
+    pub max_retries: Option<u32>,
+
+    /// Delay between restarts.
+    pub backoff: Backoff,
+
+    /// Time an enclave must run for before its restarts are no longer considered consecutive,
+    /// resetting the retry count and backoff.
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            restart: Restart::default(),
+            max_retries: None,
+            backoff: Backoff::default(),
+            reset_after: Duration::from_secs(300),
+        }
+    }
+}
+
+impl RestartPolicy {
+    pub fn new(restart: Restart) -> Self {
+        Self {
+            restart,
+            ..Default::default()
+        }
+    }
+
+    /// Check whether an enclave that ended for the given reason should be restarted, ignoring
+    /// the retry limit.
+    pub fn should_restart(&self, reason: &ExitReason) -> bool {
+        match self.restart {
+            Restart::Never => false,
+            Restart::Always => true,
+            Restart::OnFailure => reason.is_failure(),
+        }
+    }
+}
+
+/// Liveness probing of a supervised enclave's application. See [`crate::heartbeat::probe`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LivenessCheck {
+    /// Interval between probes.
+    pub interval: Duration,
+
+    /// Time allowed for each probe to be answered.
+    pub timeout: Duration,
+
+    /// Number of consecutive unanswered probes after which the enclave is considered
+    /// unresponsive.
+    pub misses: u32,
+
+    /// Time allowed after the enclave starts before probing begins.
+    pub grace_period: Duration,
+}
+
+impl Default for LivenessCheck {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            timeout: Duration::from_secs(1),
+            misses: 3,
+            grace_period: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Why a supervised enclave ended.
+#[derive(Clone, Debug)]
+pub enum ExitReason {
+    /// The enclave VM exited by itself. The driver does not report why, so this is not
+    /// considered a failure.
+    Exited,
+
+    /// The enclave's application missed the given number of consecutive liveness probes, and
+    /// the enclave was terminated.
+    Unresponsive { misses: u32 },
+
+    /// The enclave could not be launched.
+    LaunchFailed(Arc<LaunchError>),
+}
+
+impl ExitReason {
+    /// Check whether the enclave failed, rather than exiting by itself.
+    pub fn is_failure(&self) -> bool {
+        !matches!(self, Self::Exited)
+    }
+}
+
+impl fmt::Display for ExitReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Exited => "enclave exited".to_string(),
+            Self::Unresponsive { misses } => {
+                format!("enclave missed {misses} consecutive liveness probe(s)")
+            }
+            Self::LaunchFailed(e) => format!("unable to launch enclave: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+/// Lifecycle event of a supervised enclave.
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// The enclave is being launched, for the given attempt (starting from zero for the first
+    /// launch).
+    Starting { attempt: u32 },
+
+    /// The enclave was launched with the given CID.
+    Started { cid: u64 },
+
+    /// The enclave ended. The CID is None if the enclave could not be launched.
+    Exited {
+        cid: Option<u64>,
+        reason: ExitReason,
+    },
+
+    /// The enclave will be restarted after a delay, as the given consecutive restart (starting
+    /// from one).
+    Restarting { restart: u32, delay: Duration },
+
+    /// The enclave will not be restarted after it ended for the given reason.
+    GaveUp { reason: ExitReason },
+
+    /// The supervisor is terminating the enclave at the request of its owner.
+    Stopping { cid: u64 },
+
+    /// The supervisor stopped at the request of its owner.
+    Stopped,
+}
@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! systemd service notifications (see sd_notify(3)). When an enclave launcher runs as a
+//! `Type=notify` unit, a [`Notifier`] reports the service's readiness and status to systemd over
+//! the datagram socket named by `$NOTIFY_SOCKET`, and pings its watchdog if `WatchdogSec=` is
+//! set.
+
+use std::{
+    env, fmt, io,
+    os::{
+        linux::net::SocketAddrExt,
+        unix::net::{SocketAddr, UnixDatagram},
+    },
+    sync::Arc,
+    time::Duration,
+};
+
+type Result<T> = std::result::Result<T, SystemdError>;
+
+/// Error that may occur when notifying systemd.
+#[derive(Debug)]
+pub enum SystemdError {
+    /// The notification socket address is neither a path nor an abstract socket name.
+    InvalidSocket(String),
+
+    /// Unable to create the socket notifications are sent from.
+    Socket(io::Error),
+
+    /// Unable to send a notification.
+    Send(io::Error),
+}
+
+impl fmt::Display for SystemdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::InvalidSocket(s) => format!("invalid notification socket address \"{s}\""),
+            Self::Socket(e) => format!("unable to create notification socket: {e}"),
+            Self::Send(e) => format!("unable to send notification: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+/// A state reported to systemd.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum State {
+    /// Service startup is finished (`READY=1`).
+    Ready,
+
+    /// The service is shutting down (`STOPPING=1`).
+    Stopping,
+
+    /// Free-form status shown by `systemctl status` (`STATUS=`).
+    Status(String),
+
+    /// Reset the service's watchdog timer (`WATCHDOG=1`).
+    Watchdog,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::Ready => "READY=1".to_string(),
+            Self::Stopping => "STOPPING=1".to_string(),
+            // Each assignment takes a single line.
+            Self::Status(s) => format!("STATUS={}", s.replace('\n', " ")),
+            Self::Watchdog => "WATCHDOG=1".to_string(),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
+
+/// Sends notifications to systemd. Clones share the same socket.
+#[derive(Clone, Debug)]
+pub struct Notifier {
+    socket: Arc<UnixDatagram>,
+    addr: SocketAddr,
+    watchdog: Option<Duration>,
+}
+
+impl Notifier {
+    /// Create a notifier from the environment systemd sets up for the service. Returns None if
+    /// the service is not expected to send notifications (`$NOTIFY_SOCKET` is unset).
+    pub fn from_env() -> Result<Option<Self>> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    /// Create a notifier from the variables systemd sets up for the service (`NOTIFY_SOCKET`,
+    /// `WATCHDOG_USEC` and `WATCHDOG_PID`), as looked up by name.
+    pub fn from_vars(lookup: impl Fn(&str) -> Option<String>) -> Result<Option<Self>> {
+        let Some(socket) = lookup("NOTIFY_SOCKET") else {
+            return Ok(None);
+        };
+
+        let mut notifier = Self::new(&socket)?;
+        notifier.watchdog = watchdog_from_vars(lookup);
+
+        Ok(Some(notifier))
+    }
+
+    /// Create a notifier sending to a socket, given as a path or (prefixed with '@') an abstract
+    /// socket name.
+    pub fn new(socket: &str) -> Result<Self> {
+        let addr = match socket.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name),
+            None if socket.starts_with('/') => SocketAddr::from_pathname(socket),
+            None => return Err(SystemdError::InvalidSocket(socket.to_string())),
+        }
+        .map_err(|_| SystemdError::InvalidSocket(socket.to_string()))?;
+
+        let socket = UnixDatagram::unbound().map_err(SystemdError::Socket)?;
+
+        Ok(Self {
+            socket: Arc::new(socket),
+            addr,
+            watchdog: None,
+        })
+    }
+
+    /// Expect watchdog pings within a timeout, in place of that set by systemd.
+    pub fn with_watchdog(mut self, timeout: Duration) -> Self {
+        self.watchdog = Some(timeout);
+        self
+    }
+
+    /// Get the time within which systemd expects watchdog pings, if its watchdog is enabled.
+    pub fn watchdog(&self) -> Option<Duration> {
+        self.watchdog
+    }
+
+    /// Send states to systemd in a single notification.
+    pub fn notify(&self, states: &[State]) -> Result<()> {
+        let msg: String = states.iter().map(|s| format!("{s}\n")).collect();
+
+        self.socket
+            .send_to_addr(msg.as_bytes(), &self.addr)
+            .map_err(SystemdError::Send)?;
+
+        Ok(())
+    }
+
+    /// Report a status.
+    pub fn status(&self, status: impl Into<String>) -> Result<()> {
+        self.notify(&[State::Status(status.into())])
+    }
+}
+
+/// Read the watchdog timeout set by systemd (`$WATCHDOG_USEC`), if it applies to this process
+/// (`$WATCHDOG_PID`, when set, is its PID).
+pub fn watchdog_timeout() -> Option<Duration> {
+    watchdog_from_vars(|name| env::var(name).ok())
+}
+
+fn watchdog_from_vars(lookup: impl Fn(&str) -> Option<String>) -> Option<Duration> {
+    if let Some(pid) = lookup("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+
+    let usec: u64 = lookup("WATCHDOG_USEC")?.parse().ok()?;
+
+    (usec > 0).then(|| Duration::from_micros(usec))
+}
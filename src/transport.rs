@@ -0,0 +1,236 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transports for communicating between the parent instance and enclaves, addressed by
+//! (CID, port) pairs. Besides vsock, a stand-in transport over Unix sockets allows vsock-based
+//! protocols to be tested on any Linux machine.
+
+use nix::sys::socket::{
+    bind, connect, socket, AddressFamily, SockFlag, SockType, UnixAddr as NixUnixAddr,
+};
+use std::{
+    fmt, io,
+    net::{Shutdown, TcpListener, TcpStream},
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use vsock::{VsockListener, VsockStream};
+
+/// CID used to listen for connections on any CID.
+pub const VMADDR_CID_ANY: u32 = u32::MAX;
+
+/// CID of the hypervisor, which serves enclave consoles.
+pub const VMADDR_CID_HYPERVISOR: u32 = 0;
+
+/// CID of the parent instance.
+pub const VMADDR_CID_PARENT: u32 = 3;
+
+/// Address of a transport endpoint.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Addr {
+    /// Context identifier of the VM.
+    pub cid: u32,
+
+    /// Port within the VM.
+    pub port: u32,
+}
+
+impl Addr {
+    pub fn new(cid: u32, port: u32) -> Self {
+        Self { cid, port }
+    }
+}
+
+impl fmt::Display for Addr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.cid, self.port)
+    }
+}
+
+/// A connected stream that can be split for bidirectional copying.
+pub trait Stream: io::Read + io::Write + AsRawFd + Send + Sized + 'static {
+    /// Create an independently owned handle to the stream.
+    fn try_clone(&self) -> io::Result<Self>;
+
+    /// Shut down the read, write, or both halves of the stream.
+    fn shutdown(&self, how: Shutdown) -> io::Result<()>;
+}
+
+/// A listener accepting connections.
+pub trait Listener: AsRawFd {
+    type Stream: Stream;
+
+    /// Accept a new connection.
+    fn accept(&self) -> io::Result<Self::Stream>;
+}
+
+macro_rules! impl_stream {
+    ($stream:ty) => {
+        impl Stream for $stream {
+            fn try_clone(&self) -> io::Result<Self> {
+                <$stream>::try_clone(self)
+            }
+
+            fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+                <$stream>::shutdown(self, how)
+            }
+        }
+    };
+}
+
+impl_stream!(VsockStream);
+impl_stream!(TcpStream);
+impl_stream!(UnixStream);
+
+macro_rules! impl_listener {
+    ($listener:ty, $stream:ty) => {
+        impl Listener for $listener {
+            type Stream = $stream;
+
+            fn accept(&self) -> io::Result<Self::Stream> {
+                <$listener>::accept(self).map(|(stream, _)| stream)
+            }
+        }
+    };
+}
+
+impl_listener!(VsockListener, VsockStream);
+impl_listener!(TcpListener, TcpStream);
+impl_listener!(UnixListener, UnixStream);
+
+/// Connects and listens on (CID, port) addresses.
+pub trait Transport: Send + Sync {
+    type Stream: Stream;
+    type Listener: Listener<Stream = Self::Stream> + Send + Sync;
+
+    /// Listen for connections on an address.
+    fn bind(&self, addr: Addr) -> io::Result<Self::Listener>;
+
+    /// Connect to an address.
+    fn connect(&self, addr: Addr) -> io::Result<Self::Stream>;
+
+    /// Accept a connection on a listener, returning the stream and the peer's address.
+    fn accept(&self, listener: &Self::Listener) -> io::Result<(Self::Stream, Addr)>;
+}
+
+/// The AF_VSOCK transport.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct VsockTransport;
+
+impl Transport for VsockTransport {
+    type Stream = VsockStream;
+    type Listener = VsockListener;
+
+    fn bind(&self, addr: Addr) -> io::Result<Self::Listener> {
+        VsockListener::bind_with_cid_port(addr.cid, addr.port)
+    }
+
+    fn connect(&self, addr: Addr) -> io::Result<Self::Stream> {
+        VsockStream::connect_with_cid_port(addr.cid, addr.port)
+    }
+
+    fn accept(&self, listener: &Self::Listener) -> io::Result<(Self::Stream, Addr)> {
+        let (stream, addr) = listener.accept()?;
+
+        Ok((stream, Addr::new(addr.cid(), addr.port())))
+    }
+}
+
+/// A stand-in for vsock that maps addresses to Unix sockets in a directory. Each instance acts on
+/// behalf of a VM with a given CID: listening on [`VMADDR_CID_ANY`] listens on that CID, and
+/// peers see connections as originating from it.
+#[derive(Clone, Debug)]
+pub struct UnixTransport {
+    dir: PathBuf,
+    cid: u32,
+}
+
+impl UnixTransport {
+    /// Create a transport for the VM with the given CID, placing sockets in dir. Transports of
+    /// VMs that communicate with each other must share the same directory.
+    pub fn new(dir: impl Into<PathBuf>, cid: u32) -> Self {
+        Self {
+            dir: dir.into(),
+            cid,
+        }
+    }
+
+    /// Get the directory holding the transport's sockets.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, addr: Addr) -> PathBuf {
+        self.dir.join(format!("{}-{}.sock", addr.cid, addr.port))
+    }
+}
+
+impl Transport for UnixTransport {
+    type Stream = UnixStream;
+    type Listener = UnixListener;
+
+    fn bind(&self, mut addr: Addr) -> io::Result<Self::Listener> {
+        if addr.cid == VMADDR_CID_ANY {
+            addr.cid = self.cid;
+        }
+
+        // Remove any socket left behind by a previous listener.
+        let path = self.path(addr);
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => (),
+        }
+
+        UnixListener::bind(path)
+    }
+
+    fn connect(&self, addr: Addr) -> io::Result<Self::Stream> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        // Bind the connecting socket to a path identifying this transport's CID, so that the
+        // peer can determine where the connection originated.
+        let local = self.dir.join(format!(
+            "peer-{}-{}-{}.sock",
+            self.cid,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        let fd = socket(
+            AddressFamily::Unix,
+            SockType::Stream,
+            SockFlag::SOCK_CLOEXEC,
+            None,
+        )?;
+        let stream = unsafe { UnixStream::from_raw_fd(fd) };
+
+        bind(fd, &NixUnixAddr::new(&local)?)?;
+        let result = connect(fd, &NixUnixAddr::new(&self.path(addr))?);
+
+        // The peer identifies the connection by the path it was bound to, which no longer needs
+        // to exist on the filesystem.
+        std::fs::remove_file(&local)?;
+        result?;
+
+        Ok(stream)
+    }
+
+    fn accept(&self, listener: &Self::Listener) -> io::Result<(Self::Stream, Addr)> {
+        let (stream, peer) = listener.accept()?;
+
+        // Connections from other transports are tagged with their CID and ephemeral "port".
+        let cid = peer
+            .as_pathname()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.strip_prefix("peer-"))
+            .and_then(|n| n.split('-').next())
+            .and_then(|cid| cid.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown peer address"))?;
+
+        Ok((stream, Addr::new(cid, 0)))
+    }
+}
@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt, io, net::SocketAddr};
+
+/// Error that may occur when configuring or running a tunnel.
+#[derive(Debug)]
+pub enum TunnelError {
+    /// A mapping is not of the form LOCAL_ADDR=[CID:]PORT.
+    InvalidMapping(String),
+
+    /// Unable to listen on a local address.
+    Bind(SocketAddr, io::Error),
+
+    /// Error while waiting for or accepting connections on a local address.
+    Accept(SocketAddr, io::Error),
+}
+
+impl fmt::Display for TunnelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self {
+            Self::InvalidMapping(m) => {
+                format!("invalid mapping {m}, expected LOCAL_ADDR=[CID:]PORT")
+            }
+            Self::Bind(addr, e) => format!("unable to listen on {addr}: {e}"),
+            Self::Accept(addr, e) => format!("unable to accept connection on {addr}: {e}"),
+        };
+
+        write!(f, "{}", msg)
+    }
+}
@@ -0,0 +1,81 @@
+// SPDX-License-Identifier: Apache-2.0
+
+//! Enclave-side tunnel forwarding local TCP connections over vsock, allowing applications inside
+//! an enclave to reach the network through a vsock proxy on the parent instance.
+
+mod error;
+mod types;
+
+pub use error::*;
+pub use types::*;
+
+use crate::{
+    forward::{self, ShutdownHandle},
+    transport::{Addr, Transport, VsockTransport},
+};
+use std::{net::TcpListener, thread};
+
+type Result<T> = std::result::Result<T, TunnelError>;
+
+/// Forwards connections accepted on local TCP addresses to vsock ports.
+pub struct Tunnel {
+    mappings: Vec<TunnelMapping>,
+    max_connections: Option<usize>,
+    shutdown: ShutdownHandle,
+}
+
+impl Tunnel {
+    /// Create a tunnel for the given mappings. Each mapping may forward up to max_connections
+    /// connections at once, if provided.
+    pub fn new(mappings: Vec<TunnelMapping>, max_connections: Option<usize>) -> Self {
+        Self {
+            mappings,
+            max_connections,
+            shutdown: ShutdownHandle::default(),
+        }
+    }
+
+    /// Get a handle that can be used to shut down the tunnel.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Accept and forward connections over vsock until the tunnel is shut down.
+    pub fn run(&self) -> Result<()> {
+        self.run_with(&VsockTransport)
+    }
+
+    /// Accept and forward connections over the given transport until the tunnel is shut down. If
+    /// any mapping fails, the whole tunnel is shut down.
+    pub fn run_with<T: Transport>(&self, transport: &T) -> Result<()> {
+        // Bind all local addresses up front, so that the tunnel fails before accepting any
+        // connection if one is unavailable.
+        let mut listeners = Vec::new();
+        for m in &self.mappings {
+            let listener = TcpListener::bind(m.local).map_err(|e| TunnelError::Bind(m.local, e))?;
+            listeners.push((m, listener));
+        }
+
+        thread::scope(|s| {
+            let threads: Vec<_> = listeners
+                .iter()
+                .map(|(m, listener)| {
+                    s.spawn(move || {
+                        let result =
+                            forward::run(listener, &self.shutdown, self.max_connections, |_| {
+                                transport.connect(Addr::new(m.cid, m.port))
+                            })
+                            .map_err(|e| TunnelError::Accept(m.local, e));
+
+                        if result.is_err() {
+                            self.shutdown.shutdown();
+                        }
+                        result
+                    })
+                })
+                .collect();
+
+            threads.into_iter().try_for_each(|t| t.join().unwrap())
+        })
+    }
+}
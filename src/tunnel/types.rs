@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use super::error::*;
+
+use std::{net::SocketAddr, str::FromStr};
+
+pub use crate::transport::VMADDR_CID_PARENT;
+
+/// Maps a local TCP address inside the enclave to a vsock port, typically one on which the
+/// parent runs a vsock proxy.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TunnelMapping {
+    /// Local address to accept TCP connections on.
+    pub local: SocketAddr,
+
+    /// CID to forward connections to.
+    pub cid: u32,
+
+    /// vsock port to forward connections to.
+    pub port: u32,
+}
+
+impl TunnelMapping {
+    /// Map a local address to a vsock port on the parent instance.
+    pub fn new(local: SocketAddr, port: u32) -> Self {
+        Self {
+            local,
+            cid: VMADDR_CID_PARENT,
+            port,
+        }
+    }
+}
+
+impl FromStr for TunnelMapping {
+    type Err = TunnelError;
+
+    /// Parse a mapping of the form LOCAL_ADDR=[CID:]PORT, e.g. "127.0.0.1:443=8000". If the CID
+    /// is omitted, the parent instance's CID is used.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || TunnelError::InvalidMapping(s.to_string());
+
+        let (local, remote) = s.split_once('=').ok_or_else(invalid)?;
+        let local = local.parse().map_err(|_| invalid())?;
+
+        let (cid, port) = match remote.split_once(':') {
+            Some((cid, port)) => (cid.parse().map_err(|_| invalid())?, port),
+            None => (VMADDR_CID_PARENT, remote),
+        };
+        let port = port.parse().map_err(|_| invalid())?;
+
+        Ok(Self { local, cid, port })
+    }
+}
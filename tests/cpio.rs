@@ -0,0 +1,90 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::cpio::{Archive, Entry, EntryKind};
+use std::os::unix::fs::PermissionsExt;
+
+fn field(header: &[u8], index: usize) -> u32 {
+    let start = 6 + index * 8;
+    u32::from_str_radix(std::str::from_utf8(&header[start..start + 8]).unwrap(), 16).unwrap()
+}
+
+// Archives are identical regardless of the order in which entries are added, with parent
+// directories created implicitly.
+#[test]
+fn cpio_reproducible() {
+    let mut a = Archive::new();
+    a.add("/init", Entry::file(b"#!/bin/sh\n".to_vec(), 0o755))
+        .unwrap();
+    a.add("dev/console", Entry::char_device(5, 1, 0o600))
+        .unwrap();
+    a.add("bin/sh", Entry::symlink("busybox")).unwrap();
+
+    let mut b = Archive::new();
+    b.add("bin/sh", Entry::symlink("busybox")).unwrap();
+    b.add("./dev//console", Entry::char_device(5, 1, 0o600))
+        .unwrap();
+    b.add("init", Entry::file(b"#!/bin/sh\n".to_vec(), 0o755))
+        .unwrap();
+
+    let bytes = a.to_bytes().unwrap();
+    assert_eq!(bytes, b.to_bytes().unwrap());
+    assert_eq!(bytes.len() % 4, 0);
+
+    let paths: Vec<&str> = a.entries().map(|(path, _)| path).collect();
+    assert_eq!(paths, ["bin", "bin/sh", "dev", "dev/console", "init"]);
+    assert!(a.add("../etc/passwd", Entry::fifo(0o644)).is_err());
+
+    // First entry: the "bin" directory.
+    assert_eq!(&bytes[..6], b"070701");
+    assert_eq!(field(&bytes, 0), 1);
+    assert_eq!(field(&bytes, 1), 0o040755);
+    assert_eq!(field(&bytes, 5), 0);
+    assert_eq!(field(&bytes, 11), 4);
+    assert_eq!(&bytes[110..114], b"bin\0");
+
+    // The device node carries its device numbers.
+    let console = bytes
+        .windows(12)
+        .position(|w| w == b"dev/console\0")
+        .unwrap()
+        - 110;
+    assert_eq!(field(&bytes[console..], 1), 0o020600);
+    assert_eq!(field(&bytes[console..], 9), 5);
+    assert_eq!(field(&bytes[console..], 10), 1);
+
+    assert!(bytes.ends_with(b"TRAILER!!!\0\0\0\0"));
+    assert_eq!(Archive::parse(&bytes).unwrap(), a);
+}
+
+// A tar stream and a directory tree produce the same archive, with owners normalized.
+#[test]
+fn cpio_tar_and_dir() {
+    let dir = TempDir::new("cpio");
+    let root = dir.join("root");
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+    std::fs::write(root.join("etc/hostname"), b"enclave\n").unwrap();
+    std::fs::set_permissions(root.join("etc"), PermissionsExt::from_mode(0o755)).unwrap();
+    std::fs::set_permissions(root.join("etc/hostname"), PermissionsExt::from_mode(0o644)).unwrap();
+    std::os::unix::fs::symlink("/etc/hostname", root.join("hostname")).unwrap();
+
+    let mut tar = tar::Builder::new(Vec::new());
+    tar.follow_symlinks(false);
+    tar.append_dir_all(".", &root).unwrap();
+    let tar = tar.into_inner().unwrap();
+
+    let from_dir = Archive::from_dir(&root).unwrap();
+    let from_tar = Archive::from_tar(&tar[..]).unwrap();
+    assert_eq!(from_dir.to_bytes().unwrap(), from_tar.to_bytes().unwrap());
+
+    assert_eq!(
+        from_dir.get("etc/hostname"),
+        Some(&Entry::file(b"enclave\n".to_vec(), 0o644))
+    );
+    assert_eq!(
+        from_tar.get("hostname").map(|e| &e.kind),
+        Some(&EntryKind::Symlink("/etc/hostname".to_string()))
+    );
+}
@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use nitro_enclaves::{
+    cpio::{Archive, Entry},
+    eif::{
+        CmdlineError, ContentDiff, Eif, EifError, EifMetadata, FileChange, FileDiff, KernelCmdline,
+        SectionChange, SectionType, MAX_CMDLINE_SIZE,
+    },
+};
+use std::io::Cursor;
+
+fn image(app: &[u8]) -> Eif {
+    Eif::new(
+        b"kernel".to_vec(),
+        &"reboot=k console=ttyS0".parse().unwrap(),
+        vec![b"init".to_vec(), app.to_vec()],
+    )
+    .unwrap()
+}
+
+// Write an image and read it back, detecting corruption with the CRC.
+#[test]
+fn eif_roundtrip() {
+    let eif = image(b"app");
+
+    let mut bytes = Vec::new();
+    eif.write(&mut bytes).unwrap();
+    assert_eq!(&bytes[..4], b".eif");
+
+    let read = Eif::read(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(read, eif);
+    assert_eq!(read.cmdline(), Some(&b"reboot=k console=ttyS0"[..]));
+    assert_eq!(read.sections_of(SectionType::Ramdisk).count(), 2);
+
+    // Flip a byte of the application ramdisk.
+    let last = bytes.len() - 1;
+    bytes[last] ^= 1;
+    assert!(matches!(
+        Eif::read(&mut Cursor::new(&bytes)),
+        Err(EifError::CrcMismatch { .. })
+    ));
+
+    bytes[0] = b'x';
+    assert!(matches!(
+        Eif::read(&mut Cursor::new(&bytes)),
+        Err(EifError::InvalidMagic)
+    ));
+}
+
+// Changing the application ramdisk changes PCR0 and PCR2, but not the boot measurement PCR1.
+#[test]
+fn eif_pcrs() {
+    let a = image(b"app").pcrs();
+    let b = image(b"other app").pcrs();
+
+    assert_eq!(a.keys().copied().collect::<Vec<_>>(), [0, 1, 2]);
+    assert!(a.values().all(|pcr| pcr.len() == 48));
+    assert_ne!(a[&0], b[&0]);
+    assert_eq!(a[&1], b[&1]);
+    assert_ne!(a[&2], b[&2]);
+}
+
+// Compare two images, per section and file, explaining which sections changed each PCR.
+#[test]
+fn eif_diff() {
+    let ramdisk = |server: &[u8], mode| {
+        let mut archive = Archive::new();
+        archive
+            .add("rootfs/bin/server", Entry::file(server.to_vec(), mode))
+            .unwrap();
+        archive
+            .add("cmd", Entry::file(b"/bin/server\n".to_vec(), 0o644))
+            .unwrap();
+        archive.to_bytes().unwrap()
+    };
+    let old = Eif::new(
+        b"kernel".to_vec(),
+        &"console=ttyS0".parse().unwrap(),
+        vec![b"init".to_vec(), ramdisk(b"v1", 0o755)],
+    )
+    .unwrap();
+    let new = Eif::new(
+        b"kernel".to_vec(),
+        &"console=ttyS0 quiet".parse().unwrap(),
+        vec![b"init".to_vec(), ramdisk(b"v2", 0o700)],
+    )
+    .unwrap();
+
+    assert!(old.diff(&old).is_empty());
+    assert!(old.diff(&old).pcrs.is_empty());
+
+    let diff = old.diff(&new);
+    assert!(diff.header.is_empty());
+    assert_eq!(diff.sections.len(), 2);
+    assert_eq!(
+        diff.sections[0].change,
+        SectionChange::Modified {
+            old_size: 13,
+            new_size: 19,
+            content: ContentDiff::Cmdline {
+                old: "console=ttyS0".to_string(),
+                new: "console=ttyS0 quiet".to_string(),
+            },
+        }
+    );
+    let SectionChange::Modified { content, .. } = &diff.sections[1].change else {
+        panic!("ramdisk not modified");
+    };
+    assert_eq!(
+        *content,
+        ContentDiff::Files(vec![FileDiff {
+            path: "rootfs/bin/server".to_string(),
+            change: FileChange::Modified {
+                contents: true,
+                mode: Some((0o755, 0o700)),
+                owner: None,
+                mtime: None,
+                ino: None,
+                nlink: None,
+            },
+        }])
+    );
+
+    let causes: Vec<_> = diff.pcrs.iter().map(|p| (p.index, &p.causes[..])).collect();
+    assert_eq!(
+        causes,
+        [
+            (
+                0,
+                &[(SectionType::Cmdline, 0), (SectionType::Ramdisk, 1)][..]
+            ),
+            (1, &[(SectionType::Cmdline, 0)][..]),
+            (2, &[(SectionType::Ramdisk, 1)][..]),
+        ]
+    );
+    assert!(diff
+        .to_string()
+        .contains("M rootfs/bin/server contents mode 755 -> 700"));
+
+    // Files rebuilt with other timestamps.
+    let mut archive = Archive::parse(&ramdisk(b"v1", 0o755)).unwrap();
+    archive.set_mtime(1700000000);
+    let rebuilt = Eif::new(
+        b"kernel".to_vec(),
+        &"console=ttyS0".parse().unwrap(),
+        vec![b"init".to_vec(), archive.to_bytes().unwrap()],
+    )
+    .unwrap();
+    let diff = old.diff(&rebuilt);
+    let SectionChange::Modified { content, .. } = &diff.sections[0].change else {
+        panic!("ramdisk not modified");
+    };
+    let ContentDiff::Files(files) = content else {
+        panic!("no file differences");
+    };
+    assert_eq!(files.len(), 4);
+    assert!(files.iter().all(|f| matches!(
+        f.change,
+        FileChange::Modified {
+            contents: false,
+            mtime: Some((0, 1700000000)),
+            ..
+        }
+    )));
+    assert!(diff.to_string().contains("M cmd mtime 0 -> 1700000000"));
+
+    // The same files, encoded differently.
+    let mut padded = ramdisk(b"v1", 0o755);
+    padded.extend_from_slice(&[0; 512]);
+    let padded = Eif::new(
+        b"kernel".to_vec(),
+        &"console=ttyS0".parse().unwrap(),
+        vec![b"init".to_vec(), padded],
+    )
+    .unwrap();
+    let SectionChange::Modified { content, .. } = &old.diff(&padded).sections[0].change else {
+        panic!("ramdisk not modified");
+    };
+    assert_eq!(*content, ContentDiff::Encoding);
+}
+
+// Write an image's metadata and read it back, alone or with the whole image.
+#[test]
+fn eif_metadata() {
+    let mut eif = image(b"app");
+    assert_eq!(eif.metadata().unwrap(), None);
+
+    let metadata = EifMetadata::new("server", "1.2.0").with_custom("commit", "0a1b2c3");
+    eif.set_metadata(&metadata).unwrap();
+    assert_eq!(eif.sections[2].section_type, SectionType::Metadata);
+    assert_eq!(eif.metadata().unwrap(), Some(metadata.clone()));
+
+    let mut bytes = Vec::new();
+    eif.write(&mut bytes).unwrap();
+    assert_eq!(
+        Eif::read_metadata(&mut Cursor::new(&bytes)).unwrap(),
+        Some(metadata.clone())
+    );
+
+    // Sections of unknown types are skipped.
+    let offset = u64::from_be_bytes(bytes[28..36].try_into().unwrap()) as usize;
+    bytes[offset..offset + 2].copy_from_slice(&0x7fffu16.to_be_bytes());
+    assert_eq!(
+        Eif::read_metadata(&mut Cursor::new(&bytes)).unwrap(),
+        Some(metadata)
+    );
+
+    // Metadata written by nitro-cli, without custom fields.
+    let section = eif
+        .sections
+        .iter_mut()
+        .find(|s| s.section_type == SectionType::Metadata)
+        .unwrap();
+    section.data = br#"{"ImageName":"hello","ImageVersion":"latest","BuildMetadata":{
+        "BuildTime":"2024-01-01T00:00:00Z","BuildTool":"nitro-cli","BuildToolVersion":"1.3.0",
+        "OperatingSystem":"linux","KernelVersion":"6.1"},"DockerInfo":{"Id":"sha256:00"},
+        "CustomMetadata":null}"#
+        .to_vec();
+    let metadata = eif.metadata().unwrap().unwrap();
+    assert_eq!(metadata.image_name, "hello");
+    assert_eq!(metadata.build_info.build_tool, "nitro-cli");
+    assert_eq!(metadata.docker_info["Id"], "sha256:00");
+    assert!(metadata.custom.is_empty());
+}
+
+// Parse, edit and validate kernel command lines, and edit that of an existing image.
+#[test]
+fn eif_cmdline() {
+    let mut cmdline =
+        KernelCmdline::parse(r#"console=ttyS0 quiet "dyndbg=file x.c +p" init=/init -- --verbose"#)
+            .unwrap();
+    assert_eq!(cmdline.get("console"), Some(Some("ttyS0")));
+    assert_eq!(cmdline.get("quiet"), Some(None));
+    assert_eq!(cmdline.get("dyndbg"), Some(Some("file x.c +p")));
+    assert_eq!(cmdline.init_args(), ["--verbose"]);
+
+    cmdline
+        .set("console", "hvc0")
+        .remove("quiet")
+        .flag("nomodules");
+    assert_eq!(
+        cmdline.to_string(),
+        r#"console=hvc0 dyndbg="file x.c +p" init=/init nomodules -- --verbose"#
+    );
+    assert_eq!(KernelCmdline::parse(&cmdline.to_string()).unwrap(), cmdline);
+
+    assert!(KernelCmdline::nitro().get("i8042.noaux").is_some());
+    assert!(KernelCmdline::nitro_aarch64().get("i8042.noaux").is_none());
+    assert_eq!(
+        KernelCmdline::nitro_aarch64().get("reboot"),
+        Some(Some("k"))
+    );
+
+    assert!(matches!(
+        KernelCmdline::parse(r#"init="/bin/sh"#),
+        Err(CmdlineError::UnbalancedQuotes)
+    ));
+    let mut long = KernelCmdline::new();
+    long.set("padding", &"x".repeat(MAX_CMDLINE_SIZE));
+    assert!(matches!(long.validate(), Err(CmdlineError::TooLong(_))));
+    let mut invalid = KernelCmdline::new();
+    invalid.set("init", "\"quoted\"");
+    assert!(matches!(
+        invalid.validate(),
+        Err(CmdlineError::InvalidValue(_))
+    ));
+    assert!(matches!(
+        Eif::new(b"kernel".to_vec(), &invalid, Vec::new()),
+        Err(CmdlineError::InvalidValue(_))
+    ));
+
+    // Edit the command line of an image, which changes its PCR0 and PCR1 but not PCR2.
+    let eif = image(b"app");
+    let mut edited = eif.clone();
+    let mut cmdline = edited.kernel_cmdline().unwrap().unwrap();
+    cmdline.set("console", "hvc0");
+    edited.set_cmdline(&cmdline).unwrap();
+    assert!(matches!(
+        edited.set_cmdline(&long),
+        Err(EifError::Cmdline(CmdlineError::TooLong(_)))
+    ));
+
+    let mut bytes = Vec::new();
+    edited.write(&mut bytes).unwrap();
+    let read = Eif::read(&mut Cursor::new(&bytes)).unwrap();
+    assert_eq!(read.cmdline(), Some(&b"reboot=k console=hvc0"[..]));
+
+    let deltas: Vec<usize> = eif.diff(&read).pcrs.iter().map(|p| p.index).collect();
+    assert_eq!(deltas, [0, 1]);
+}
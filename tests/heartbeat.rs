@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::{
+    console,
+    heartbeat::{self, HeartbeatError, ReadyListener, ENCLAVE_READY_VSOCK_PORT, HEART_BEAT},
+    launch::{BootCalibration, BootImage, BootTimeoutPolicy, CALIBRATION_SAMPLES},
+    transport::{Addr, Transport, UnixTransport, VMADDR_CID_HYPERVISOR, VMADDR_CID_PARENT},
+};
+use std::{
+    io::{Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+// Signal readiness from a simulated enclave and verify the parent echoes the heartbeat back.
+#[test]
+fn heartbeat_ready() {
+    let dir = TempDir::new("heartbeat");
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let enclave = UnixTransport::new(&dir, 16);
+
+    let listener = ReadyListener::bind(parent).unwrap();
+
+    let t = thread::spawn(move || heartbeat::signal(&enclave, Duration::from_secs(5)));
+
+    listener.wait(Duration::from_secs(5), 16).unwrap();
+    t.join().unwrap().unwrap();
+
+    // No enclave sends a heartbeat.
+    assert!(matches!(
+        listener.wait(Duration::from_millis(50), 16),
+        Err(HeartbeatError::Timeout)
+    ));
+
+    // Enclaves started concurrently share the listener, whichever order their heartbeats arrive
+    // in.
+    let listener = &listener;
+    thread::scope(|s| {
+        let waiters: Vec<_> = [16, 17]
+            .map(|cid| s.spawn(move || listener.wait(Duration::from_secs(5), cid)))
+            .into();
+        for cid in [17, 16] {
+            let enclave = UnixTransport::new(&dir, cid);
+            heartbeat::signal(&enclave, Duration::from_secs(5)).unwrap();
+        }
+        for waiter in waiters {
+            waiter.join().unwrap().unwrap();
+        }
+    });
+}
+
+// Estimate boot timeouts from the image and memory sizes, then from observed boot times.
+#[test]
+fn heartbeat_boot_timeout() {
+    let dir = TempDir::new("heartbeat-boot");
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let enclave = UnixTransport::new(&dir, 16);
+
+    let policy = BootTimeoutPolicy {
+        base: Duration::from_secs(10),
+        per_image_gib: Duration::from_secs(20),
+        per_memory_gib: Duration::from_secs(2),
+        min: Duration::from_secs(5),
+        max: Duration::from_secs(120),
+        ..Default::default()
+    }
+    .with_calibration(BootCalibration::new());
+    let image = BootImage {
+        size: 512 << 20,
+        hash: "abc".to_string(),
+    };
+
+    // No boot times were observed, so the model is used and clamped.
+    assert_eq!(policy.timeout(&image, 4 << 30), Duration::from_secs(28));
+    assert_eq!(policy.timeout(&image, 100 << 30), Duration::from_secs(120));
+
+    let listener = ReadyListener::bind(parent).unwrap();
+    let started = Instant::now();
+    let t = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        heartbeat::signal(&enclave, Duration::from_secs(5))
+    });
+
+    // The boot time is measured from the enclave's start, not from the beginning of the wait.
+    thread::sleep(Duration::from_millis(200));
+    let elapsed = listener
+        .wait_boot(&policy, &image, 4 << 30, 16, started)
+        .unwrap();
+    t.join().unwrap().unwrap();
+
+    // The boot time was recorded, and the next timeout is predicted from it (clamped to the
+    // minimum).
+    let calibration = policy.calibration.clone().unwrap();
+    assert!(elapsed >= Duration::from_millis(200));
+    let observed = calibration.observed("abc");
+    assert_eq!(observed.len(), 1);
+    assert_eq!(observed[0].as_millis(), elapsed.as_millis());
+    assert_eq!(policy.timeout(&image, 4 << 30), Duration::from_secs(5));
+
+    calibration.record("abc", Duration::from_secs(40));
+    assert_eq!(policy.timeout(&image, 4 << 30), Duration::from_secs(80));
+
+    // Invalid margins fall back to the maximum timeout.
+    for margin in [-1.0, f64::NAN] {
+        let invalid = BootTimeoutPolicy {
+            margin,
+            ..policy.clone()
+        };
+        assert_eq!(invalid.timeout(&image, 4 << 30), Duration::from_secs(120));
+    }
+
+    // So does a model whose estimate overflows.
+    for overflowing in [
+        BootTimeoutPolicy {
+            base: Duration::MAX,
+            ..policy.clone()
+        },
+        BootTimeoutPolicy {
+            per_memory_gib: Duration::MAX,
+            ..policy.clone()
+        },
+    ] {
+        let image = BootImage {
+            hash: "uncalibrated".to_string(),
+            ..image.clone()
+        };
+        assert_eq!(
+            overflowing.timeout(&image, 4 << 30),
+            Duration::from_secs(120)
+        );
+    }
+
+    // Only the latest boot times are kept.
+    for _ in 0..CALIBRATION_SAMPLES {
+        calibration.record("abc", Duration::from_secs(4));
+    }
+    assert_eq!(policy.timeout(&image, 4 << 30), Duration::from_secs(8));
+
+    // Observations persist across processes.
+    let path = dir.join("calibration.json");
+    calibration.save(&path).unwrap();
+    let loaded = BootCalibration::load(&path).unwrap();
+    assert_eq!(loaded.observed("abc"), calibration.observed("abc"));
+    assert!(BootCalibration::load(dir.join("missing.json"))
+        .unwrap()
+        .observed("abc")
+        .is_empty());
+}
+
+// An enclave rejects an echo that is not the heartbeat.
+#[test]
+fn heartbeat_wrong_echo() {
+    let dir = TempDir::new("heartbeat-echo");
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let enclave = UnixTransport::new(&dir, 16);
+
+    let listener = parent
+        .bind(Addr::new(VMADDR_CID_PARENT, ENCLAVE_READY_VSOCK_PORT))
+        .unwrap();
+
+    let t = thread::spawn(move || {
+        let (mut stream, _) = parent.accept(&listener).unwrap();
+        let mut buf = [0u8];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], HEART_BEAT);
+        stream.write_all(&[0]).unwrap();
+    });
+
+    assert!(matches!(
+        heartbeat::signal(&enclave, Duration::from_secs(5)),
+        Err(HeartbeatError::Unexpected(0))
+    ));
+
+    t.join().unwrap();
+}
+
+// Read an enclave's console output from a simulated hypervisor that starts serving it late.
+#[test]
+fn console_read() {
+    let dir = TempDir::new("console");
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let hypervisor = UnixTransport::new(&dir, VMADDR_CID_HYPERVISOR);
+
+    let t = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(200));
+        let listener = hypervisor.bind(console::addr(16)).unwrap();
+        let (mut stream, _) = hypervisor.accept(&listener).unwrap();
+        stream
+            .write_all(b"Booting Linux on physical CPU 0x0\n")
+            .unwrap();
+    });
+
+    let mut console = console::connect(&parent, 16, Duration::from_secs(5)).unwrap();
+    let mut output = String::new();
+    console.read_to_string(&mut output).unwrap();
+    assert!(output.contains("Booting Linux"));
+
+    t.join().unwrap();
+}
@@ -0,0 +1,96 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::{
+    inbound::{EnclaveCid, InboundConfig, InboundForwarder, ProxyProtocol},
+    transport::{Addr, Transport, UnixTransport, VMADDR_CID_ANY, VMADDR_CID_PARENT},
+};
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+// Encode PROXY protocol headers for IPv4 and IPv6 connections.
+#[test]
+fn proxy_protocol_header() {
+    let src = "192.0.2.1:56324".parse().unwrap();
+    let dst = "192.0.2.2:443".parse().unwrap();
+
+    assert_eq!(
+        ProxyProtocol::V1.header(src, dst),
+        b"PROXY TCP4 192.0.2.1 192.0.2.2 56324 443\r\n"
+    );
+
+    let v2 = ProxyProtocol::V2.header(src, dst);
+    assert_eq!(&v2[..12], b"\r\n\r\n\0\r\nQUIT\n");
+    assert_eq!(&v2[12..16], &[0x21, 0x11, 0x00, 0x0c]);
+    assert_eq!(
+        &v2[16..],
+        &[192, 0, 2, 1, 192, 0, 2, 2, 0xdc, 0x04, 0x01, 0xbb]
+    );
+
+    let v2 = ProxyProtocol::V2.header("[2001:db8::1]:1000".parse().unwrap(), dst);
+    assert_eq!(&v2[12..16], &[0x21, 0x21, 0x00, 0x24]);
+    assert_eq!(v2.len(), 16 + 36);
+}
+
+// Forward a connection into an enclave that only becomes available after the connection is
+// accepted, using Unix sockets in place of vsock.
+#[test]
+fn inbound_forward() {
+    let dir = TempDir::new("inbound");
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let enclave = UnixTransport::new(&dir, 16);
+
+    // The enclave is not running yet.
+    let cid = EnclaveCid::default();
+
+    let mut config = InboundConfig::new(5000);
+    config.proxy_protocol = Some(ProxyProtocol::V1);
+    config.retry_delay = Duration::from_millis(20);
+    let forwarder = InboundForwarder::new(cid.clone(), config);
+    let shutdown = forwarder.shutdown_handle();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let local = listener.local_addr().unwrap();
+
+    thread::scope(|s| {
+        let t = s.spawn(|| forwarder.run_with(&listener, &parent));
+
+        let mut client = TcpStream::connect(local).unwrap();
+        client.write_all(b"request").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        // "Start" the enclave with CID 16, serving on port 5000.
+        let server = enclave.bind(Addr::new(VMADDR_CID_ANY, 5000)).unwrap();
+        cid.set(16);
+
+        let (mut stream, peer) = enclave.accept(&server).unwrap();
+        assert_eq!(peer.cid, VMADDR_CID_PARENT);
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        stream.write_all(b"response").unwrap();
+        drop(stream);
+
+        let client_addr = client.local_addr().unwrap();
+        assert_eq!(
+            received,
+            format!(
+                "PROXY TCP4 127.0.0.1 127.0.0.1 {} {}\r\nrequest",
+                client_addr.port(),
+                local.port()
+            )
+        );
+
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).unwrap();
+        assert_eq!(reply, "response");
+
+        shutdown.shutdown();
+        t.join().unwrap().unwrap();
+    });
+}
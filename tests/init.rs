@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use nitro_enclaves::init::{self, Command, InitError};
+use nix::sys::wait::WaitStatus;
+
+// Parse an application's command line and environment.
+#[test]
+fn command_parse() {
+    let command = Command::parse(
+        "/usr/bin/server\n--port\n8443\n",
+        "PATH=/usr/bin:/bin\nGREETING=a=b\n\n",
+    )
+    .unwrap();
+
+    assert_eq!(command.argv, ["/usr/bin/server", "--port", "8443"]);
+    assert_eq!(
+        command.env,
+        [
+            ("PATH".to_string(), "/usr/bin:/bin".to_string()),
+            ("GREETING".to_string(), "a=b".to_string()),
+        ]
+    );
+
+    assert!(matches!(
+        Command::parse("", ""),
+        Err(InitError::EmptyCommand)
+    ));
+    assert!(matches!(
+        Command::parse("/bin/sh", "NOVALUE"),
+        Err(InitError::InvalidEnv(_))
+    ));
+}
+
+// Run an application as a child process, and wait for it to exit.
+#[test]
+fn command_spawn() {
+    let command = Command::parse("/bin/sh\n-c\nexit $CODE\n", "CODE=3\n").unwrap();
+    let pid = command.spawn().unwrap();
+
+    assert!(matches!(
+        init::reap_until(pid).unwrap(),
+        WaitStatus::Exited(p, 3) if p.as_raw() as u32 == pid
+    ));
+
+    assert!(matches!(
+        Command::parse("/nonexistent", "").unwrap().spawn(),
+        Err(InitError::Exec(..))
+    ));
+}
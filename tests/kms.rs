@@ -0,0 +1,185 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::{
+    eif::Eif,
+    kms::{
+        ber_to_der,
+        sigv4::{self, SigningRequest},
+        Credentials, DecryptRequest, FakeKms, KmsClient, KmsConfig, KmsError,
+    },
+    nsm::FakeNsm,
+    transport::{Addr, Transport, UnixTransport, VMADDR_CID_PARENT},
+};
+use std::{
+    collections::BTreeMap,
+    thread,
+    time::{Duration, UNIX_EPOCH},
+};
+
+const PROXY_PORT: u32 = 8000;
+
+fn credentials() -> Credentials {
+    Credentials {
+        access_key_id: "AKIDEXAMPLE".to_string(),
+        secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+        session_token: Some("token".to_string()),
+    }
+}
+
+fn image(app: &[u8]) -> Eif {
+    Eif::new(
+        b"kernel".to_vec(),
+        &"console=ttyS0".parse().unwrap(),
+        vec![b"init".to_vec(), app.to_vec()],
+    )
+    .unwrap()
+}
+
+// Signature of the GET example of the AWS Signature Version 4 documentation.
+#[test]
+fn sigv4_example() {
+    let date = sigv4::amz_date(UNIX_EPOCH + Duration::from_secs(1440938160));
+    assert_eq!(date, "20150830T123600Z");
+
+    let authorization = sigv4::authorization(
+        &Credentials {
+            session_token: None,
+            ..credentials()
+        },
+        "us-east-1",
+        "iam",
+        &SigningRequest {
+            method: "GET",
+            path: "/",
+            query: "Action=ListUsers&Version=2010-05-08",
+            headers: &[
+                (
+                    "Content-Type",
+                    "application/x-www-form-urlencoded; charset=utf-8",
+                ),
+                ("Host", "iam.amazonaws.com"),
+                ("X-Amz-Date", &date),
+            ],
+            body: b"",
+        },
+    );
+
+    assert_eq!(
+        authorization,
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+         SignedHeaders=content-type;host;x-amz-date, \
+         Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+    );
+}
+
+// Reject BER lengths that overflow when added to the header length.
+#[test]
+fn ber_length_overflow() {
+    for ber in [
+        &[0x04, 0x88, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff][..],
+        &[0x30, 0x88, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff][..],
+    ] {
+        assert!(matches!(ber_to_der(ber), Err(KmsError::Envelope(_))));
+    }
+}
+
+// An enclave running the image allowed by the key policy decrypts a ciphertext.
+#[test]
+fn kms_decrypt() {
+    let dir = TempDir::new("kms");
+    let eif = image(b"app");
+    let nsm = FakeNsm::new(eif.pcrs()).unwrap();
+    let kms = FakeKms::new(nsm.root_certificate().to_vec(), credentials(), "eu-west-1");
+
+    let key_id = kms.create_key(eif.pcrs()).unwrap();
+    let context = BTreeMap::from([("service".to_string(), "db".to_string())]);
+    let ciphertext = kms.encrypt(&key_id, b"hunter2", &context).unwrap();
+
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let listener = parent
+        .bind(Addr::new(VMADDR_CID_PARENT, PROXY_PORT))
+        .unwrap();
+    let client = KmsClient::new(
+        UnixTransport::new(&dir, 16),
+        Addr::new(VMADDR_CID_PARENT, PROXY_PORT),
+        KmsConfig::new("eu-west-1", credentials()),
+    );
+
+    thread::scope(|s| {
+        s.spawn(|| kms.run(&listener).unwrap());
+
+        let request = DecryptRequest {
+            ciphertext_blob: ciphertext.clone(),
+            key_id: Some(key_id.clone()),
+            encryption_context: context.clone(),
+        };
+        assert_eq!(client.decrypt(&nsm, &request).unwrap(), b"hunter2");
+
+        // The encryption context is bound to the ciphertext.
+        let request = DecryptRequest {
+            encryption_context: BTreeMap::new(),
+            ..request
+        };
+        let err = client.decrypt(&nsm, &request).unwrap_err();
+        assert!(
+            matches!(err, KmsError::Service { status: 400, ref error_type, .. } if error_type == "InvalidCiphertextException")
+        );
+
+        kms.shutdown_handle().shutdown();
+    });
+}
+
+// The key policy denies decryption to an enclave running a different image, and to requests
+// signed with other credentials.
+#[test]
+fn kms_policy_denied() {
+    let dir = TempDir::new("kms-denied");
+    let nsm = FakeNsm::new(image(b"tampered app").pcrs()).unwrap();
+    let kms = FakeKms::new(nsm.root_certificate().to_vec(), credentials(), "eu-west-1");
+
+    let key_id = kms.create_key(image(b"app").pcrs()).unwrap();
+    let ciphertext = kms.encrypt(&key_id, b"hunter2", &BTreeMap::new()).unwrap();
+    let request = DecryptRequest {
+        ciphertext_blob: ciphertext,
+        ..Default::default()
+    };
+
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let listener = parent
+        .bind(Addr::new(VMADDR_CID_PARENT, PROXY_PORT))
+        .unwrap();
+    let proxy = Addr::new(VMADDR_CID_PARENT, PROXY_PORT);
+
+    thread::scope(|s| {
+        s.spawn(|| kms.run(&listener).unwrap());
+
+        let client = KmsClient::new(
+            UnixTransport::new(&dir, 16),
+            proxy,
+            KmsConfig::new("eu-west-1", credentials()),
+        );
+        let err = client.decrypt(&nsm, &request).unwrap_err();
+        assert!(
+            matches!(err, KmsError::Service { ref error_type, .. } if error_type == "AccessDeniedException")
+        );
+
+        let other = Credentials {
+            secret_access_key: "other".to_string(),
+            ..credentials()
+        };
+        let client = KmsClient::new(
+            UnixTransport::new(&dir, 16),
+            proxy,
+            KmsConfig::new("eu-west-1", other),
+        );
+        let err = client.decrypt(&nsm, &request).unwrap_err();
+        assert!(
+            matches!(err, KmsError::Service { ref error_type, .. } if error_type == "InvalidSignatureException")
+        );
+
+        kms.shutdown_handle().shutdown();
+    });
+}
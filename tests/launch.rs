@@ -1,36 +1,24 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use nitro_enclaves::{
-    launch::{ImageType, Launcher, MemoryInfo, PollTimeout, StartFlags},
-    Device,
-};
-use nix::{
-    poll::{poll, PollFd, PollFlags},
-    sys::{
-        socket::{connect, socket, AddressFamily, SockFlag, SockType, VsockAddr as NixVsockAddr},
-        time::{TimeVal, TimeValLike},
+    console,
+    heartbeat::ReadyListener,
+    launch::{
+        release_cid, reserve_cid, BootImage, BootTimeoutPolicy, CidPolicy, ImageType, IoctlError,
+        LaunchAborted, LaunchError, LaunchStep, LaunchTransaction, Launcher, MemoryInfo,
+        MemoryScrub, Rollback, StartFlags,
     },
-    unistd::read,
+    transport::VsockTransport,
+    Device,
 };
 use std::{
     fs::File,
-    io::{Read, Write},
-    os::fd::{AsRawFd, RawFd},
+    io::Read,
+    time::{Duration, Instant},
 };
-use vsock::{VsockAddr, VsockListener};
 
 const ENCLAVE_VM_SIZE_MIB: usize = 128;
 
-const ENCLAVE_READY_VSOCK_PORT: u32 = 9000;
-const CID_TO_CONSOLE_PORT_OFFSET: u32 = 10000;
-
-const VMADDR_CID_PARENT: u32 = 3;
-const VMADDR_CID_HYPERVISOR: u32 = 0;
-
-const SO_VM_SOCKETS_CONNECT_TIMEOUT: i32 = 6;
-
-const HEART_BEAT: u8 = 0xb7;
-
 // Create and start a nitro enclave using the library API.
 #[test]
 fn launch() {
@@ -43,79 +31,168 @@ fn launch() {
     // Open the test EIF file.
     let mut eif = File::open("tests/test_data/hello.eif").unwrap();
 
-    // Set enclave memory with provided EIF file and 128 MiB of memory.
-    let mem = MemoryInfo::new(ImageType::Eif(&mut eif), ENCLAVE_VM_SIZE_MIB);
+    // Set enclave memory with provided EIF file and 128 MiB of memory. Scrub the memory outside
+    // of the image and verify that it was cleared.
+    let mut mem = MemoryInfo::new(ImageType::Eif(&mut eif), ENCLAVE_VM_SIZE_MIB);
+    mem.scrub = MemoryScrub::Verify;
     launcher.set_memory(mem).unwrap();
 
+    // Verify all requested memory was allocated and the image was placed within it.
+    let layout = launcher.memory_layout().unwrap();
+    assert_eq!(layout.total_size(), (ENCLAVE_VM_SIZE_MIB << 20) as u64);
+    assert!(layout.image_range().end <= layout.total_size());
+
     // Add one vCPU to the enclave.
     launcher.add_vcpu(None).unwrap();
 
-    // Create a vsock listener to verify enclave kernel started.
-    let sockaddr = VsockAddr::new(VMADDR_CID_PARENT, ENCLAVE_READY_VSOCK_PORT);
-    let listener = VsockListener::bind(&sockaddr).unwrap();
+    // Create a vsock listener to verify enclave kernel started, and identify the image whose boot
+    // is awaited.
+    let listener = ReadyListener::bind(VsockTransport).unwrap();
+    let image = BootImage::from_file(&mut eif).unwrap();
 
     // Start the enclave (in debug mode) and get its CID.
+    let started = Instant::now();
     let cid: u32 = launcher
         .start(StartFlags::DEBUG, None)
         .unwrap()
         .try_into()
         .unwrap();
+    assert_eq!(launcher.cid(), Some(cid as u64));
 
-    // Given the enclave image and amount of memory (in bytes), calculate the poll timeout for the
-    // vsock listener.
-    let poll_timeout = PollTimeout::try_from((&eif, ENCLAVE_VM_SIZE_MIB << 20)).unwrap();
-
-    // Verify the enclave kernel has booted (setting the vsock timeout to the value calculated in
-    // poll_timeout).
-    enclave_check(listener, poll_timeout.into(), cid);
+    // Verify the enclave kernel has booted, waiting up to the timeout the default policy allows
+    // for the image and amount of memory (in bytes).
+    let policy = BootTimeoutPolicy::default();
+    let boot_time = listener
+        .wait_boot(&policy, &image, ENCLAVE_VM_SIZE_MIB << 20, cid, started)
+        .unwrap();
+    assert!(boot_time <= policy.timeout(&image, ENCLAVE_VM_SIZE_MIB << 20));
 
     // The enclave was started in debug mode. Listen for debug output on a vsock for the enclave.
-    listen(VMADDR_CID_HYPERVISOR, cid + CID_TO_CONSOLE_PORT_OFFSET);
-}
+    listen(cid);
 
-pub fn enclave_check(listener: VsockListener, poll_timeout_ms: libc::c_int, cid: u32) {
-    let mut poll_fds = [PollFd::new(listener.as_raw_fd(), PollFlags::POLLIN)];
-    let result = poll(&mut poll_fds, poll_timeout_ms);
-    if result == Ok(0) {
-        panic!("no pollfds have selected events");
-    } else if result != Ok(1) {
-        panic!("more than one pollfd has selected events");
-    }
+    // Terminate the enclave, verifying that its memory was scrubbed.
+    launcher.terminate().unwrap();
+}
 
-    let mut stream = listener.accept().unwrap();
+// Roll back a launch whose start fails after memory and vCPUs were set.
+#[test]
+fn launch_rollback() {
+    let device = Device::open().unwrap();
+    let mut eif = File::open("tests/test_data/hello.eif").unwrap();
 
-    // Wait until the other end is closed
-    let mut buf = [0u8];
-    let bytes = stream.0.read(&mut buf).unwrap();
+    // An invalid CID is rejected when starting the enclave.
+    let err = LaunchTransaction::begin(&device)
+        .and_then(|t| {
+            t.set_memory(MemoryInfo::new(
+                ImageType::Eif(&mut eif),
+                ENCLAVE_VM_SIZE_MIB,
+            ))
+        })
+        .and_then(|t| t.add_vcpu(None))
+        .and_then(|t| t.start(StartFlags::DEBUG, CidPolicy::Strict(1)))
+        .err()
+        .unwrap();
 
-    if bytes != 1 || buf[0] != HEART_BEAT {
-        panic!("enclave check produced wrong output");
-    }
+    let LaunchError::Aborted(aborted) = err else {
+        panic!("launch not rolled back: {err}");
+    };
+    assert_eq!(aborted.step, LaunchStep::Start);
+    assert!(matches!(aborted.error, LaunchError::InvalidCid(1)));
+    assert!(aborted.rollback.vm_closed);
+    assert_eq!(aborted.rollback.vcpus.len(), 1);
+    assert!(aborted.rollback.regions > 0);
+    assert_eq!(aborted.rollback.memory, (ENCLAVE_VM_SIZE_MIB << 20) as u64);
+    assert!(aborted.rollback.errors.is_empty());
+}
 
-    stream.0.write_all(&buf).unwrap();
+// Report the failed step and the resources released by a rollback.
+#[test]
+fn launch_aborted_report() {
+    let err = LaunchError::Aborted(Box::new(LaunchAborted {
+        step: LaunchStep::AddVcpu(Some(3)),
+        error: LaunchError::CidInUse(16),
+        rollback: Rollback {
+            vm_closed: true,
+            vcpus: vec![1, 2],
+            regions: 2,
+            memory: 256 << 20,
+            reserved_cpus: vec![1, 2, 3, 4],
+            ..Default::default()
+        },
+    }));
+
+    assert!(matches!(err.cause(), LaunchError::CidInUse(16)));
+    assert_eq!(
+        err.to_string(),
+        "unable to add vCPU 3: enclave CID 16 is already in use (rolled back: closed enclave \
+         VM, freed vCPUs 1-2, unmapped 2 memory region(s) (256 MiB), released reserved CPUs 1-4)"
+    );
+    assert_eq!(
+        Rollback::default().to_string(),
+        "nothing to release".to_string()
+    );
+}
 
-    if stream.1.cid() != cid {
-        panic!("CID mismatch");
+// Choose CIDs according to policies, skipping those reserved, without starting enclaves.
+#[test]
+fn cid_policy() {
+    let mut tried = Vec::new();
+
+    // Invalid CIDs are rejected before starting the enclave.
+    for cid in [1, u32::MAX as u64] {
+        let ret = CidPolicy::Strict(cid).start_with(|cid| {
+            tried.push(cid);
+            Ok(cid)
+        });
+        assert!(matches!(ret, Err(LaunchError::InvalidCid(c)) if c == cid));
     }
+    assert!(tried.is_empty());
+
+    // CIDs reserved by this process are skipped, and those rejected by the driver (in use by
+    // another process) are tried past.
+    assert!(reserve_cid(1000));
+    let cid = CidPolicy::Range(1000..=1002)
+        .start_with(|cid| {
+            tried.push(cid);
+            match cid {
+                1001 => Err(LaunchError::Ioctl(IoctlError::InvalidEnclaveCid)),
+                cid => Ok(cid),
+            }
+        })
+        .unwrap();
+    assert_eq!(cid, 1002);
+    assert_eq!(tried, [1001, 1002]);
+
+    // The chosen CID stays reserved until released, unlike the one that failed.
+    assert!(matches!(
+        CidPolicy::Strict(1002).start_with(Ok),
+        Err(LaunchError::CidInUse(1002))
+    ));
+    assert!(!reserve_cid(1002));
+    release_cid(1002);
+    assert_eq!(CidPolicy::Strict(1001).start_with(Ok).unwrap(), 1001);
+    release_cid(1001);
+    release_cid(1000);
+
+    // The driver may start the enclave with a different CID, which is reserved instead.
+    assert_eq!(
+        CidPolicy::Strict(1003).start_with(|_| Ok(1004)).unwrap(),
+        1004
+    );
+    assert!(reserve_cid(1003));
+    assert!(!reserve_cid(1004));
+    release_cid(1003);
+    release_cid(1004);
+
+    assert!(matches!(
+        CidPolicy::Range(1..=3).start_with(Ok),
+        Err(LaunchError::CidRangeExhausted)
+    ));
 }
 
-fn listen(cid: u32, port: u32) {
-    // Create a vsock to listen for enclave debug output.
-    let socket_fd = socket(
-        AddressFamily::Vsock,
-        SockType::Stream,
-        SockFlag::empty(),
-        None,
-    )
-    .unwrap();
-
-    let sockaddr = NixVsockAddr::new(cid, port);
-
-    // Set the vsock connection timeout.
-    vsock_timeout(socket_fd);
-
-    // Try to connect to the enclave.
-    connect(socket_fd, &sockaddr).unwrap();
+fn listen(cid: u32) {
+    // Connect to the enclave's console, retrying for up to 20 seconds.
+    let mut console = console::connect(&VsockTransport, cid, Duration::from_secs(20)).unwrap();
 
     // The testing EIF image prints Linux boot logs as debug output. One such message contains:
     //
@@ -125,12 +202,8 @@ fn listen(cid: u32, port: u32) {
     let mut boot_msg_found = false;
 
     let mut buf = [0u8; 512];
-    loop {
-        // Read debug output from vsock.
-        let ret = read(socket_fd, &mut buf);
-        let Ok(sz) = ret else {
-            break;
-        };
+    // Read debug output from the console.
+    while let Ok(sz) = console.read(&mut buf) {
         if sz != 0 {
             let msg = String::from_utf8(buf[..sz].to_vec()).unwrap();
             // Check if the Linux boot message is found in any of the output.
@@ -148,22 +221,3 @@ fn listen(cid: u32, port: u32) {
         panic!("Linux boot message not found from vsock output");
     }
 }
-
-fn vsock_timeout(socket_fd: RawFd) {
-    // Set the timeout to 20 seconds.
-    let timeval = TimeVal::milliseconds(20000);
-
-    let ret = unsafe {
-        libc::setsockopt(
-            socket_fd,
-            libc::AF_VSOCK,
-            SO_VM_SOCKETS_CONNECT_TIMEOUT,
-            &timeval as *const _ as *const libc::c_void,
-            size_of::<TimeVal>() as u32,
-        )
-    };
-
-    if ret != 0 {
-        panic!("error setting vsock timeout");
-    }
-}
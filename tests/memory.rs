@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::launch::{
+    HugePageCount, HugePagePlan, HugetlbfsBackend, ImageType, MemInitError, MemfdBackend,
+    MemoryBackend, MemoryInfo, MemoryLayout, UserMemoryRegions,
+};
+use std::{
+    fs::{self, File},
+    io,
+    sync::{Arc, Mutex},
+};
+
+const MIB: u64 = 1 << 20;
+
+// Verify the placement of an enclave image spanning multiple memory regions.
+#[test]
+fn layout_image_span() {
+    let mut layout = MemoryLayout::new(6 * MIB, 4 * MIB);
+    layout.push(2 * MIB, 2 * MIB, 0x1000_0000);
+    layout.push(8 * MIB, 2 * MIB, 0x2000_0000);
+    layout.push(2 * MIB, 2 * MIB, 0x3000_0000);
+    layout.push(16 * MIB, 16 * MIB, 0x4000_0000);
+
+    assert_eq!(layout.total_size(), 28 * MIB);
+    assert_eq!(layout.image_range(), 6 * MIB..10 * MIB);
+
+    let offsets: Vec<u64> = layout.regions.iter().map(|r| r.offset).collect();
+    assert_eq!(offsets, vec![0, 2 * MIB, 10 * MIB, 12 * MIB]);
+
+    let images: Vec<_> = layout.regions.iter().map(|r| r.image.clone()).collect();
+    assert_eq!(images, vec![None, Some(4 * MIB..8 * MIB), None, None]);
+}
+
+// Verify an image crossing a region boundary is split between both regions.
+#[test]
+fn layout_image_boundary() {
+    let mut layout = MemoryLayout::new(MIB, 2 * MIB);
+    layout.push(2 * MIB, 2 * MIB, 0x1000_0000);
+    layout.push(2 * MIB, 2 * MIB, 0x2000_0000);
+
+    assert_eq!(layout.regions[0].image, Some(MIB..2 * MIB));
+    assert_eq!(layout.regions[1].image, Some(0..MIB));
+}
+
+// Verify the fewest huge pages are chosen from those available.
+#[test]
+fn hugepage_plan() {
+    let available = [
+        HugePageCount {
+            page_size: 2 << 20,
+            count: 1024,
+        },
+        HugePageCount {
+            page_size: 1 << 30,
+            count: 2,
+        },
+    ];
+
+    let plan = HugePagePlan::new(3 << 30, &available).unwrap();
+    assert_eq!(
+        plan.0,
+        vec![
+            HugePageCount {
+                page_size: 1 << 30,
+                count: 2,
+            },
+            HugePageCount {
+                page_size: 2 << 20,
+                count: 512,
+            },
+        ]
+    );
+    assert_eq!(plan.pages(), 514);
+    assert_eq!(plan.size(), 3 << 30);
+    assert_eq!(plan.to_string(), "2 x 1024 MiB, 512 x 2 MiB");
+
+    // Not enough pages to cover the requested memory.
+    assert!(HugePagePlan::new(5 << 30, &available).is_none());
+}
+
+// A backend mapping ordinary memory in place of huge pages, recording the mappings requested.
+#[derive(Debug, Default)]
+struct RecordingBackend {
+    maps: Arc<Mutex<Vec<(usize, usize)>>>,
+}
+
+impl MemoryBackend for RecordingBackend {
+    fn available(&self) -> io::Result<Vec<HugePageCount>> {
+        Ok(vec![HugePageCount {
+            page_size: 2 << 20,
+            count: 8,
+        }])
+    }
+
+    fn map(&self, page_size: usize, size: usize) -> io::Result<*mut libc::c_void> {
+        if page_size != 2 << 20 {
+            return Err(io::ErrorKind::Unsupported.into());
+        }
+        self.maps.lock().unwrap().push((page_size, size));
+
+        map_anonymous(size)
+    }
+}
+
+fn map_anonymous(size: usize) -> io::Result<*mut libc::c_void> {
+    let addr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+
+    match addr {
+        libc::MAP_FAILED => Err(io::Error::last_os_error()),
+        addr => Ok(addr),
+    }
+}
+
+fn image_file(name: &str) -> (TempDir, File) {
+    let dir = TempDir::new(name);
+    let path = dir.join("image");
+    fs::write(&path, b"image").unwrap();
+    let file = File::open(&path).unwrap();
+
+    (dir, file)
+}
+
+// Allocate enclave memory through the backend chosen in the memory info, then release it.
+#[test]
+fn memory_backend_choice() {
+    let (_dir, mut image) = image_file("backend-choice");
+    let backend = RecordingBackend::default();
+    let maps = backend.maps.clone();
+
+    let mut info = MemoryInfo::new(ImageType::Eif(&mut image), 8);
+    info.backend = Box::new(backend);
+    let mut regions = UserMemoryRegions::new(&info).unwrap();
+
+    // The pages the backend has free are mapped at once.
+    assert_eq!(*maps.lock().unwrap(), [(2 << 20, 8 << 20)]);
+    let size: u64 = regions.inner_ref().iter().map(|r| r.size).sum();
+    assert_eq!(size, 8 << 20);
+
+    regions.release().unwrap();
+    assert!(regions.inner_ref().is_empty());
+
+    // Requested huge pages are used as is, and failing to map them is an error rather than a
+    // fallback to other page sizes.
+    maps.lock().unwrap().clear();
+    info.size_mib = 4;
+    info.hugepages = Some(HugePagePlan(vec![HugePageCount {
+        page_size: 2 << 20,
+        count: 2,
+    }]));
+    UserMemoryRegions::new(&info).unwrap();
+    assert_eq!(*maps.lock().unwrap(), [(2 << 20, 4 << 20)]);
+
+    // Requested huge pages must match the memory size.
+    for count in [1, 3] {
+        info.hugepages = Some(HugePagePlan(vec![HugePageCount {
+            page_size: 2 << 20,
+            count,
+        }]));
+        assert!(matches!(
+            UserMemoryRegions::new(&info),
+            Err(MemInitError::HugePagePlanMismatch { size, .. }) if size == 4 << 20
+        ));
+    }
+
+    info.size_mib = 1024;
+    info.hugepages = Some(HugePagePlan(vec![HugePageCount {
+        page_size: 1 << 30,
+        count: 1,
+    }]));
+    assert!(matches!(
+        UserMemoryRegions::new(&info),
+        Err(MemInitError::NoHugePageFound)
+    ));
+}
+
+// Map and release huge pages with memfd_create(2). Mapping is only checked, and the test
+// otherwise returns early, if the host has free huge pages.
+#[test]
+fn memory_backend_memfd() {
+    // Page sizes unknown to the kernel cannot be mapped.
+    assert!(MemfdBackend.map(3 << 20, 3 << 20).is_err());
+
+    let available = MemfdBackend.available().unwrap();
+    let Some(free) = available.iter().find(|a| a.count > 0) else {
+        return;
+    };
+
+    let addr = MemfdBackend.map(free.page_size, free.page_size).unwrap();
+    unsafe {
+        *(addr as *mut u8) = 0xff;
+        assert_eq!(libc::munmap(addr, free.page_size), 0);
+    }
+}
+
+// Map and release huge pages from a hugetlbfs mount. Mapping is only checked, and the test
+// otherwise returns early, if an accessible hugetlbfs mount has free pages.
+#[test]
+fn memory_backend_hugetlbfs() {
+    // Directories on other file systems are rejected.
+    let backend = HugetlbfsBackend::new(std::env::temp_dir());
+    assert_eq!(
+        backend.available().unwrap_err().kind(),
+        io::ErrorKind::InvalidInput
+    );
+    assert!(backend.map(2 << 20, 2 << 20).is_err());
+
+    let mounts = fs::read_to_string("/proc/mounts").unwrap();
+    let Some(mount) = mounts
+        .lines()
+        .map(|l| l.split(' ').collect::<Vec<_>>())
+        .find(|fields| fields.get(2) == Some(&"hugetlbfs"))
+    else {
+        return;
+    };
+
+    let backend = HugetlbfsBackend::new(mount[1]);
+    let Ok(available) = backend.available() else {
+        return;
+    };
+    let Some(free) = available.iter().find(|a| a.count > 0) else {
+        return;
+    };
+
+    let addr = backend.map(free.page_size, free.page_size).unwrap();
+    unsafe {
+        *(addr as *mut u8) = 0xff;
+        assert_eq!(libc::munmap(addr, free.page_size), 0);
+    }
+}
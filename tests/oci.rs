@@ -0,0 +1,228 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use flate2::{write::GzEncoder, Compression};
+use nitro_enclaves::{
+    cpio::{Entry, EntryKind},
+    eif::{KernelCmdline, SectionType},
+    oci::{Image, ImageConfig, OciError},
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{io::Write, path::Path};
+
+/// Build a layer of files (directories when without contents).
+fn layer(files: &[(&str, Option<&[u8]>)]) -> Vec<u8> {
+    let mut tar = tar::Builder::new(Vec::new());
+    for (path, data) in files {
+        let mut header = tar::Header::new_gnu();
+        match data {
+            Some(data) => {
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(data.len() as u64);
+                header.set_mode(0o644);
+                tar.append_data(&mut header, path, *data).unwrap();
+            }
+            None => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                header.set_mode(0o755);
+                tar.append_data(&mut header, path, &[][..]).unwrap();
+            }
+        }
+    }
+
+    tar.into_inner().unwrap()
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn config() -> Vec<u8> {
+    serde_json::to_vec(&json!({
+        "architecture": "amd64",
+        "os": "linux",
+        "config": {
+            "Entrypoint": ["/bin/server"],
+            "Cmd": ["--port", "8443"],
+            "Env": ["PATH=/bin"],
+            "WorkingDir": "/srv",
+        },
+    }))
+    .unwrap()
+}
+
+fn layers() -> Vec<Vec<u8>> {
+    vec![
+        gzip(&layer(&[
+            ("bin", None),
+            ("bin/server", Some(b"v1")),
+            ("etc", None),
+            ("etc/secret", Some(b"build secret")),
+            ("var/cache", None),
+            ("var/cache/old", Some(b"stale")),
+        ])),
+        layer(&[
+            ("bin/server", Some(b"v2")),
+            ("etc/.wh.secret", Some(b"")),
+            ("var/cache/new", Some(b"fresh")),
+            ("var/cache/.wh..wh..opq", Some(b"")),
+        ]),
+    ]
+}
+
+/// Write an OCI image layout with a multi-architecture index.
+fn oci_layout(dir: &Path) {
+    let blobs = dir.join("blobs/sha256");
+    std::fs::create_dir_all(&blobs).unwrap();
+    let add = |data: &[u8], media_type: &str| {
+        let hex: String = Sha256::digest(data)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        std::fs::write(blobs.join(&hex), data).unwrap();
+        json!({ "mediaType": media_type, "digest": format!("sha256:{hex}"), "size": data.len() })
+    };
+
+    let layers: Vec<_> = layers()
+        .iter()
+        .map(|l| add(l, "application/vnd.oci.image.layer.v1.tar+gzip"))
+        .collect();
+    let manifest = json!({
+        "schemaVersion": 2,
+        "config": add(&config(), "application/vnd.oci.image.config.v1+json"),
+        "layers": layers,
+    });
+    let mut manifest = add(
+        &serde_json::to_vec(&manifest).unwrap(),
+        "application/vnd.oci.image.manifest.v1+json",
+    );
+    manifest["platform"] = json!({ "architecture": "amd64", "os": "linux" });
+
+    let nested = json!({ "schemaVersion": 2, "manifests": [manifest] });
+    let nested = add(
+        &serde_json::to_vec(&nested).unwrap(),
+        "application/vnd.oci.image.index.v1+json",
+    );
+    let index = json!({ "schemaVersion": 2, "manifests": [nested] });
+    std::fs::write(dir.join("index.json"), serde_json::to_vec(&index).unwrap()).unwrap();
+    std::fs::write(dir.join("oci-layout"), br#"{"imageLayoutVersion":"1.0.0"}"#).unwrap();
+}
+
+/// Build a docker save archive.
+fn docker_archive() -> Vec<u8> {
+    let mut files = vec![
+        ("config.json".to_string(), config()),
+        (
+            "manifest.json".to_string(),
+            serde_json::to_vec(&json!([{
+                "Config": "config.json",
+                "RepoTags": ["server:latest"],
+                "Layers": ["a/layer.tar", "b/layer.tar"],
+            }]))
+            .unwrap(),
+        ),
+    ];
+    for (name, data) in ["a", "b"].iter().zip(layers()) {
+        files.push((format!("{name}/layer.tar"), data));
+    }
+
+    let mut tar = tar::Builder::new(Vec::new());
+    for (path, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, path, &data[..]).unwrap();
+    }
+
+    tar.into_inner().unwrap()
+}
+
+fn check(image: &Image) {
+    assert_eq!(
+        image.config,
+        ImageConfig {
+            entrypoint: vec!["/bin/server".to_string()],
+            cmd: vec!["--port".to_string(), "8443".to_string()],
+            env: vec!["PATH=/bin".to_string()],
+            working_dir: Some("/srv".to_string()),
+        }
+    );
+
+    let paths: Vec<&str> = image.rootfs.entries().map(|(path, _)| path).collect();
+    assert_eq!(
+        paths,
+        [
+            "bin",
+            "bin/server",
+            "etc",
+            "var",
+            "var/cache",
+            "var/cache/new"
+        ]
+    );
+    assert_eq!(
+        image.rootfs.get("bin/server").map(|e| &e.kind),
+        Some(&EntryKind::File(b"v2".to_vec()))
+    );
+
+    let ramdisk = image.ramdisk().unwrap();
+    assert_eq!(
+        ramdisk.get("cmd"),
+        Some(&Entry::file(b"/bin/server\n--port\n8443\n".to_vec(), 0o644))
+    );
+    assert_eq!(
+        ramdisk.get("env"),
+        Some(&Entry::file(b"PATH=/bin\n".to_vec(), 0o644))
+    );
+    assert!(ramdisk.get("rootfs/var/cache/new").is_some());
+
+    let eif = image
+        .to_eif(
+            b"kernel".to_vec(),
+            &KernelCmdline::nitro(),
+            b"init".to_vec(),
+        )
+        .unwrap();
+    let ramdisks: Vec<_> = eif.sections_of(SectionType::Ramdisk).collect();
+    assert_eq!(ramdisks.len(), 2);
+    assert_eq!(ramdisks[1].data, ramdisk.to_bytes().unwrap());
+}
+
+// Convert an OCI image layout, applying layers with whiteouts.
+#[test]
+fn oci_layout_image() {
+    let dir = TempDir::new("oci");
+    oci_layout(&dir);
+
+    check(&Image::load_for(&dir, "amd64").unwrap());
+    assert!(matches!(
+        Image::load_for(&dir, "arm64"),
+        Err(OciError::NoManifest(_))
+    ));
+
+    // Blobs are verified against their digests.
+    let index: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("index.json")).unwrap()).unwrap();
+    let digest = index["manifests"][0]["digest"].as_str().unwrap();
+    std::fs::write(
+        dir.join("blobs/sha256").join(&digest[7..]),
+        br#"{"manifests":[]}"#,
+    )
+    .unwrap();
+    assert!(matches!(
+        Image::load_for(&dir, "amd64"),
+        Err(OciError::DigestMismatch(_))
+    ));
+}
+
+// Convert a docker save archive.
+#[test]
+fn oci_docker_archive() {
+    check(&Image::from_tar(&docker_archive()[..], "amd64").unwrap());
+}
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::proxy::{Allowlist, AllowlistEntry, Proxy, ProxyConfig, ProxyError};
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, TcpListener},
+    os::unix::net::{UnixListener, UnixStream},
+    thread,
+};
+
+const CONFIG: &str = "
+allowlist:
+- {address: kms.us-east-1.amazonaws.com, port: 443}
+- {address: 127.0.0.1, port: 8080}
+";
+
+// Parse an allowlist in the vsock-proxy configuration format.
+#[test]
+fn allowlist_parse() {
+    let allowlist = Allowlist::from_yaml(CONFIG).unwrap();
+
+    assert_eq!(
+        allowlist.0,
+        vec![
+            AllowlistEntry {
+                address: "kms.us-east-1.amazonaws.com".to_string(),
+                port: 443,
+            },
+            AllowlistEntry {
+                address: "127.0.0.1".to_string(),
+                port: 8080,
+            },
+        ]
+    );
+
+    assert!(matches!(
+        Allowlist::from_yaml("allowlist:\n- {address: localhost}\n"),
+        Err(ProxyError::ConfigParse(_))
+    ));
+}
+
+// Ensure a proxy cannot be created for a remote that is not in the allowlist.
+#[test]
+fn proxy_not_allowed() {
+    let allowlist = Allowlist::from_yaml(CONFIG).unwrap();
+
+    let config = ProxyConfig::new("127.0.0.1", 8081);
+    assert!(matches!(
+        Proxy::new(config, &allowlist),
+        Err(ProxyError::NotAllowed(_, 8081))
+    ));
+}
+
+// Forward a connection through the proxy, using a Unix socket in place of vsock.
+#[test]
+fn proxy_forward() {
+    // A TCP server that echoes back data in upper case.
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = server.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (mut stream, _) = server.accept().unwrap();
+        let mut buf = String::new();
+        stream.read_to_string(&mut buf).unwrap();
+        stream.write_all(buf.to_uppercase().as_bytes()).unwrap();
+    });
+
+    let allowlist = Allowlist(vec![AllowlistEntry {
+        address: "127.0.0.1".to_string(),
+        port,
+    }]);
+    let proxy = Proxy::new(ProxyConfig::new("127.0.0.1", port), &allowlist).unwrap();
+    let shutdown = proxy.shutdown_handle();
+
+    let dir = TempDir::new("proxy");
+    let path = dir.join("vsock.sock");
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+
+    let proxy = thread::spawn(move || proxy.run(&listener));
+
+    let mut client = UnixStream::connect(&path).unwrap();
+    client.write_all(b"hello enclave").unwrap();
+    client.shutdown(Shutdown::Write).unwrap();
+
+    let mut reply = String::new();
+    client.read_to_string(&mut reply).unwrap();
+    assert_eq!(reply, "HELLO ENCLAVE");
+
+    shutdown.shutdown();
+    proxy.join().unwrap().unwrap();
+}
@@ -0,0 +1,166 @@
+// SPDX-License-Identifier: Apache-2.0
+
+use nitro_enclaves::{
+    attestation::{AttestationDoc, AttestationError, VerifyPolicy},
+    nsm::{AttestationRequest, Attester, FakeNsm},
+    ratls::{self, AttestedCertVerifier, KeyBinding, RaTlsError},
+};
+use rustls::{ClientConnection, ServerConnection, StreamOwned};
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+fn fake_nsm() -> FakeNsm {
+    FakeNsm::new(BTreeMap::from([(0, vec![1; 48]), (8, vec![8; 48])])).unwrap()
+}
+
+fn policy(nsm: &FakeNsm) -> VerifyPolicy {
+    let mut policy = VerifyPolicy::new(nsm.root_certificate().to_vec());
+    policy.pcrs.insert(0, vec![1; 48]);
+
+    policy
+}
+
+// Verify documents produced by a fake NSM against PCR, freshness, nonce and root policies.
+#[test]
+fn attestation_verify() {
+    let mut nsm = fake_nsm();
+    let request = AttestationRequest {
+        nonce: Some(b"nonce"),
+        ..Default::default()
+    };
+    let document = nsm.attest(&request).unwrap();
+    let now = SystemTime::now();
+
+    let mut policy = policy(&nsm);
+    policy.nonce = Some(b"nonce".to_vec());
+    let doc = AttestationDoc::verify(&document, &policy, now).unwrap();
+    assert_eq!(doc, AttestationDoc::parse(&document).unwrap());
+    assert_eq!(&doc.pcrs, nsm.pcrs());
+
+    let mut wrong = policy.clone();
+    wrong.pcrs.insert(8, vec![0; 48]);
+    assert!(matches!(
+        AttestationDoc::verify(&document, &wrong, now),
+        Err(AttestationError::PcrMismatch(8))
+    ));
+
+    let mut wrong = policy.clone();
+    wrong.nonce = Some(b"other".to_vec());
+    assert!(matches!(
+        AttestationDoc::verify(&document, &wrong, now),
+        Err(AttestationError::NonceMismatch)
+    ));
+
+    let wrong = VerifyPolicy::new(fake_nsm().root_certificate().to_vec());
+    assert!(matches!(
+        AttestationDoc::verify(&document, &wrong, now),
+        Err(AttestationError::UntrustedRoot)
+    ));
+
+    // Corrupt the end of the signature.
+    let mut corrupt = document.clone();
+    *corrupt.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        AttestationDoc::verify(&corrupt, &policy, now),
+        Err(AttestationError::Signature)
+    ));
+
+    nsm.set_timestamp(Some(now - Duration::from_secs(3600)));
+    let document = nsm.attest(&request).unwrap();
+    assert!(matches!(
+        AttestationDoc::verify(&document, &policy, now),
+        Err(AttestationError::Stale(_))
+    ));
+
+    // Documents dated in the future are only accepted within a small clock skew.
+    nsm.set_timestamp(Some(now + Duration::from_secs(5)));
+    let document = nsm.attest(&request).unwrap();
+    AttestationDoc::verify(&document, &policy, now).unwrap();
+
+    nsm.set_timestamp(Some(now + Duration::from_secs(3600)));
+    let document = nsm.attest(&request).unwrap();
+    assert!(matches!(
+        AttestationDoc::verify(&document, &policy, now),
+        Err(AttestationError::FutureTimestamp(_))
+    ));
+}
+
+// Complete a TLS handshake with a server presenting an attested certificate, and fail it when the
+// server's PCRs do not satisfy the client's policy.
+#[test]
+fn ratls_handshake() {
+    let nsm = fake_nsm();
+
+    let exchange = |policy: VerifyPolicy| -> io::Result<String> {
+        let server_config = Arc::new(ratls::server_config(&nsm, KeyBinding::PublicKey).unwrap());
+        let client_config = Arc::new(ratls::client_config(policy).unwrap());
+        let (client_sock, server_sock) = UnixStream::pair().unwrap();
+
+        let server = thread::spawn(move || -> io::Result<()> {
+            let conn = ServerConnection::new(server_config).unwrap();
+            let mut tls = StreamOwned::new(conn, server_sock);
+            let mut buf = [0u8; 4];
+            tls.read_exact(&mut buf)?;
+            assert_eq!(&buf, b"ping");
+            tls.write_all(b"pong")?;
+            tls.conn.send_close_notify();
+            tls.flush()
+        });
+
+        let conn = ClientConnection::new(client_config, "enclave".try_into().unwrap()).unwrap();
+        let mut tls = StreamOwned::new(conn, client_sock);
+        let result = tls.write_all(b"ping").and_then(|()| {
+            let mut reply = String::new();
+            tls.read_to_string(&mut reply)?;
+            Ok(reply)
+        });
+        drop(tls);
+        let _ = server.join().unwrap();
+
+        result
+    };
+
+    assert_eq!(exchange(policy(&nsm)).unwrap(), "pong");
+
+    let mut wrong = policy(&nsm);
+    wrong.pcrs.insert(0, vec![0; 48]);
+    let err = exchange(wrong).unwrap_err();
+    assert!(err.to_string().contains("PCR0"));
+}
+
+// Certificates may bind their key through the document's user data instead, and certificates
+// attesting a different key are rejected.
+#[test]
+fn ratls_key_binding() {
+    let nsm = fake_nsm();
+    let verifier = AttestedCertVerifier::new(policy(&nsm));
+    let now = SystemTime::now();
+
+    let (cert, _) = ratls::generate_certificate(&nsm, KeyBinding::UserData).unwrap();
+    let doc = verifier.verify(&cert, now).unwrap();
+    assert!(doc.public_key.is_none());
+    assert!(doc.user_data.is_some());
+
+    // An attester binding an unrelated key.
+    struct Replay(FakeNsm);
+    impl Attester for Replay {
+        fn attest(&self, _: &AttestationRequest) -> Result<Vec<u8>, nitro_enclaves::nsm::NsmError> {
+            self.0.attest(&AttestationRequest {
+                public_key: Some(b"someone else's key"),
+                ..Default::default()
+            })
+        }
+    }
+    let replay = Replay(nsm);
+    let (cert, _) = ratls::generate_certificate(&replay, KeyBinding::PublicKey).unwrap();
+    assert!(matches!(
+        verifier.verify(&cert, now),
+        Err(RaTlsError::KeyMismatch)
+    ));
+}
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::{
+    rpc::{Cbor, Client, Codec, Frame, Json, RpcError, Server},
+    transport::{Addr, Transport, UnixTransport, VMADDR_CID_ANY, VMADDR_CID_PARENT},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Sum {
+    values: Vec<u64>,
+}
+
+fn server<C: Codec>(codec: C) -> Server<C> {
+    let mut server = Server::new(codec);
+    server
+        .register("sum", |req: Sum| {
+            Ok::<_, String>(req.values.iter().sum::<u64>())
+        })
+        .register("sleep", |ms: u64| {
+            thread::sleep(Duration::from_millis(ms));
+            Ok::<_, String>(ms)
+        })
+        .register("fail", |_: ()| Err::<(), _>("no such secret"));
+
+    server
+}
+
+// Encode frames of each kind and decode them back.
+#[test]
+fn frame_roundtrip() {
+    let frames = [
+        Frame::Request {
+            id: 1,
+            method: "sum".to_string(),
+            body: vec![1, 2, 3],
+        },
+        Frame::Response {
+            id: u64::MAX,
+            body: Vec::new(),
+        },
+        Frame::Error {
+            id: 7,
+            message: "unknown method".to_string(),
+        },
+    ];
+
+    let mut buf = Vec::new();
+    for frame in &frames {
+        frame.write_to(&mut buf).unwrap();
+    }
+    assert_eq!(&buf[..4], &(9 + 2 + 3 + 3u32).to_be_bytes());
+
+    let mut r = &buf[..];
+    for frame in frames {
+        assert_eq!(Frame::read_from(&mut r).unwrap(), Some(frame));
+    }
+    assert_eq!(Frame::read_from(&mut r).unwrap(), None);
+
+    // A frame claiming to be larger than the maximum is rejected.
+    let mut r = &[0xff, 0xff, 0xff, 0xff][..];
+    assert!(matches!(
+        Frame::read_from(&mut r),
+        Err(RpcError::FrameTooLarge(_))
+    ));
+}
+
+// Call methods of a server in a simulated enclave from the parent, with each codec.
+#[test]
+fn rpc_call() {
+    fn check<C: Codec + Copy>(name: &str, codec: C) {
+        let dir = TempDir::new(name);
+        let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+        let enclave = UnixTransport::new(&dir, 16);
+
+        let server = server(codec);
+        let shutdown = server.shutdown_handle();
+        let listener = enclave.bind(Addr::new(VMADDR_CID_ANY, 7000)).unwrap();
+
+        thread::scope(|s| {
+            let t = s.spawn(|| server.run(&listener));
+
+            let client = Client::connect(&parent, 16, 7000, codec).unwrap();
+            let sum: u64 = client
+                .call(
+                    "sum",
+                    &Sum {
+                        values: vec![1, 2, 3],
+                    },
+                )
+                .unwrap();
+            assert_eq!(sum, 6);
+
+            let err = client.call::<_, ()>("fail", &()).unwrap_err();
+            assert!(matches!(err, RpcError::Remote(e) if e == "no such secret"));
+
+            let err = client.call::<_, ()>("missing", &()).unwrap_err();
+            assert!(matches!(err, RpcError::Remote(e) if e.contains("unknown method")));
+
+            // Requests that the handler cannot decode are rejected.
+            let err = client.call::<_, u64>("sum", &"nope").unwrap_err();
+            assert!(matches!(err, RpcError::Remote(e) if e.contains("invalid request")));
+
+            shutdown.shutdown();
+            t.join().unwrap().unwrap();
+        });
+    }
+
+    check("rpc-cbor", Cbor);
+    check("rpc-json", Json);
+}
+
+// Concurrent calls over one connection complete independently, and calls past their deadline
+// time out without affecting later calls.
+#[test]
+fn rpc_concurrent() {
+    let dir = TempDir::new("rpc-concurrent");
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let enclave = UnixTransport::new(&dir, 16);
+
+    let server = server(Cbor);
+    let shutdown = server.shutdown_handle();
+    let listener = enclave.bind(Addr::new(VMADDR_CID_ANY, 7000)).unwrap();
+
+    let client = Client::connect(&parent, 16, 7000, Cbor).unwrap();
+
+    thread::scope(|s| {
+        let t = s.spawn(|| server.run(&listener));
+
+        let (tx, rx) = mpsc::channel();
+        for ms in [300u64, 10] {
+            let (client, tx) = (&client, tx.clone());
+            s.spawn(move || {
+                let slept: u64 = client.call("sleep", &ms).unwrap();
+                tx.send(slept).unwrap();
+            });
+        }
+        assert_eq!(rx.recv().unwrap(), 10);
+        assert_eq!(rx.recv().unwrap(), 300);
+
+        let start = Instant::now();
+        let err = client
+            .call_timeout::<_, u64>("sleep", &500u64, Duration::from_millis(50))
+            .unwrap_err();
+        assert!(matches!(err, RpcError::Timeout));
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        let sum: u64 = client.call("sum", &Sum { values: vec![4] }).unwrap();
+        assert_eq!(sum, 4);
+
+        shutdown.shutdown();
+        t.join().unwrap().unwrap();
+    });
+}
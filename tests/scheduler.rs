@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::{
+    launch::{EnclaveConfig, HugePageCount},
+    scheduler::{format_cpu_list, parse_cpu_list, ResourceManager, SchedulerError, Topology},
+};
+use std::{collections::BTreeSet, fs, path::Path};
+
+fn write(root: &Path, path: &str, contents: &str) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+// A host with two NUMA nodes of eight hyperthreaded CPUs each, CPU n sharing its core with
+// CPU n + 8. Three cores of each node are in the NE CPU pool. Node 0 has 1 GiB of 2 MiB pages
+// free, and node 1 has 2 GiB of 1 GiB pages.
+fn sysfs(name: &str) -> TempDir {
+    let root = TempDir::new(name);
+
+    write(
+        &root,
+        "module/nitro_enclaves/parameters/ne_cpus",
+        "1-3,5-7,9-11,13-15\n",
+    );
+    for cpu in 0..16 {
+        let core = cpu % 8;
+        write(
+            &root,
+            &format!("devices/system/cpu/cpu{cpu}/topology/thread_siblings_list"),
+            &format!("{core},{}\n", core + 8),
+        );
+    }
+    write(&root, "devices/system/node/node0/cpulist", "0-3,8-11\n");
+    write(&root, "devices/system/node/node1/cpulist", "4-7,12-15\n");
+    write(
+        &root,
+        "devices/system/node/node0/hugepages/hugepages-2048kB/free_hugepages",
+        "512\n",
+    );
+    write(
+        &root,
+        "devices/system/node/node1/hugepages/hugepages-1048576kB/free_hugepages",
+        "2\n",
+    );
+
+    root
+}
+
+#[test]
+fn cpu_list() {
+    let cpus = parse_cpu_list("0-3,8,10-11\n").unwrap();
+    assert_eq!(cpus, BTreeSet::from([0, 1, 2, 3, 8, 10, 11]));
+    assert_eq!(
+        format_cpu_list(&cpus.into_iter().collect::<Vec<_>>()),
+        "0-3,8,10-11"
+    );
+
+    assert!(parse_cpu_list("3-1").is_none());
+    assert!(parse_cpu_list("a").is_none());
+}
+
+// Admit enclaves against full cores and memory of a single NUMA node, rejecting over-commits with
+// a capacity report.
+#[test]
+fn scheduler_admit() {
+    let root = sysfs("scheduler");
+    let topology = Topology::from_sysfs(&root).unwrap();
+    assert_eq!(topology.cores.len(), 6);
+    assert_eq!(topology.cores[0].cpus, vec![1, 9]);
+    assert_eq!(topology.cores[3].node, 1);
+    assert_eq!(
+        topology.memory[0],
+        (
+            0,
+            vec![HugePageCount {
+                page_size: 2 << 20,
+                count: 512
+            }]
+        )
+    );
+
+    let manager = ResourceManager::new(topology);
+
+    // Four vCPUs (two cores) and 512 MiB fit on node 0.
+    let mut config = EnclaveConfig::new("app.eif", 512, 4);
+    let first = manager.admit(&config).unwrap();
+    assert_eq!(first.cpu_ids(), &[1, 9, 2, 10]);
+    assert_eq!(first.numa_node(), 0);
+    first.apply(&mut config);
+    assert_eq!(config.cpu_ids, Some(vec![1, 9, 2, 10]));
+    assert_eq!(config.numa_node, Some(0));
+    assert_eq!(config.hugepages.as_ref(), Some(first.memory()));
+    assert_eq!(
+        first.memory().0,
+        [HugePageCount {
+            page_size: 2 << 20,
+            count: 256
+        }]
+    );
+
+    // Node 0 has a single core left, so the next enclave goes to node 1.
+    let second = manager
+        .admit(&EnclaveConfig::new("app.eif", 2048, 4))
+        .unwrap();
+    assert_eq!(second.cpu_ids(), &[5, 13, 6, 14]);
+    assert_eq!(second.numa_node(), 1);
+
+    // Hyperthreads cannot be split between enclaves.
+    assert!(matches!(
+        manager.admit(&EnclaveConfig::new("app.eif", 128, 3)),
+        Err(SchedulerError::NotFullCores {
+            cpus: 3,
+            threads_per_core: 2
+        })
+    ));
+
+    // Each node has a single core left.
+    let Err(SchedulerError::InsufficientCapacity(report)) =
+        manager.admit(&EnclaveConfig::new("app.eif", 128, 4))
+    else {
+        panic!("over-commit admitted");
+    };
+    assert_eq!(report.nodes[0].cpus, vec![3, 11]);
+    assert_eq!(report.nodes[1].memory_mib(), 0);
+    assert_eq!(
+        report.to_string(),
+        "requested 4 vCPU(s) and 128 MiB on a single NUMA node; available: \
+         node 0: 1 core(s) (CPUs 3,11), 512 MiB (256 x 2 MiB); \
+         node 1: 1 core(s) (CPUs 7,15), 0 MiB;"
+    );
+
+    // Specific CPUs must be free, full cores of a single node.
+    let mut explicit = EnclaveConfig::new("app.eif", 128, 0);
+    explicit.cpu_ids = Some(vec![5, 13]);
+    assert!(matches!(
+        manager.admit(&explicit),
+        Err(SchedulerError::CpuUnavailable(5))
+    ));
+    explicit.cpu_ids = Some(vec![3]);
+    assert!(matches!(
+        manager.admit(&explicit),
+        Err(SchedulerError::NotFullCores { .. })
+    ));
+    explicit.cpu_ids = Some(vec![3, 11, 7, 15]);
+    assert!(matches!(
+        manager.admit(&explicit),
+        Err(SchedulerError::MixedNumaNodes)
+    ));
+
+    // Resources are released once an enclave's reservation is dropped.
+    drop(first);
+    let capacity = manager.capacity();
+    assert_eq!(capacity[0].cores, 3);
+    assert_eq!(capacity[0].memory_mib(), 1024);
+    manager
+        .admit(&EnclaveConfig::new("app.eif", 1024, 6))
+        .unwrap();
+}
@@ -0,0 +1,342 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::{
+    heartbeat::LivenessResponder,
+    inbound::EnclaveCid,
+    launch::{CidPolicy, LaunchAborted, LaunchError, LaunchStep, Rollback},
+    supervisor::{
+        Backoff, Enclave, Event, ExitReason, LivenessCheck, Restart, RestartPolicy, Supervisor,
+        SupervisorError,
+    },
+    systemd::Notifier,
+    transport::{UnixTransport, VMADDR_CID_PARENT},
+};
+use std::{
+    os::{
+        fd::AsRawFd,
+        fd::RawFd,
+        unix::net::{UnixDatagram, UnixStream},
+    },
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Receiver,
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+// A simulated enclave, whose VM file descriptor hangs up once the peer end is dropped.
+struct FakeEnclave {
+    cid: u64,
+    vm: UnixStream,
+    terminated: Arc<AtomicBool>,
+}
+
+impl FakeEnclave {
+    fn new(cid: u64) -> (Self, UnixStream) {
+        let (vm, peer) = UnixStream::pair().unwrap();
+        let enclave = Self {
+            cid,
+            vm,
+            terminated: Arc::new(AtomicBool::new(false)),
+        };
+
+        (enclave, peer)
+    }
+}
+
+impl Enclave for FakeEnclave {
+    fn cid(&self) -> u64 {
+        self.cid
+    }
+
+    fn vm_fd(&self) -> RawFd {
+        self.vm.as_raw_fd()
+    }
+
+    fn terminate(self) -> Result<(), LaunchError> {
+        self.terminated.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+fn next(events: &Receiver<Event>) -> Event {
+    events.recv_timeout(Duration::from_secs(5)).unwrap()
+}
+
+// Restart an enclave that keeps exiting, reusing its CID if available, with exponential backoff
+// until the maximum number of retries.
+#[test]
+fn supervisor_restart() {
+    let policy = RestartPolicy {
+        restart: Restart::Always,
+        max_retries: Some(2),
+        backoff: Backoff {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(15),
+            multiplier: 2.0,
+        },
+        ..Default::default()
+    };
+    let supervisor = Supervisor::new(UnixTransport::new(std::env::temp_dir(), 3), policy);
+    let events = supervisor.subscribe();
+
+    // The previous CID is unavailable for the second relaunch.
+    let mut policies = Vec::new();
+    let err = supervisor
+        .run(CidPolicy::Preferred(Some(16)), |cid: CidPolicy| {
+            policies.push(cid.clone());
+            let cid = match cid {
+                CidPolicy::Strict(cid) if policies.len() == 3 => {
+                    return Err(LaunchError::Aborted(Box::new(LaunchAborted {
+                        step: LaunchStep::Start,
+                        error: LaunchError::CidInUse(cid),
+                        rollback: Rollback::default(),
+                    })));
+                }
+                CidPolicy::Strict(cid) => cid,
+                _ => 16 + policies.len() as u64,
+            };
+
+            // The enclave exits immediately.
+            Ok(FakeEnclave::new(cid).0)
+        })
+        .unwrap_err();
+
+    assert!(matches!(
+        err,
+        SupervisorError::RetriesExhausted {
+            retries: 2,
+            reason: ExitReason::Exited
+        }
+    ));
+    assert_eq!(
+        policies,
+        vec![
+            CidPolicy::Preferred(Some(16)),
+            CidPolicy::Strict(17),
+            CidPolicy::Strict(17),
+            CidPolicy::Preferred(Some(16)),
+        ]
+    );
+
+    let events: Vec<Event> = events.try_iter().collect();
+    let delays: Vec<Duration> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Restarting { delay, .. } => Some(*delay),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        delays,
+        vec![Duration::from_millis(10), Duration::from_millis(15)]
+    );
+
+    let cids: Vec<u64> = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Started { cid } => Some(*cid),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(cids, vec![17, 17, 20]);
+    assert!(matches!(events.last(), Some(Event::GaveUp { .. })));
+}
+
+// Restart an enclave whose application stops answering liveness probes, then stop supervising
+// it.
+#[test]
+fn supervisor_liveness() {
+    let dir = TempDir::new("supervisor-liveness");
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let enclave = UnixTransport::new(&dir, 16);
+
+    let policy = RestartPolicy {
+        restart: Restart::OnFailure,
+        backoff: Backoff {
+            initial: Duration::from_millis(10),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let enclave_cid = EnclaveCid::default();
+    let supervisor = Supervisor::new(parent, policy)
+        .with_liveness(LivenessCheck {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(100),
+            misses: 2,
+            grace_period: Duration::ZERO,
+        })
+        .with_enclave_cid(enclave_cid.clone());
+    let events = supervisor.subscribe();
+    let shutdown = supervisor.shutdown_handle();
+
+    let responder = LivenessResponder::bind(&enclave).unwrap();
+    let responder_shutdown = responder.shutdown_handle();
+
+    let mut launched = Vec::new();
+    let mut peers = Vec::new();
+    thread::scope(|s| {
+        s.spawn(|| responder.run().unwrap());
+
+        let t = s.spawn(|| {
+            supervisor.run(CidPolicy::Strict(16), |cid| {
+                assert_eq!(cid, CidPolicy::Strict(16));
+                let (enclave, peer) = FakeEnclave::new(16);
+                launched.push(enclave.terminated.clone());
+                peers.push(peer);
+                Ok(enclave)
+            })
+        });
+
+        assert!(matches!(next(&events), Event::Starting { attempt: 0 }));
+        assert!(matches!(next(&events), Event::Started { cid: 16 }));
+        assert_eq!(enclave_cid.get(), Some(16));
+
+        // The application answers probes for a while, then stops.
+        thread::sleep(Duration::from_millis(200));
+        responder_shutdown.shutdown();
+
+        assert!(matches!(
+            next(&events),
+            Event::Exited {
+                cid: Some(16),
+                reason: ExitReason::Unresponsive { misses: 2 }
+            }
+        ));
+        assert_eq!(enclave_cid.get(), None);
+        assert!(matches!(
+            next(&events),
+            Event::Restarting { restart: 1, .. }
+        ));
+        assert!(matches!(next(&events), Event::Starting { attempt: 1 }));
+        assert!(matches!(next(&events), Event::Started { cid: 16 }));
+
+        shutdown.shutdown();
+        assert!(matches!(next(&events), Event::Stopping { cid: 16 }));
+        assert!(matches!(next(&events), Event::Stopped));
+        assert_eq!(enclave_cid.get(), None);
+        t.join().unwrap().unwrap();
+    });
+
+    assert_eq!(launched.len(), 2);
+    assert!(launched.iter().all(|t| t.load(Ordering::SeqCst)));
+}
+
+// An enclave exiting by itself is not restarted under the on-failure policy.
+#[test]
+fn supervisor_on_failure_exit() {
+    let supervisor = Supervisor::new(
+        UnixTransport::new(std::env::temp_dir(), 3),
+        RestartPolicy::new(Restart::OnFailure),
+    );
+
+    let (enclave, peer) = FakeEnclave::new(16);
+    let mut enclave = Some(enclave);
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        drop(peer);
+    });
+
+    supervisor
+        .run(CidPolicy::default(), |_| Ok(enclave.take().unwrap()))
+        .unwrap();
+}
+
+// Receive the notifications sent to a fake systemd until none arrive for a while.
+fn notifications(socket: &UnixDatagram) -> Vec<String> {
+    let mut buf = [0; 4096];
+    let mut msgs = Vec::new();
+    while let Ok(n) = socket.recv(&mut buf) {
+        msgs.push(String::from_utf8(buf[..n].to_vec()).unwrap());
+    }
+
+    msgs
+}
+
+// Report the enclave's lifecycle to systemd, pinging its watchdog while the enclave answers
+// liveness probes.
+#[test]
+fn supervisor_notify() {
+    let dir = TempDir::new("supervisor-notify");
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let enclave = UnixTransport::new(&dir, 16);
+
+    let systemd = UnixDatagram::bind(dir.join("notify.sock")).unwrap();
+    systemd
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    let notifier = Notifier::new(dir.join("notify.sock").to_str().unwrap())
+        .unwrap()
+        .with_watchdog(Duration::from_millis(100));
+
+    // The application answers probes for a while, then stops and the enclave is not restarted.
+    let supervisor = Supervisor::new(parent, RestartPolicy::new(Restart::Never))
+        .with_liveness(LivenessCheck {
+            interval: Duration::from_millis(20),
+            timeout: Duration::from_millis(100),
+            misses: 1,
+            grace_period: Duration::ZERO,
+        })
+        .with_notifier(notifier.clone());
+    let responder = LivenessResponder::bind(&enclave).unwrap();
+    let responder_shutdown = responder.shutdown_handle();
+
+    let mut peers = Vec::new();
+    thread::scope(|s| {
+        s.spawn(|| responder.run().unwrap());
+        s.spawn(|| {
+            thread::sleep(Duration::from_millis(400));
+            responder_shutdown.shutdown();
+        });
+
+        let err = supervisor
+            .run(CidPolicy::Strict(16), |_| {
+                let (enclave, peer) = FakeEnclave::new(16);
+                peers.push(peer);
+                Ok(enclave)
+            })
+            .unwrap_err();
+        assert!(matches!(err, SupervisorError::Failed(_)));
+    });
+
+    let msgs = notifications(&systemd);
+    assert_eq!(msgs[0], "STATUS=Launching enclave (attempt 1)\n");
+    assert_eq!(msgs[1], "STATUS=Enclave running (CID 16)\n");
+    let pings = msgs.iter().filter(|m| *m == "WATCHDOG=1\n").count();
+    assert!(pings >= 2, "{msgs:?}");
+    assert!(msgs[2..2 + pings].iter().all(|m| m == "WATCHDOG=1\n"));
+    assert!(msgs[2 + pings].starts_with("STATUS=Enclave ended: "));
+    assert!(msgs[3 + pings].starts_with("STATUS=Enclave not restarted: "));
+
+    // Stopping the supervisor is reported before the enclave is terminated.
+    let supervisor = Supervisor::new(
+        UnixTransport::new(&dir, VMADDR_CID_PARENT),
+        RestartPolicy::default(),
+    )
+    .with_notifier(notifier);
+    let shutdown = supervisor.shutdown_handle();
+    let (enclave, _peer) = FakeEnclave::new(16);
+    let mut enclave = Some(enclave);
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        shutdown.shutdown();
+    });
+    supervisor
+        .run(CidPolicy::Strict(16), |_| Ok(enclave.take().unwrap()))
+        .unwrap();
+
+    let msgs = notifications(&systemd);
+    assert_eq!(
+        msgs[msgs.len() - 2..],
+        [
+            "STOPPING=1\nSTATUS=Stopping enclave (CID 16)\n",
+            "STATUS=Enclave stopped\n"
+        ]
+    );
+}
@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::systemd::{Notifier, State, SystemdError};
+use std::{
+    collections::HashMap,
+    os::{
+        linux::net::SocketAddrExt,
+        unix::net::{SocketAddr, UnixDatagram},
+    },
+    time::Duration,
+};
+
+// Send notifications to a fake systemd listening on a path or an abstract socket.
+#[test]
+fn systemd_notify() {
+    let dir = TempDir::new("notify");
+    let path = dir.join("notify.sock");
+    let systemd = UnixDatagram::bind(&path).unwrap();
+    let mut buf = [0; 256];
+
+    let notifier = Notifier::new(path.to_str().unwrap()).unwrap();
+    notifier
+        .notify(&[State::Ready, State::Status("booted\nin 2s".to_string())])
+        .unwrap();
+    let n = systemd.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"READY=1\nSTATUS=booted in 2s\n");
+
+    let name = format!("nitro-enclaves-notify-{}", std::process::id());
+    let abstract_systemd =
+        UnixDatagram::bind_addr(&SocketAddr::from_abstract_name(&name).unwrap()).unwrap();
+    Notifier::new(&format!("@{name}"))
+        .unwrap()
+        .notify(&[State::Stopping])
+        .unwrap();
+    let n = abstract_systemd.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"STOPPING=1\n");
+
+    assert!(matches!(
+        Notifier::new("notify.sock"),
+        Err(SystemdError::InvalidSocket(_))
+    ));
+}
+
+// Read the notification socket and watchdog timeout set by systemd.
+#[test]
+fn systemd_vars() {
+    let pid = std::process::id().to_string();
+    let vars = |vars: &[(&str, &str)]| {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Notifier::from_vars(move |name| vars.get(name).cloned())
+    };
+
+    assert!(vars(&[]).unwrap().is_none());
+
+    let notifier = vars(&[
+        ("NOTIFY_SOCKET", "/run/systemd/notify"),
+        ("WATCHDOG_USEC", "2000000"),
+        ("WATCHDOG_PID", &pid),
+    ])
+    .unwrap()
+    .unwrap();
+    assert_eq!(notifier.watchdog(), Some(Duration::from_secs(2)));
+
+    // The watchdog is meant for another process.
+    let notifier = vars(&[
+        ("NOTIFY_SOCKET", "/run/systemd/notify"),
+        ("WATCHDOG_USEC", "2000000"),
+        ("WATCHDOG_PID", "1"),
+    ])
+    .unwrap()
+    .unwrap();
+    assert_eq!(notifier.watchdog(), None);
+
+    assert!(matches!(
+        vars(&[("NOTIFY_SOCKET", "notify")]),
+        Err(SystemdError::InvalidSocket(_))
+    ));
+}
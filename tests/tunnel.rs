@@ -0,0 +1,82 @@
+// SPDX-License-Identifier: Apache-2.0
+
+mod common;
+
+use common::TempDir;
+use nitro_enclaves::{
+    transport::{Addr, Transport, UnixTransport, VMADDR_CID_ANY},
+    tunnel::{Tunnel, TunnelError, TunnelMapping, VMADDR_CID_PARENT},
+};
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+// Parse mappings of the form LOCAL_ADDR=[CID:]PORT.
+#[test]
+fn mapping_parse() {
+    let m: TunnelMapping = "127.0.0.1:443=8000".parse().unwrap();
+    assert_eq!(
+        m,
+        TunnelMapping::new("127.0.0.1:443".parse().unwrap(), 8000)
+    );
+    assert_eq!(m.cid, VMADDR_CID_PARENT);
+
+    let m: TunnelMapping = "[::1]:80=16:8080".parse().unwrap();
+    assert_eq!(m.local, "[::1]:80".parse().unwrap());
+    assert_eq!((m.cid, m.port), (16, 8080));
+
+    assert!(matches!(
+        "127.0.0.1:443".parse::<TunnelMapping>(),
+        Err(TunnelError::InvalidMapping(_))
+    ));
+}
+
+// Forward a local TCP connection through the tunnel, using Unix sockets in place of vsock.
+#[test]
+fn tunnel_forward() {
+    let dir = TempDir::new("tunnel");
+    let parent = UnixTransport::new(&dir, VMADDR_CID_PARENT);
+    let enclave = UnixTransport::new(&dir, 16);
+
+    // A parent server on vsock port 8000 that echoes back data in upper case.
+    let server = parent.bind(Addr::new(VMADDR_CID_ANY, 8000)).unwrap();
+    thread::spawn(move || {
+        let (mut stream, peer) = parent.accept(&server).unwrap();
+        assert_eq!(peer.cid, 16);
+        let mut buf = String::new();
+        stream.read_to_string(&mut buf).unwrap();
+        stream.write_all(buf.to_uppercase().as_bytes()).unwrap();
+    });
+
+    // Reserve a free local port for the tunnel.
+    let local = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let tunnel = Tunnel::new(vec![TunnelMapping::new(local, 8000)], None);
+    let shutdown = tunnel.shutdown_handle();
+
+    thread::scope(|s| {
+        let t = s.spawn(|| tunnel.run_with(&enclave));
+
+        let mut client = loop {
+            match TcpStream::connect(local) {
+                Ok(client) => break client,
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+        client.write_all(b"hello parent").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).unwrap();
+        assert_eq!(reply, "HELLO PARENT");
+
+        shutdown.shutdown();
+        t.join().unwrap().unwrap();
+    });
+}